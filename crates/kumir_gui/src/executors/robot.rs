@@ -3,6 +3,7 @@ use std::sync::atomic::AtomicBool;
 
 use crate::executors::Executor;
 use egui::{Pos2, Vec2 as eguiVec2};
+use kumir_lang::messages::Message;
 use kumir_runtime::FuncResult;
 use log::info;
 use vello::Scene;
@@ -27,7 +28,7 @@ pub struct RobotEditingState {
     pub deleting_columns_mode: ColumnsMode,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Hovered {
     Cell {
         min: Point,
@@ -54,7 +55,7 @@ pub enum Hovered {
     None,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct FieldParameters {
     fill_color: Color,
     field_color: Color,
@@ -67,7 +68,16 @@ struct FieldParameters {
     hovered_color: Color,
 }
 
-#[derive(Debug)]
+/// Move or painting done by a program, kept to take it back
+#[derive(Debug, Clone, Copy)]
+struct RobotAction {
+    from: (usize, usize),
+    to: (usize, usize),
+    /// Cell painted, with whether it was painted before
+    painted: Option<(usize, usize, bool)>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Robot {
     width: usize,
     height: usize,
@@ -86,6 +96,11 @@ pub struct Robot {
     field_parameters: FieldParameters,
     scene_is_dirty: Arc<AtomicBool>,
     pub editing_state: RobotEditingState,
+    /// Whether the running program is traced and its actions are kept
+    recording: bool,
+    done: Vec<RobotAction>,
+    /// Actions taken back, the last one is the first to repeat
+    undone: Vec<RobotAction>,
 }
 
 impl Robot {
@@ -120,6 +135,9 @@ impl Robot {
                 borders
             },
             scene_is_dirty,
+            recording: false,
+            done: Vec::new(),
+            undone: Vec::new(),
             colored: vec![vec![false; height]; width],
             x: 0,
             y: 0,
@@ -510,43 +528,123 @@ impl Robot {
     }
 
     pub fn move_right(&mut self) -> FuncResult<()> {
-        if self.free_right().unwrap().unwrap() && self.move_robot(1, 0) {
+        let from = (self.x, self.y);
+        let result = if self.free_right().unwrap().unwrap() && self.move_robot(1, 0) {
             Ok(None)
         } else {
-            Err("Robot is destroyed: there is a wall on the right".to_string())
-        }
+            Err(Message::RobotWallRight.into())
+        };
+        self.record(from, None);
+        result
     }
 
     pub fn move_left(&mut self) -> FuncResult<()> {
-        if self.free_left().unwrap().unwrap() && self.move_robot(-1, 0) {
+        let from = (self.x, self.y);
+        let result = if self.free_left().unwrap().unwrap() && self.move_robot(-1, 0) {
             Ok(None)
         } else {
-            Err("Robot is destroyed: there is a wall on the left".to_string())
-        }
+            Err(Message::RobotWallLeft.into())
+        };
+        self.record(from, None);
+        result
     }
 
     pub fn move_up(&mut self) -> FuncResult<()> {
-        if self.free_above().unwrap().unwrap() && self.move_robot(0, -1) {
+        let from = (self.x, self.y);
+        let result = if self.free_above().unwrap().unwrap() && self.move_robot(0, -1) {
             Ok(None)
         } else {
-            Err("Robot is destroyed: there is a wall above it".to_string())
-        }
+            Err(Message::RobotWallAbove.into())
+        };
+        self.record(from, None);
+        result
     }
 
     pub fn move_down(&mut self) -> FuncResult<()> {
-        if self.free_below().unwrap().unwrap() && self.move_robot(0, 1) {
+        let from = (self.x, self.y);
+        let result = if self.free_below().unwrap().unwrap() && self.move_robot(0, 1) {
             Ok(None)
         } else {
-            Err("Robot is destroyed: there is a wall below it".to_string())
-        }
+            Err(Message::RobotWallBelow.into())
+        };
+        self.record(from, None);
+        result
     }
 
     pub fn paint(&mut self) -> FuncResult<()> {
         self.scene_dirty();
+        let painted = (self.x, self.y, self.colored[self.x][self.y]);
         self.colored[self.x][self.y] = true;
+        self.record((self.x, self.y), Some(painted));
         Ok(None)
     }
 
+    /// Field and robot as they are, to run a program aside without drawing
+    /// it or changing this field
+    pub fn headless_copy(&self) -> Robot {
+        Robot {
+            scene_is_dirty: Arc::default(),
+            recording: false,
+            done: Vec::new(),
+            undone: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Forgets the actions of the last run and keeps the new run's ones
+    /// only if it is traced to step back
+    pub fn start_run(&mut self, recording: bool) {
+        self.recording = recording;
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    fn record(&mut self, from: (usize, usize), painted: Option<(usize, usize, bool)>) {
+        if !self.recording {
+            return;
+        }
+        self.undone.clear();
+        self.done.push(RobotAction {
+            from,
+            to: (self.x, self.y),
+            painted,
+        });
+    }
+
+    /// Takes back the last `actions` moves and paintings
+    pub fn undo(&mut self, actions: usize) {
+        for _ in 0..actions {
+            let Some(action) = self.done.pop() else {
+                break;
+            };
+            (self.x, self.y) = action.from;
+            if let Some((x, y, was_painted)) = action.painted
+                && let Some(cell) = self.colored.get_mut(x).and_then(|column| column.get_mut(y))
+            {
+                *cell = was_painted;
+            }
+            self.undone.push(action);
+        }
+        self.scene_dirty();
+    }
+
+    /// Repeats `actions` moves and paintings taken back by [`Robot::undo`]
+    pub fn redo(&mut self, actions: usize) {
+        for _ in 0..actions {
+            let Some(action) = self.undone.pop() else {
+                break;
+            };
+            (self.x, self.y) = action.to;
+            if let Some((x, y, _)) = action.painted
+                && let Some(cell) = self.colored.get_mut(x).and_then(|column| column.get_mut(y))
+            {
+                *cell = true;
+            }
+            self.done.push(action);
+        }
+        self.scene_dirty();
+    }
+
     pub fn free_right(&self) -> FuncResult<bool> {
         Ok(Some(!self.vertical_borders[self.x + 1][self.y]))
     }
@@ -862,3 +960,92 @@ impl Executor for Robot {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn robot(recording: bool) -> Robot {
+        let mut robot = Robot::new(3, 3, 10.0, 0.0, 0.0, Arc::default());
+        robot.start_run(recording);
+        robot
+    }
+
+    #[test]
+    fn undo_and_redo_moves_and_paintings() {
+        let mut robot = robot(true);
+        robot.paint().unwrap();
+        robot.move_right().unwrap();
+        robot.move_down().unwrap();
+        robot.paint().unwrap();
+        assert_eq!((robot.x, robot.y), (1, 1));
+
+        robot.undo(1);
+        assert_eq!((robot.x, robot.y), (1, 1));
+        assert!(!robot.colored[1][1]);
+        robot.undo(2);
+        assert_eq!((robot.x, robot.y), (0, 0));
+        assert!(robot.colored[0][0]);
+        robot.undo(10);
+        assert_eq!((robot.x, robot.y), (0, 0));
+        assert!(!robot.colored[0][0]);
+
+        robot.redo(3);
+        assert_eq!((robot.x, robot.y), (1, 1));
+        assert!(robot.colored[0][0]);
+        assert!(!robot.colored[1][1]);
+        robot.redo(10);
+        assert!(robot.colored[1][1]);
+    }
+
+    #[test]
+    fn painting_a_painted_cell_is_undone_to_painted() {
+        let mut robot = robot(true);
+        robot.paint().unwrap();
+        robot.paint().unwrap();
+        robot.undo(1);
+        assert!(robot.colored[0][0]);
+    }
+
+    #[test]
+    fn failed_move_is_one_action() {
+        let mut robot = robot(true);
+        assert!(robot.move_left().is_err());
+        robot.move_right().unwrap();
+        robot.undo(1);
+        assert_eq!((robot.x, robot.y), (0, 0));
+        assert_eq!(robot.done.len(), 1);
+    }
+
+    #[test]
+    fn new_action_drops_the_undone_ones() {
+        let mut robot = robot(true);
+        robot.move_right().unwrap();
+        robot.undo(1);
+        robot.move_down().unwrap();
+        robot.redo(1);
+        assert_eq!((robot.x, robot.y), (0, 1));
+    }
+
+    #[test]
+    fn untraced_run_keeps_nothing() {
+        let mut robot = robot(false);
+        robot.move_right().unwrap();
+        robot.paint().unwrap();
+        assert!(robot.done.is_empty());
+        robot.undo(2);
+        assert_eq!((robot.x, robot.y), (1, 0));
+        assert!(robot.colored[1][0]);
+    }
+
+    #[test]
+    fn run_forgets_the_last_one() {
+        let mut robot = robot(true);
+        robot.move_right().unwrap();
+        robot.undo(1);
+        robot.start_run(true);
+        assert!(robot.done.is_empty());
+        robot.redo(1);
+        assert_eq!((robot.x, robot.y), (0, 0));
+    }
+}
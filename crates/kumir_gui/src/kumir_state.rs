@@ -1,6 +1,10 @@
 use crate::executors::robot::Robot;
 use crate::executors::{Executor, NoneSelected};
+use crate::widgets::flowchart::FlowchartState;
+use crate::widgets::terminal::TerminalState;
+use crate::widgets::trace_table::TraceTableState;
 use egui::{Pos2, Vec2};
+use kumir_runtime::control::RuntimeControl;
 
 use std::fmt;
 use std::sync::atomic::AtomicBool;
@@ -191,7 +195,14 @@ pub struct KumirState {
     pub modes: ModesStored,
     // pub visual_mode: VisualMode,
     pub min_point: Pos2,
-    pub kill_flag: Arc<AtomicBool>,
+    /// Control of the running program, `None` when nothing runs
+    pub runtime_control: Arc<Mutex<Option<RuntimeControl>>>,
+    /// Output of the last run program
+    pub terminal: Arc<Mutex<TerminalState>>,
+    /// Flowcharts of the program in the IDE, following the running one
+    pub flowchart: Arc<Mutex<FlowchartState>>,
+    /// Trace table of the program in the IDE
+    pub trace_table: TraceTableState,
     pub scene_is_dirty: Arc<AtomicBool>,
 }
 
@@ -226,7 +237,10 @@ impl KumirState {
             },
             // visual_mode: VisualMode::Dark,
             min_point: Pos2::new(10.0, 85.0),
-            kill_flag: Default::default(),
+            runtime_control: Default::default(),
+            terminal: Default::default(),
+            flowchart: Default::default(),
+            trace_table: Default::default(),
             scene_is_dirty,
         }
     }
@@ -1,7 +1,9 @@
-use kumir_runtime::{FuncResult, RobotRequirements, RuntimeRequirementsTrait};
-use log::info;
-use std::time::Duration;
-use wasm_thread as thread;
+use std::path::PathBuf;
+
+use kumir_runtime::{
+    FuncResult, MemoryFs, NativeFs, RobotRequirements, RuntimeRequirementsTrait, VirtualFs,
+};
+use log::{error, info};
 
 use crate::kumir_state::Modes;
 
@@ -12,7 +14,7 @@ macro_rules! call_method_in_enum {
                 let mut obj = executor.lock().unwrap();
                 (&mut obj).$method_name()
             }
-            _ => Err("Выбранный исполнитель отличается от загружаемого".to_string()),
+            _ => Err("Выбранный исполнитель отличается от загружаемого".into()),
         }
     };
     ($object:expr, $class:ident::$variant:ident, $method_name:ident, $($arg:expr),+) => {
@@ -28,42 +30,60 @@ macro_rules! call_method_in_enum {
 
 pub struct GuiRuntimeRequirements {
     pub mode: Modes,
-    pub sleep_duration: Duration,
+    /// Directory shared with the `Файлы` actor, files are kept in memory
+    /// without one
+    pub files_root: Option<PathBuf>,
+    /// Seed to replay a run with, a new one every run without it
+    pub seed: Option<u64>,
+    /// Whether the program can step back while paused
+    pub record_trace: bool,
 }
 
 impl RuntimeRequirementsTrait for GuiRuntimeRequirements {
     fn println(&self, message: &str) {
         info!("{message}")
     }
+
+    fn file_system(&self) -> Box<dyn VirtualFs> {
+        let Some(root) = &self.files_root else {
+            return Box::new(MemoryFs::default());
+        };
+        match NativeFs::new(root) {
+            Ok(fs) => Box::new(fs),
+            Err(err) => {
+                error!("Can't share {root:?} with the program: {err}");
+                Box::new(MemoryFs::default())
+            }
+        }
+    }
+
+    fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    fn record_trace(&self) -> bool {
+        self.record_trace
+    }
 }
 
 impl RobotRequirements for GuiRuntimeRequirements {
     fn move_up(&self) -> FuncResult<()> {
-        let res = call_method_in_enum!(self.mode.clone(), Modes::Robot, move_up);
-        thread::sleep(self.sleep_duration);
-        res
+        call_method_in_enum!(self.mode.clone(), Modes::Robot, move_up)
     }
 
     fn move_down(&self) -> FuncResult<()> {
-        let res = call_method_in_enum!(self.mode.clone(), Modes::Robot, move_down);
-        thread::sleep(self.sleep_duration);
-        res
+        call_method_in_enum!(self.mode.clone(), Modes::Robot, move_down)
     }
 
     fn move_left(&self) -> FuncResult<()> {
-        let res = call_method_in_enum!(self.mode.clone(), Modes::Robot, move_left);
-        thread::sleep(self.sleep_duration);
-        res
+        call_method_in_enum!(self.mode.clone(), Modes::Robot, move_left)
     }
 
     fn move_right(&self) -> FuncResult<()> {
-        let res = call_method_in_enum!(self.mode.clone(), Modes::Robot, move_right);
-        thread::sleep(self.sleep_duration);
-        res
+        call_method_in_enum!(self.mode.clone(), Modes::Robot, move_right)
     }
 
     fn paint(&self) -> FuncResult<()> {
-        thread::sleep(self.sleep_duration);
         call_method_in_enum!(self.mode.clone(), Modes::Robot, paint)
     }
 
@@ -106,4 +126,16 @@ impl RobotRequirements for GuiRuntimeRequirements {
     fn not_colored(&self) -> FuncResult<bool> {
         call_method_in_enum!(self.mode.clone(), Modes::Robot, not_colored)
     }
+
+    fn undo(&self, actions: usize) {
+        if let Modes::Robot(robot) = &self.mode {
+            robot.lock().unwrap().undo(actions);
+        }
+    }
+
+    fn redo(&self, actions: usize) {
+        if let Modes::Robot(robot) = &self.mode {
+            robot.lock().unwrap().redo(actions);
+        }
+    }
 }
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use egui::{
+    Align2, Color32, ComboBox, CornerRadius, FontId, Painter, Pos2, Response, ScrollArea, Sense,
+    Stroke, StrokeKind, Ui, Vec2, Widget,
+};
+use kumir_lang::{
+    flowchart::{Flowchart, Shape},
+    interpreter::Interpreter,
+    lexer::Span,
+};
+use kumir_runtime::events::RuntimeEvent;
+
+/// Flowcharts of the program in the IDE and the statement the program is
+/// paused at
+#[derive(Default)]
+pub struct FlowchartState {
+    pub charts: Vec<Flowchart>,
+    /// Why the program couldn't be parsed
+    pub error: Option<String>,
+    pub current: Option<Span>,
+    /// Index of the chart shown
+    pub selected: usize,
+}
+
+impl FlowchartState {
+    pub fn build(&mut self, code: &str) {
+        match Interpreter::new_from_string(code, Arc::default()) {
+            Ok(interpreter) => {
+                self.charts = interpreter.flowcharts();
+                self.error = None;
+            }
+            Err(err) => {
+                self.charts.clear();
+                self.error = Some(err.to_string());
+            }
+        }
+        self.selected = 0;
+    }
+
+    /// Follows the statements the program stops at, showing the chart of
+    /// the current one
+    pub fn handle(&mut self, event: &RuntimeEvent) {
+        match event {
+            RuntimeEvent::StatementEntered(span) | RuntimeEvent::Rewound(span) => {
+                self.current = Some(*span);
+                if let Some(chart) = self
+                    .charts
+                    .iter()
+                    .position(|chart| chart.block_at(*span).is_some())
+                {
+                    self.selected = chart;
+                }
+            }
+            RuntimeEvent::Started { .. } | RuntimeEvent::Finished(_) => self.current = None,
+            _ => {}
+        }
+    }
+}
+
+pub struct FlowchartView<'a> {
+    pub state: &'a mut FlowchartState,
+}
+
+impl Widget for FlowchartView<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        ui.scope(|ui: &mut Ui| {
+            if let Some(error) = &self.state.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            let Some(chart) = self.state.charts.get(self.state.selected) else {
+                ui.label("Блок-схема строится кнопкой «Блок-схема» в IDE");
+                return;
+            };
+            ui.horizontal(|ui| {
+                ComboBox::from_id_salt("flowchart")
+                    .selected_text(chart_name(chart))
+                    .show_ui(ui, |ui| {
+                        for (i, chart) in self.state.charts.iter().enumerate() {
+                            ui.selectable_value(&mut self.state.selected, i, chart_name(chart));
+                        }
+                    });
+                if ui.button("Копировать SVG").clicked() {
+                    ui.ctx().copy_text(chart.to_svg());
+                }
+                if ui.button("Копировать DOT").clicked() {
+                    ui.ctx().copy_text(chart.to_dot());
+                }
+            });
+            let current = self.state.current.and_then(|span| chart.block_at(span));
+            ScrollArea::both().show(ui, |ui| draw(ui, chart, current));
+        })
+        .response
+    }
+}
+
+fn chart_name(chart: &Flowchart) -> String {
+    match &chart.name {
+        Some(name) => format!("алг {name}"),
+        None => "Основная программа".to_string(),
+    }
+}
+
+fn draw(ui: &mut Ui, chart: &Flowchart, current: Option<usize>) {
+    let layout = chart.layout();
+    let size = Vec2::new(layout.width, layout.height);
+    let (response, painter) = ui.allocate_painter(size, Sense::hover());
+    let origin = response.rect.min.to_vec2();
+    let pos = |(x, y): (f32, f32)| Pos2::new(x, y) + origin;
+    let color = ui.visuals().text_color();
+    let stroke = Stroke::new(1.0, color);
+    let font = FontId::proportional(12.0);
+    for edge in &layout.edges {
+        let points: Vec<_> = edge.points.iter().map(|point| pos(*point)).collect();
+        for segment in points.windows(2) {
+            painter.line_segment([segment[0], segment[1]], stroke);
+        }
+        if let [.., from, tip] = points[..] {
+            arrow_head(&painter, from, tip, color);
+        }
+        if let Some(label) = &edge.label {
+            painter.text(
+                pos(edge.label_at),
+                Align2::LEFT_BOTTOM,
+                label,
+                font.clone(),
+                color,
+            );
+        }
+    }
+    for (i, (block, placed)) in chart.blocks.iter().zip(&layout.blocks).enumerate() {
+        let fill = match current == Some(i) {
+            true => ui.visuals().selection.bg_fill,
+            false => ui.visuals().extreme_bg_color,
+        };
+        let outline: Vec<_> = block
+            .shape
+            .outline(&placed.bounds)
+            .into_iter()
+            .map(pos)
+            .collect();
+        let rect = egui::Rect::from_points(&outline);
+        match block.shape {
+            Shape::Terminator => {
+                let radius = CornerRadius::same((rect.height() / 2.0) as u8);
+                painter.rect(rect, radius, fill, stroke, StrokeKind::Inside);
+            }
+            Shape::Process | Shape::PredefinedProcess => {
+                painter.rect(rect, CornerRadius::ZERO, fill, stroke, StrokeKind::Inside);
+            }
+            _ => {
+                painter.add(egui::Shape::convex_polygon(outline, fill, stroke));
+            }
+        }
+        if block.shape == Shape::PredefinedProcess {
+            for x in [rect.left() + 10.0, rect.right() - 10.0] {
+                painter.line_segment(
+                    [Pos2::new(x, rect.top()), Pos2::new(x, rect.bottom())],
+                    stroke,
+                );
+            }
+        }
+        painter.text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            &placed.text,
+            font.clone(),
+            color,
+        );
+    }
+}
+
+fn arrow_head(painter: &Painter, from: Pos2, tip: Pos2, color: Color32) {
+    let direction = (tip - from).normalized();
+    let back = tip - direction * 8.0;
+    let side = direction.rot90() * 4.0;
+    painter.add(egui::Shape::convex_polygon(
+        vec![tip, back + side, back - side],
+        color,
+        Stroke::NONE,
+    ));
+}
@@ -1,4 +1,6 @@
+pub mod flowchart;
 pub mod panes;
 pub mod robot_gui;
 pub mod terminal;
+pub mod trace_table;
 pub mod usage_diagnostics;
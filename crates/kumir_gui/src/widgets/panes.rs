@@ -1,11 +1,15 @@
 use crate::kumir_state::{KumirState, Modes};
 use crate::runtime_requirements::GuiRuntimeRequirements;
-use crate::widgets::{robot_gui::RobotWidget, terminal::Terminal};
+use crate::widgets::{
+    flowchart::FlowchartView, robot_gui::RobotWidget, terminal::Terminal,
+    trace_table::TraceTableView,
+};
 use egui::Ui;
 use egui::{Align2, Sense, TextureId, load::SizedTexture};
 use egui_extras::syntax_highlighting::{CodeTheme, highlight};
 use kumir_gui_docs::Docs;
-use kumir_runtime::{Lang, Runtime};
+use kumir_lang::messages::Locale;
+use kumir_runtime::{Lang, Runtime, events::RuntimeEvent};
 use log::{error, info};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -23,6 +27,10 @@ pub struct IDEWindowOptions {
     code: String,
     lang: Lang,
     sleep_duration: u64,
+    /// Seed typed by the user, empty for a new one every run
+    seed: String,
+    /// Record Kumir programs to step back while paused
+    record_trace: bool,
 }
 
 pub enum Pane {
@@ -32,6 +40,8 @@ pub enum Pane {
     IDE(IDEWindowOptions),
     Vello(Arc<Mutex<VelloWindowOptions>>),
     Docs,
+    TraceTable,
+    Flowchart,
 }
 
 pub struct TreeBehavior<'a> {
@@ -58,6 +68,8 @@ impl egui_tiles::Behavior<Pane> for TreeBehavior<'_> {
             Pane::IDE(_) => "IDE".to_string().into(),
             Pane::Vello(_) => "Vello window".to_string().into(),
             Pane::Docs => format!("Docs").into(),
+            Pane::TraceTable => "Таблица трассировки".into(),
+            Pane::Flowchart => "Блок-схема".into(),
         }
     }
     fn pane_ui(
@@ -89,7 +101,12 @@ impl egui_tiles::Behavior<Pane> for TreeBehavior<'_> {
                 ui.label(format!("The contents of pane {nr}."));
             }
             Pane::Terminal => {
-                ui.add(Terminal {});
+                let control = self.kumir_state.runtime_control.lock().unwrap().clone();
+                let mut state = self.kumir_state.terminal.lock().unwrap();
+                ui.add(Terminal {
+                    state: &mut state,
+                    control,
+                });
             }
             Pane::IDE(options) => {
                 ui.label("Самое современное IDE");
@@ -115,47 +132,169 @@ impl egui_tiles::Behavior<Pane> for TreeBehavior<'_> {
                         });
                 });
 
+                ui.horizontal(|ui: &mut Ui| {
+                    ui.label("Язык сообщений: ");
+                    let locale = &mut self.kumir_state.terminal.lock().unwrap().locale;
+                    egui::ComboBox::from_id_salt("locale")
+                        .selected_text(locale.to_string())
+                        .show_ui(ui, |ui| {
+                            for option in [Locale::Ru, Locale::En] {
+                                ui.selectable_value(locale, option, option.to_string());
+                            }
+                        });
+                });
+
                 // let mut theme = CodeTheme::from_memory(ui.ctx(), ui.style());
 
+                let control = self.kumir_state.runtime_control.lock().unwrap().clone();
+
                 ui.horizontal(|ui| {
                     ui.label("Задержка действий исполнителя: ");
-                    ui.add(egui::DragValue::new(&mut options.sleep_duration).speed(1));
+                    let delay = ui.add(egui::DragValue::new(&mut options.sleep_duration).speed(1));
+                    if delay.changed()
+                        && let Some(control) = &control
+                    {
+                        control.set_delay(Duration::from_millis(options.sleep_duration));
+                    }
                 });
 
+                ui.horizontal(|ui| {
+                    ui.label("Зерно случайных чисел: ");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut options.seed)
+                            .hint_text("новое при каждом запуске")
+                            .desired_width(160.0),
+                    );
+                });
+
+                if options.lang == Lang::Kumir {
+                    ui.checkbox(&mut options.record_trace, "Запоминать шаги для шага назад");
+                }
+
                 ui.horizontal(|ui| {
                     if ui.add(egui::Button::new("Запустить")).clicked() {
                         let mode = self.kumir_state.selected_mode.clone();
                         let lang = options.lang.clone();
                         let code = options.code.clone();
                         let duration = options.sleep_duration.clone();
-                        let kill_flag = self.kumir_state.kill_flag.clone();
+                        let seed = options.seed.trim().parse().ok();
+                        let record_trace = options.record_trace && lang == Lang::Kumir;
+                        let runtime_control = self.kumir_state.runtime_control.clone();
+                        let terminal = self.kumir_state.terminal.clone();
+                        let flowchart = self.kumir_state.flowchart.clone();
                         let scene_is_dirty = self.kumir_state.scene_is_dirty.clone();
+                        if let Some(control) = runtime_control.lock().unwrap().take() {
+                            control.stop();
+                        }
+                        if let Modes::Robot(robot) = &mode {
+                            robot.lock().unwrap().start_run(record_trace);
+                        }
                         thread::spawn(move || {
                             info!("Starting runtime");
 
-                            let mut target = kumir_runtime::Target::init(
+                            let (mut target, control) = match kumir_runtime::Target::init(
                                 Arc::new(GuiRuntimeRequirements {
                                     mode: mode,
-                                    sleep_duration: Duration::from_millis(duration),
+                                    files_root: if cfg!(target_arch = "wasm32") {
+                                        None
+                                    } else {
+                                        std::env::current_dir().ok()
+                                    },
+                                    seed,
+                                    record_trace,
                                 }),
                                 lang,
                                 code,
-                                kill_flag,
-                            )
-                            .unwrap();
-                            if let Err(err) = target.run() {
-                                error!("{err}")
+                                Default::default(),
+                            ) {
+                                Ok(target) => target,
+                                Err(err) => {
+                                    error!("{err}");
+                                    return;
+                                }
                             };
+                            control.set_delay(Duration::from_millis(duration));
+                            *runtime_control.lock().unwrap() = Some(control.clone());
+                            let events = control.subscribe();
+                            let consumer = thread::spawn(move || {
+                                for event in events {
+                                    let finished = matches!(event, RuntimeEvent::Finished(_));
+                                    flowchart.lock().unwrap().handle(&event);
+                                    terminal.lock().unwrap().handle(event);
+                                    if finished {
+                                        break;
+                                    }
+                                }
+                            });
+                            let _ = target.run();
+                            let _ = consumer.join();
+                            let mut current = runtime_control.lock().unwrap();
+                            if current
+                                .as_ref()
+                                .is_some_and(|current| current.ptr_eq(&control))
+                            {
+                                *current = None;
+                            }
                             info!("Something should run");
                         });
                     }
 
+                    if let Some(control) = &control {
+                        if control.is_paused() {
+                            if ui.add(egui::Button::new("Продолжить")).clicked() {
+                                control.resume();
+                            }
+                        } else if ui.add(egui::Button::new("Пауза")).clicked() {
+                            control.pause();
+                        }
+
+                        if options.record_trace
+                            && control.is_paused()
+                            && ui.add(egui::Button::new("Шаг назад")).clicked()
+                        {
+                            control.step_back();
+                        }
+
+                        if ui.add(egui::Button::new("Шаг")).clicked() {
+                            control.step();
+                        }
+                    }
+
                     if ui.add(egui::Button::new("Остановить")).clicked() {
-                        self.kumir_state
-                            .kill_flag
-                            .store(true, std::sync::atomic::Ordering::Relaxed);
+                        if let Some(control) =
+                            self.kumir_state.runtime_control.lock().unwrap().take()
+                        {
+                            control.stop();
+                        }
                         info!("Something should stop");
                     }
+
+                    if options.lang == Lang::Kumir
+                        && ui.add(egui::Button::new("Форматировать")).clicked()
+                    {
+                        match kumir_lang::formatter::format(&options.code) {
+                            Ok(code) => options.code = code,
+                            Err(err) => error!("{err}"),
+                        }
+                    }
+
+                    if options.lang == Lang::Kumir
+                        && ui.add(egui::Button::new("Таблица трассировки")).clicked()
+                    {
+                        self.kumir_state
+                            .trace_table
+                            .build(&options.code, &self.kumir_state.modes.robot);
+                    }
+
+                    if options.lang == Lang::Kumir
+                        && ui.add(egui::Button::new("Блок-схема")).clicked()
+                    {
+                        self.kumir_state
+                            .flowchart
+                            .lock()
+                            .unwrap()
+                            .build(&options.code);
+                    }
                 });
 
                 let mut layouter = |ui: &egui::Ui, buf: &str, wrap_width: f32| {
@@ -257,6 +396,15 @@ impl egui_tiles::Behavior<Pane> for TreeBehavior<'_> {
             Pane::Docs => {
                 self.docs.ui(ui);
             }
+            Pane::Flowchart => {
+                let mut state = self.kumir_state.flowchart.lock().unwrap();
+                ui.add(FlowchartView { state: &mut state });
+            }
+            Pane::TraceTable => {
+                ui.add(TraceTableView {
+                    state: &mut self.kumir_state.trace_table,
+                });
+            }
         }
         if title_bar_response.drag_started() {
             egui_tiles::UiResponse::DragStarted
@@ -276,17 +424,21 @@ pub fn create_tree(vello_options: Arc<Mutex<VelloWindowOptions>>) -> egui_tiles:
 
     let mut tiles = egui_tiles::Tiles::default();
 
-    let mut tabs = vec![];
-
-    tabs.push(tiles.insert_pane(Pane::Vello(vello_options)));
-    tabs.push(tiles.insert_pane(gen_pane()));
-    tabs.push(tiles.insert_pane(Pane::IDE(IDEWindowOptions {
-        code: "print(\"Hello, world!\")\n".to_string(),
-        lang: Lang::Python,
-        sleep_duration: 200,
-    })));
-    tabs.push(tiles.insert_pane(Pane::Terminal));
-    tabs.push(tiles.insert_pane(Pane::Docs));
+    let tabs = vec![
+        tiles.insert_pane(Pane::Vello(vello_options)),
+        tiles.insert_pane(gen_pane()),
+        tiles.insert_pane(Pane::IDE(IDEWindowOptions {
+            code: "print(\"Hello, world!\")\n".to_string(),
+            lang: Lang::Python,
+            sleep_duration: 200,
+            seed: String::new(),
+            record_trace: false,
+        })),
+        tiles.insert_pane(Pane::Terminal),
+        tiles.insert_pane(Pane::Docs),
+        tiles.insert_pane(Pane::TraceTable),
+        tiles.insert_pane(Pane::Flowchart),
+    ];
 
     let root = tiles.insert_tab_tile(tabs);
 
@@ -1,11 +1,72 @@
-use egui::{Response, Ui, Widget};
+use egui::{Response, ScrollArea, TextEdit, Ui, Widget};
+use kumir_lang::messages::Locale;
+use kumir_runtime::{control::RuntimeControl, events::RuntimeEvent};
+use log::error;
 
-pub struct Terminal {}
+/// Output of the running program and the line it waits for
+#[derive(Default)]
+pub struct TerminalState {
+    pub output: String,
+    pub waiting_for_input: bool,
+    pub input: String,
+    /// Seed of the random functions in the last run
+    pub seed: Option<u64>,
+    /// Language of the error messages shown here, the log is in English
+    pub locale: Locale,
+}
+
+impl TerminalState {
+    pub fn handle(&mut self, event: RuntimeEvent) {
+        match event {
+            RuntimeEvent::Started { seed } => {
+                self.output.clear();
+                self.waiting_for_input = false;
+                self.seed = seed;
+            }
+            RuntimeEvent::Output(text) => self.output.push_str(&text),
+            RuntimeEvent::InputRequested => self.waiting_for_input = true,
+            RuntimeEvent::Finished(Err(diagnostic)) => {
+                error!("{}", diagnostic.text(Locale::En));
+                self.output
+                    .push_str(&format!("\n{}\n", diagnostic.text(self.locale)));
+                self.waiting_for_input = false;
+            }
+            RuntimeEvent::Finished(Ok(())) => self.waiting_for_input = false,
+            _ => {}
+        }
+    }
+}
+
+pub struct Terminal<'a> {
+    pub state: &'a mut TerminalState,
+    pub control: Option<RuntimeControl>,
+}
 
-impl Widget for Terminal {
+impl Widget for Terminal<'_> {
     fn ui(self, ui: &mut Ui) -> Response {
         ui.scope(|ui: &mut Ui| {
-            ui.label("nothing to see here");
+            if let Some(seed) = self.state.seed {
+                ui.label(format!("Зерно случайных чисел: {seed}"));
+            }
+            if self.state.waiting_for_input
+                && let Some(control) = &self.control
+            {
+                ui.horizontal(|ui| {
+                    let input = ui.add(TextEdit::singleline(&mut self.state.input));
+                    let entered =
+                        input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if entered || ui.button("Ввод").clicked() {
+                        control.send_input(&self.state.input);
+                        self.state.output.push_str(&self.state.input);
+                        self.state.output.push('\n');
+                        self.state.input.clear();
+                        self.state.waiting_for_input = false;
+                    }
+                });
+            }
+            ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| ui.monospace(&self.state.output));
         })
         .response
     }
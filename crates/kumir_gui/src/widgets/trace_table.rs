@@ -0,0 +1,191 @@
+use std::{
+    collections::VecDeque,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use egui::{Grid, Response, ScrollArea, TextEdit, Ui, Widget};
+use kumir_lang::{
+    ast::AstNode,
+    clock::VirtualClock,
+    interpreter::Interpreter,
+    io::ProgramIo,
+    limits::ExecutionLimits,
+    messages::{Error, Message},
+    trace_table::TraceTable,
+};
+use kumir_runtime::kumir_lang_runtime::{ROBOT, robot_namespace};
+
+use crate::{kumir_state::Modes, runtime_requirements::GuiRuntimeRequirements};
+
+/// Statements a program may run for its table, it runs on the UI thread
+const MAX_STEPS: u64 = 100_000;
+
+/// Trace table of the program in the IDE and what it was made with
+#[derive(Default)]
+pub struct TraceTableState {
+    /// Columns separated by commas or spaces, empty for every variable
+    pub variables: String,
+    /// What `ввод` reads, a line per value
+    pub input: String,
+    pub table: Option<TraceTable>,
+}
+
+impl TraceTableState {
+    /// Runs `code` and makes its table. `Робот` runs on a copy of the field
+    /// of `robot`, other actors are not available
+    pub fn build(&mut self, code: &str, robot: &Modes) {
+        let variables: Vec<String> = self
+            .variables
+            .split([',', ' '])
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        let mut interpreter = Interpreter::new(AstNode::Program(vec![]), Arc::default());
+        if let Modes::Robot(robot) = robot {
+            let field = robot.lock().unwrap().headless_copy();
+            let requirements = Arc::new(GuiRuntimeRequirements {
+                mode: Modes::Robot(Arc::new(Mutex::new(field))),
+                files_root: None,
+                seed: None,
+                record_trace: false,
+            });
+            interpreter.register_namespace(ROBOT, robot_namespace(requirements));
+        }
+        if let Err(err) = interpreter.load_string(code) {
+            self.table = Some(TraceTable {
+                error: Some(err.to_string()),
+                ..Default::default()
+            });
+            return;
+        }
+        interpreter.set_io(InputLines(self.input.lines().map(str::to_string).collect()));
+        interpreter.set_clock(Rc::new(VirtualClock::new(Duration::ZERO)));
+        interpreter.set_limits(ExecutionLimits {
+            max_steps: Some(MAX_STEPS),
+            ..Default::default()
+        });
+        self.table = Some(interpreter.trace_table(&variables));
+    }
+}
+
+/// Input given beforehand, the output is not shown
+struct InputLines(VecDeque<String>);
+
+impl ProgramIo for InputLines {
+    fn write(&mut self, _text: &str) {}
+
+    fn read_line(&mut self) -> Result<String, Error> {
+        self.0
+            .pop_front()
+            .ok_or_else(|| Message::InputIsOver.into())
+    }
+}
+
+pub struct TraceTableView<'a> {
+    pub state: &'a mut TraceTableState,
+}
+
+impl Widget for TraceTableView<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        ui.scope(|ui: &mut Ui| {
+            ui.horizontal(|ui| {
+                ui.label("Переменные: ");
+                ui.add(
+                    TextEdit::singleline(&mut self.state.variables)
+                        .hint_text("все")
+                        .desired_width(160.0),
+                );
+            });
+            ui.label("Ввод, по значению в строке:");
+            ui.add(TextEdit::multiline(&mut self.state.input).desired_rows(2));
+            let Some(table) = &self.state.table else {
+                ui.label("Таблица строится кнопкой «Таблица трассировки» в IDE");
+                return;
+            };
+            ui.horizontal(|ui| {
+                if ui.button("Копировать Markdown").clicked() {
+                    ui.ctx().copy_text(table.to_markdown());
+                }
+                if ui.button("Копировать CSV").clicked() {
+                    ui.ctx().copy_text(table.to_csv());
+                }
+                if ui.button("Копировать HTML").clicked() {
+                    ui.ctx().copy_text(table.to_html());
+                }
+            });
+            if let Some(error) = &table.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            ScrollArea::both().show(ui, |ui| {
+                Grid::new("trace_table").striped(true).show(ui, |ui| {
+                    for header in ["Шаг", "Строка", "Оператор"] {
+                        ui.strong(header);
+                    }
+                    for variable in &table.variables {
+                        ui.strong(variable);
+                    }
+                    ui.strong("Условие");
+                    ui.end_row();
+                    for row in &table.rows {
+                        ui.label(row.step.to_string());
+                        ui.label(row.line.to_string());
+                        ui.monospace(&row.statement);
+                        for value in &row.values {
+                            ui.label(value.as_deref().unwrap_or_default());
+                        }
+                        ui.label(match row.condition {
+                            Some(true) => "да",
+                            Some(false) => "нет",
+                            None => "",
+                        });
+                        ui.end_row();
+                    }
+                });
+            });
+        })
+        .response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::robot::Robot;
+
+    fn robot() -> Modes {
+        Modes::Robot(Arc::new(Mutex::new(Robot::new(
+            3,
+            3,
+            10.0,
+            0.0,
+            0.0,
+            Arc::default(),
+        ))))
+    }
+
+    #[test]
+    fn robot_runs_on_a_copy_of_the_field() {
+        let code = "использовать Робот\nалг\nнач\nцел а := 1\nвправо\nвправо\nа := 2\nкон";
+        let robot = robot();
+        let mut state = TraceTableState::default();
+        //Twice, as the first run leaves the robot at the wall if it moves it
+        for _ in 0..2 {
+            state.build(code, &robot);
+            let table = state.table.as_ref().unwrap();
+            assert_eq!(table.error, None);
+            assert_eq!(table.rows.len(), 2);
+        }
+    }
+
+    #[test]
+    fn robot_errors_end_the_table() {
+        let code = "использовать Робот\nалг\nнач\nцел а := 1\nвлево\nа := 2\nкон";
+        let mut state = TraceTableState::default();
+        state.build(code, &robot());
+        let table = state.table.unwrap();
+        assert_eq!(table.rows.len(), 1);
+        assert!(table.error.is_some());
+    }
+}
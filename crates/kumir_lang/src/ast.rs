@@ -5,43 +5,157 @@ use std::{
     sync::{Arc, atomic::AtomicBool},
 };
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use indexmap::IndexMap;
 use log::info;
 
-use crate::lexer::{FunctionParamType, Operator, TypeDefinition};
+use crate::{
+    clock::{Clock, SystemClock},
+    coverage::Coverage,
+    debugger::Debugger,
+    fs::FileHandle,
+    inspector,
+    io::Io,
+    lexer::{FunctionParamType, Operator, Span, TypeDefinition},
+    limits::Budget,
+    messages::{Error, Message},
+    parser::MAIN,
+    profiler::Profiler,
+    random::Random,
+    resolver::KnownTypes,
+    trace::Trace,
+    types::{CustomType, CustomValue},
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum AstNode {
-    Program(Vec<Stmt>),
+    Program(Vec<Statement>),
     Stmt(Stmt),
 }
 
 impl AstNode {
-    pub fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<EvalResult, String> {
-        let scope: Rc<RefCell<Environment>> = Rc::new(RefCell::new(Environment {
-            environment: Some(environment.clone()),
-            variables: Default::default(),
-            functions: environment.borrow().functions.clone(),
-            namespaces: environment.borrow().namespaces.clone(),
-            kill_flag: environment.borrow().kill_flag.clone(),
-        }));
+    pub fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<EvalResult, Error> {
+        let scope = Environment::scope(environment);
         info!("scope variables: {:#?}", scope.borrow().get_all_vars());
         match self {
             AstNode::Program(body) => {
-                for stmt in body {
-                    let eval_result = stmt.eval(&scope)?;
-                    match eval_result {
-                        EvalResult::Procedure => {}
-                        EvalResult::Literal(literal) => return Ok(EvalResult::Literal(literal)),
-                        EvalResult::Break => return Ok(EvalResult::Break),
-                    }
+                let result = Self::eval_body(body, &scope);
+                //The variables of the scope go away with it, so the trace
+                //sees what the last statement wrote before
+                let trace = scope.borrow().trace.clone();
+                if let Some(trace) = trace {
+                    trace.borrow_mut().scope_left(|| inspector::inspect(&scope));
                 }
-                Ok(EvalResult::Procedure)
+                result
             }
             AstNode::Stmt(stmt) => stmt.eval(environment),
         }
     }
+
+    fn eval_body(
+        body: &[Statement],
+        scope: &Rc<RefCell<Environment>>,
+    ) -> Result<EvalResult, Error> {
+        for statement in body {
+            let eval_result = statement.eval(scope)?;
+            match eval_result {
+                EvalResult::Procedure => {}
+                EvalResult::Literal(literal) => return Ok(EvalResult::Literal(literal)),
+                EvalResult::Break => return Ok(EvalResult::Break),
+            }
+        }
+        Ok(EvalResult::Procedure)
+    }
+
+    /// Every statement, nested ones included, in source order
+    pub fn statements(&self) -> Vec<&Statement> {
+        let mut statements = Vec::new();
+        if let AstNode::Program(body) = self {
+            for statement in body {
+                statements.push(statement);
+                let nested = match &statement.stmt {
+                    Stmt::Alg(function) => vec![&function.body],
+                    Stmt::Condition(condition) => std::iter::once(&condition.left)
+                        .chain(&condition.right)
+                        .collect(),
+                    Stmt::Loop(loop_stmt) => vec![&loop_stmt.body],
+                    Stmt::ForLoop(for_loop) => vec![&for_loop.body],
+                    Stmt::RepeatLoop(repeat_loop) => vec![&repeat_loop.body],
+                    _ => vec![],
+                };
+                for body in nested {
+                    statements.extend(body.statements());
+                }
+            }
+        }
+        statements
+    }
+}
+
+/// Statement together with its position in the source
+#[derive(Debug, PartialEq, Clone)]
+pub struct Statement {
+    pub span: Span,
+    pub stmt: Stmt,
+}
+
+impl Statement {
+    /// Runs no code of its line: the definition of an algorithm, or the call
+    /// of the program's algorithm that has the span of its `алг`
+    pub(crate) fn is_definition(&self) -> bool {
+        match &self.stmt {
+            Stmt::Alg(_) => true,
+            Stmt::FunctionCall(call) => call.name == MAIN,
+            _ => false,
+        }
+    }
+
+    pub fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<EvalResult, Error> {
+        let debugger = environment.borrow().debugger.clone();
+        //Recorded first, so a stop here can already step back from it
+        let trace = environment.borrow().trace.clone();
+        if let Some(trace) = trace {
+            let state = inspector::inspect(environment);
+            trace.borrow_mut().statement_entered(self.span, state);
+        }
+        let step = Debugger::statement_entered(&debugger, self.span, environment)
+            .and_then(|_| environment.borrow().budget.borrow_mut().step());
+        let definition = self.is_definition();
+        if let Some(profiler) = &environment.borrow().profiler
+            && !definition
+        {
+            profiler
+                .borrow_mut()
+                .statement_entered(self.span.start.line);
+        }
+        if let Some(coverage) = &environment.borrow().coverage
+            && !definition
+        {
+            coverage
+                .borrow_mut()
+                .statement_entered(self.span.start.line);
+        }
+        let trace = environment.borrow().trace.clone();
+        let checks = trace.filter(|_| {
+            matches!(
+                self.stmt,
+                Stmt::Condition(_) | Stmt::Loop(_) | Stmt::RepeatLoop(_)
+            )
+        });
+        if let Some(trace) = &checks {
+            trace.borrow_mut().enter_condition(self.span);
+        }
+        let result = step.and_then(|_| self.stmt.eval(environment));
+        if let Some(trace) = &checks {
+            trace.borrow_mut().exit_condition();
+        }
+        if let Err(message) = &result {
+            debugger
+                .borrow_mut()
+                .error_raised(self.span, message.clone());
+        }
+        result
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -62,6 +176,7 @@ pub enum Stmt {
     ForLoop(ForLoop),
     RepeatLoop(RepeatLoop),
     Output { values: Vec<Expr> },
+    Input { names: Vec<String> },
     FunctionCall(FunctionCall),
     ImportNamespace(ImportNamespace),
     Break,
@@ -73,7 +188,7 @@ pub struct ImportNamespace {
 }
 
 impl ImportNamespace {
-    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<(), String> {
+    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<(), Error> {
         environment.borrow_mut().import_namespace(&self.name)
     }
 }
@@ -81,31 +196,49 @@ impl ImportNamespace {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Loop {
     pub condition: Option<Expr>,
+    /// Where `condition` is, default without one
+    pub condition_span: Span,
     pub body: Box<AstNode>,
 }
 
 fn check_condition(
     condition: &Expr,
     environment: &Rc<RefCell<Environment>>,
-) -> Result<bool, String> {
+) -> Result<bool, Error> {
     let condition_val = condition.eval(environment)?;
-    match condition_val {
-        Literal::Bool(value) => Ok(value),
-        _ => Err(format!("{condition_val:?} must be a boolean value")),
+    let Literal::Bool(value) = condition_val else {
+        return Err(Message::NotBoolean(format!("{condition_val:?}")).into());
+    };
+    if let Some(trace) = &environment.borrow().trace {
+        trace.borrow_mut().checked(value);
     }
+    Ok(value)
+}
+
+/// Runs one pass of a loop body, a breakpoint in it stops on every pass.
+/// The pass is a step of its own, so a loop with an empty body runs into
+/// the limits too
+fn eval_iteration(
+    body: &AstNode,
+    environment: &Rc<RefCell<Environment>>,
+) -> Result<EvalResult, Error> {
+    let debugger = environment.borrow().debugger.clone();
+    debugger.borrow_mut().next_iteration();
+    environment.borrow().budget.borrow_mut().step()?;
+    body.eval(environment)
 }
 
 impl Loop {
-    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<(), String> {
+    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<(), Error> {
         if let Some(condition) = self.condition.as_ref() {
             while check_condition(condition, environment)? {
-                if EvalResult::Break == self.body.eval(environment)? {
+                if EvalResult::Break == eval_iteration(&self.body, environment)? {
                     break;
                 };
             }
         } else {
             loop {
-                if EvalResult::Break == self.body.eval(environment)? {
+                if EvalResult::Break == eval_iteration(&self.body, environment)? {
                     break;
                 };
             }
@@ -123,32 +256,36 @@ pub struct ForLoop {
 }
 
 impl ForLoop {
-    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<(), String> {
+    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<(), Error> {
         let step = 1;
         let start = if let Literal::Int(start) = self.start.eval(environment)? {
             start
         } else {
-            return Err(format!("{:?} must be an integer value in loop", self.start));
+            return Err(Message::NotIntegerInLoop(format!("{:?}", self.start)).into());
         };
         let end = if let Literal::Int(end) = self.end.eval(environment)? {
             end
         } else {
-            return Err(format!("{:?} must be an integer value in loop", self.end));
+            return Err(Message::NotIntegerInLoop(format!("{:?}", self.end)).into());
         };
-        let mut environment_mut = environment.borrow_mut();
-        environment_mut.new_var(&self.var, Some(Literal::Int(start)), TypeDefinition::Int);
+        environment
+            .borrow_mut()
+            .new_var(&self.var, Some(Literal::Int(start)), TypeDefinition::Int);
         while {
-            if let Some(Literal::Int(i)) = environment_mut.get_value(&self.var) {
+            if let Some(Literal::Int(i)) = environment.borrow().get_value(&self.var) {
                 i != end
             } else {
                 false
             }
         } {
-            if EvalResult::Break == self.body.eval(environment)? {
+            if EvalResult::Break == eval_iteration(&self.body, environment)? {
                 break;
             };
-            if let Some(Literal::Int(i)) = environment_mut.get_value(&self.var) {
-                environment_mut.assign_var(&self.var, Literal::Int(i + step))?;
+            let value = environment.borrow().get_value(&self.var);
+            if let Some(Literal::Int(i)) = value {
+                environment
+                    .borrow_mut()
+                    .assign_var(&self.var, Literal::Int(i + step))?;
             }
         }
         Ok(())
@@ -158,19 +295,19 @@ impl ForLoop {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Condition {
     pub condition: Expr,
+    pub condition_span: Span,
     pub left: Box<AstNode>,
     pub right: Option<Box<AstNode>>,
 }
 
 impl Condition {
-    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<(), String> {
-        let res = {
-            let condition_val = self.condition.eval(environment)?;
-            match condition_val {
-                Literal::Bool(value) => value,
-                _ => return Err(format!("{condition_val:?} must be a boolean value")),
-            }
-        };
+    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<(), Error> {
+        let res = check_condition(&self.condition, environment)?;
+        if let Some(coverage) = &environment.borrow().coverage {
+            //The debugger is back at the `если` after calls in the condition
+            let position = environment.borrow().debugger.borrow().current_span.start;
+            coverage.borrow_mut().branch_taken(position, res);
+        }
         if res {
             self.left.eval(environment)?;
         } else if let Some(right) = self.right.as_ref() {
@@ -183,19 +320,21 @@ impl Condition {
 #[derive(Debug, PartialEq, Clone)]
 pub struct RepeatLoop {
     pub condition: Option<Expr>,
+    /// Where `condition` is, default without one
+    pub condition_span: Span,
     pub count: Expr,
     pub body: Box<AstNode>,
 }
 
 impl RepeatLoop {
-    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<(), String> {
+    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<(), Error> {
         let mut times = if let Literal::Int(times) = self.count.eval(environment)? {
             times
         } else {
-            return Err(format!("{:?} must be an integer value in loop", self.count));
+            return Err(Message::NotIntegerInLoop(format!("{:?}", self.count)).into());
         };
         loop {
-            if EvalResult::Break == self.body.eval(environment)? {
+            if EvalResult::Break == eval_iteration(&self.body, environment)? {
                 break;
             };
             if times == 0 {
@@ -228,84 +367,106 @@ pub struct FunctionCall {
 }
 
 impl FunctionCall {
-    fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<FunctionResult, String> {
+    pub(crate) fn eval(
+        &self,
+        environment: &Rc<RefCell<Environment>>,
+    ) -> Result<FunctionResult, Error> {
         let call = self;
-        let function: FunctionVariant = environment
-            .borrow()
-            .get_function(&call.name)
-            .ok_or(format!(
-                "Couldn't call undefined function with name {}",
-                &call.name
-            ))?
-            .clone();
+        let function = environment.borrow().get_function(&call.name);
+        let function: FunctionVariant =
+            function.ok_or_else(|| environment.borrow().undefined_function(&call.name))?;
 
         let run_function = |args_expr: &Vec<Expr>,
                             params: &IndexMap<String, FunctionParameter>,
                             return_type: Option<TypeDefinition>,
                             environment: &Rc<RefCell<Environment>>,
                             function: Box<
-            dyn FnOnce(&Rc<RefCell<Environment>>) -> Result<Option<Literal>, String>,
+            dyn FnOnce(&Rc<RefCell<Environment>>) -> Result<Option<Literal>, Error>,
         >|
-         -> Result<FunctionResult, String> {
+         -> Result<FunctionResult, Error> {
             let mut args: HashMap<usize, Literal> = HashMap::new();
             for (i, (expr, param)) in args_expr.iter().zip(params.values()).enumerate() {
                 if param.result_type != FunctionParamType::ResultParam {
                     let value = expr.eval(environment)?;
+                    let value = environment.borrow().convert(value, param.type_definition);
                     args.insert(i, value);
                 }
             }
 
             //check param count
             if args_expr.len() != params.len() {
-                return Err(format!(
-                    "params and args mismatch args: {:?}, params: {:?}",
-                    args_expr, params
-                ));
+                return Err(Message::ArgsMismatch {
+                    args: format!("{args_expr:?}"),
+                    params: format!("{params:?}"),
+                }
+                .into());
             }
 
             //check param types
             for (i, arg) in args.iter() {
                 if params
                     .get_index(*i)
-                    .ok_or("Couldn't ".to_string())?
+                    .ok_or(Message::ArgsMismatch {
+                        args: format!("{args:?}"),
+                        params: format!("{params:?}"),
+                    })?
                     .1
                     .type_definition
                     != arg.get_type()
                 {
-                    return Err(format!(
-                        "params and args mismatch args: {:?}, params: {:?}",
-                        args, params
-                    ));
+                    return Err(Message::ArgsMismatch {
+                        args: format!("{args:?}"),
+                        params: format!("{params:?}"),
+                    }
+                    .into());
                 }
             }
 
             //Creating function scope...
-            let mut environment = environment.borrow_mut();
             let scope: Rc<RefCell<Environment>> = Rc::new(RefCell::new({
+                let environment = environment.borrow();
                 let mut scope = Environment::default();
                 scope.functions = environment.functions.clone();
                 scope.namespaces = environment.namespaces.clone();
                 scope.kill_flag = environment.kill_flag.clone();
+                scope.debugger = environment.debugger.clone();
+                scope.io = environment.io.clone();
+                scope.budget = environment.budget.clone();
+                scope.clock = environment.clock.clone();
+                scope.random = environment.random.clone();
+                scope.profiler = environment.profiler.clone();
+                scope.coverage = environment.coverage.clone();
+                scope.trace = environment.trace.clone();
+                scope.types = environment.types.clone();
                 scope
             }));
 
-            let mut value_to_return_from_function: Vec<String> = vec![];
+            //Parameter and the caller's variable that gets its value
+            let mut value_to_return_from_function: Vec<(String, String)> = vec![];
 
             //Map return types
             let mut is_function = false;
             {
                 let mut scope_mut = scope.borrow_mut();
                 for (i, (name, parameter)) in params.iter().enumerate() {
+                    let target = || match &args_expr[i] {
+                        Expr::Identifier(target) => Ok(target.clone()),
+                        _ => Err(Message::ResultArgNotVariable(name.clone())),
+                    };
                     match parameter.result_type {
                         FunctionParamType::ResultParam => {
-                            if environment.get_var_type(name) != Some(parameter.type_definition) {
-                                return Err(format!(
-                                    "params and args mismatch args: {:?}, params: {:?}",
-                                    args, params
-                                ));
+                            let target = target()?;
+                            if environment.borrow().get_var_type(&target)
+                                != Some(parameter.type_definition)
+                            {
+                                return Err(Message::ArgsMismatch {
+                                    args: format!("{args:?}"),
+                                    params: format!("{params:?}"),
+                                }
+                                .into());
                             }
                             scope_mut.new_var(name, None, parameter.type_definition);
-                            value_to_return_from_function.push(name.clone());
+                            value_to_return_from_function.push((name.clone(), target));
                         }
                         FunctionParamType::ArgumentParam => {
                             scope_mut.new_var(
@@ -320,7 +481,7 @@ impl FunctionCall {
                                 Some(args.get(&i).unwrap().clone()),
                                 parameter.type_definition,
                             );
-                            value_to_return_from_function.push(name.clone());
+                            value_to_return_from_function.push((name.clone(), target()?));
                         }
                     }
                 }
@@ -331,30 +492,34 @@ impl FunctionCall {
                 }
             }
 
-            //Execute function
+            //Execute function, values it returns are limited like assigned
+            //ones, natives don't assign them
             let value = function(&scope)?;
+            let budget = environment.borrow().budget.clone();
+            if let Some(value) = &value {
+                budget.borrow_mut().check_value(value)?;
+            }
             info!(
                 "scope variables after function execution: {:#?}",
                 scope.borrow().get_all_vars()
             );
             //Return values
             let scope = scope.borrow_mut();
-            for name in value_to_return_from_function {
+            for (name, target) in value_to_return_from_function {
                 let value = scope
                     .get_var(&name)
-                    .ok_or("Couldn't find variable".to_string())?
+                    .ok_or(Message::UndefinedVariable(name.clone()))?
                     .value
-                    .ok_or("Variable value is None".to_string())?;
-                environment.assign_var(&name, value)?;
+                    .ok_or(Message::ResultNotAssigned(name.clone()))?;
+                budget.borrow_mut().check_value(&value)?;
+                environment.borrow_mut().assign_var(&target, value)?;
             }
 
             if is_function {
                 if let Some(value) = value {
                     return Ok(FunctionResult::Literal(value));
                 }
-                let value = scope
-                    .get_value("знач")
-                    .ok_or("Value of alg func is nothing".to_string())?;
+                let value = scope.get_value("знач").ok_or(Message::ValueNotAssigned)?;
                 Ok(FunctionResult::Literal(value))
             } else {
                 Ok(FunctionResult::Procedure)
@@ -366,20 +531,76 @@ impl FunctionCall {
                 native_function,
                 params,
                 return_type,
-            }) => run_function(
-                &call.args,
-                &params,
-                return_type,
-                environment,
-                Box::new(move |env: &Rc<RefCell<Environment>>| native_function.borrow_mut()(env)),
-            ),
+            }) => {
+                if let Some(profiler) = &environment.borrow().profiler
+                    && environment.borrow().is_actor_command(&call.name)
+                {
+                    profiler.borrow_mut().actor_called();
+                }
+                if environment.borrow().is_actor_command(&call.name)
+                    && environment
+                        .borrow()
+                        .actor_of(&call.name)
+                        .is_some_and(|actor| !crate::stdlib::STATELESS.contains(&actor.as_str()))
+                {
+                    environment
+                        .borrow()
+                        .io
+                        .journal()
+                        .borrow_mut()
+                        .actor_called();
+                }
+                if let Some(trace) = &environment.borrow().trace
+                    && return_type.is_none()
+                    && environment.borrow().is_actor_command(&call.name)
+                    && let Some(actor) = environment.borrow().actor_of(&call.name)
+                {
+                    trace.borrow_mut().actor_called(&actor, &call.name);
+                }
+                run_function(
+                    &call.args,
+                    &params,
+                    return_type,
+                    environment,
+                    Box::new(move |env: &Rc<RefCell<Environment>>| {
+                        native_function.borrow_mut()(env)
+                    }),
+                )
+            }
+            FunctionVariant::Ambiguous(actors) => Err(Message::AmbiguousCommand {
+                name: call.name.clone(),
+                actors,
+            }
+            .into()),
             FunctionVariant::Kumir(function) => run_function(
                 &call.args,
                 &function.params,
                 function.return_type,
                 environment,
-                Box::new(|environment: &Rc<RefCell<Environment>>| {
-                    match function.body.eval(environment)? {
+                Box::new(|scope: &Rc<RefCell<Environment>>| {
+                    let debugger = environment.borrow().debugger.clone();
+                    let depth = debugger.borrow().call_stack.len() + 1;
+                    environment
+                        .borrow()
+                        .budget
+                        .borrow_mut()
+                        .check_call_depth(depth)?;
+                    debugger.borrow_mut().enter_call(
+                        &function.name,
+                        &function.params,
+                        environment,
+                        scope,
+                    );
+                    let profiler = environment.borrow().profiler.clone();
+                    if let Some(profiler) = &profiler {
+                        profiler.borrow_mut().enter_call(&function.name);
+                    }
+                    let result = function.body.eval(scope);
+                    if let Some(profiler) = &profiler {
+                        profiler.borrow_mut().exit_call();
+                    }
+                    debugger.borrow_mut().exit_call();
+                    match result? {
                         EvalResult::Literal(literal) => Ok(Some(literal)),
                         EvalResult::Procedure => Ok(None),
                         EvalResult::Break => Ok(None),
@@ -404,6 +625,8 @@ pub enum Literal {
     String(String),
     Char(char),
     Bool(bool),
+    /// Value of a type added by a namespace, see [`crate::types`]
+    Custom(Arc<dyn CustomValue>),
 }
 
 impl Display for Literal {
@@ -414,11 +637,80 @@ impl Display for Literal {
             Literal::String(value) => write!(f, "\"{}\"", value),
             Literal::Char(value) => write!(f, "'{}'", value),
             Literal::Bool(value) => write!(f, "{}", value),
+            Literal::Custom(value) => write!(f, "{}", value.to_output_string()),
         }
     }
 }
 
+impl From<i32> for Literal {
+    fn from(value: i32) -> Self {
+        Literal::Int(value)
+    }
+}
+
+impl From<f32> for Literal {
+    fn from(value: f32) -> Self {
+        Literal::Float(value)
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(value: bool) -> Self {
+        Literal::Bool(value)
+    }
+}
+
+impl From<char> for Literal {
+    fn from(value: char) -> Self {
+        Literal::Char(value)
+    }
+}
+
+impl From<String> for Literal {
+    fn from(value: String) -> Self {
+        Literal::String(value)
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(value: &str) -> Self {
+        Literal::String(value.to_string())
+    }
+}
+
 impl Literal {
+    /// `цел` passed where `вещ` is expected becomes `вещ`, other values stay
+    /// as they are
+    pub fn convert_to(self, type_def: TypeDefinition) -> Literal {
+        match (self, type_def) {
+            (Literal::Int(value), TypeDefinition::Float) => Literal::Float(value as f32),
+            (value, _) => value,
+        }
+    }
+
+    /// Value as `вывод` prints it
+    pub fn to_output_string(&self) -> String {
+        match self {
+            Literal::String(value) => value.clone(),
+            Literal::Char(value) => value.to_string(),
+            Literal::Bool(true) => "да".to_string(),
+            Literal::Bool(false) => "нет".to_string(),
+            literal => literal.to_string(),
+        }
+    }
+
+    pub fn custom(value: impl CustomValue) -> Literal {
+        Literal::Custom(Arc::new(value))
+    }
+
+    /// Value of the namespace type `T`, `None` for other values
+    pub fn as_custom<T: CustomValue>(&self) -> Option<&T> {
+        match self {
+            Literal::Custom(value) => value.downcast_ref(),
+            _ => None,
+        }
+    }
+
     pub fn get_type(&self) -> TypeDefinition {
         match self {
             Literal::Int(_) => TypeDefinition::Int,
@@ -426,6 +718,7 @@ impl Literal {
             Literal::String(_) => TypeDefinition::String,
             Literal::Char(_) => TypeDefinition::Char,
             Literal::Bool(_) => TypeDefinition::Bool,
+            Literal::Custom(value) => TypeDefinition::Custom(value.type_name()),
         }
     }
 }
@@ -444,21 +737,30 @@ pub enum EvalResult {
 }
 
 impl Stmt {
-    pub fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<EvalResult, String> {
+    pub fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<EvalResult, Error> {
         let kill_flag = environment.borrow().kill_flag.clone();
         if kill_flag.load(std::sync::atomic::Ordering::Relaxed) {
             kill_flag.store(false, std::sync::atomic::Ordering::Relaxed);
-            return Err("User interrupt".to_string());
+            return Err(Message::UserInterrupt.into());
         }
         match self {
             Stmt::VarDecl(var_decl) => {
-                let mut environment_mut = environment.borrow_mut();
-                if let Some(value) = &var_decl.value {
-                    let eval_result = Some(value.eval(environment)?);
-                    environment_mut.new_var(&var_decl.name, eval_result, var_decl.type_def);
-                } else {
-                    environment_mut.new_var(&var_decl.name, None, var_decl.type_def);
-                }
+                let value = match &var_decl.value {
+                    Some(value) => {
+                        let value = value.eval(environment)?;
+                        let value = environment.borrow().convert(value, var_decl.type_def);
+                        environment
+                            .borrow()
+                            .budget
+                            .borrow_mut()
+                            .check_value(&value)?;
+                        Some(value)
+                    }
+                    None => None,
+                };
+                environment
+                    .borrow_mut()
+                    .new_var(&var_decl.name, value, var_decl.type_def);
             }
             Stmt::VarsDecl(var_decls) => {
                 for var_decl in var_decls {
@@ -467,6 +769,11 @@ impl Stmt {
             }
             Stmt::Assign { name, value } => {
                 let value = value.eval(environment)?;
+                environment
+                    .borrow()
+                    .budget
+                    .borrow_mut()
+                    .check_value(&value)?;
                 environment.borrow_mut().assign_var(name, value)?;
             }
             Stmt::Alg(_) => {}
@@ -480,14 +787,64 @@ impl Stmt {
                 return Ok(EvalResult::Break);
             }
             Stmt::Output { values } => {
-                println!();
-                for value in values {
+                //`вывод ф, ...` writes to the file `ф`
+                let mut file = None;
+                let mut text = String::new();
+                for (i, value) in values.iter().enumerate() {
                     if Expr::NewLine == *value {
-                        println!();
-                    } else {
-                        print!("{}", value.eval(environment)?);
+                        text.push('\n');
+                        continue;
+                    }
+                    let value = value.eval(environment)?;
+                    match value.as_custom::<FileHandle>() {
+                        Some(handle) if i == 0 => file = Some(*handle),
+                        _ => text.push_str(&value.to_output_string()),
                     }
                 }
+                let io = environment.borrow().io.clone();
+                match file {
+                    Some(handle) => io.write_to(handle, &text)?,
+                    None => io.write(&text)?,
+                }
+            }
+            Stmt::Input { names } => {
+                let io = environment.borrow().io.clone();
+                //`ввод ф, ...` reads from the file `ф`
+                let file = names
+                    .first()
+                    .and_then(|name| environment.borrow().get_value(name))
+                    .and_then(|value| value.as_custom::<FileHandle>().copied());
+                for name in &names[file.map_or(0, |_| 1)..] {
+                    let type_def = environment
+                        .borrow()
+                        .get_var_type(name)
+                        .ok_or(Message::UndefinedVariable(name.clone()))?;
+                    let read = |type_def| match file {
+                        Some(handle) => io.read_from(handle, type_def),
+                        None => io.read(type_def),
+                    };
+                    let value = match type_def {
+                        //Namespace types read the rest of the line and parse it
+                        TypeDefinition::Custom(name) => {
+                            let text = read(TypeDefinition::String)?.to_output_string();
+                            environment
+                                .borrow()
+                                .get_type(name)
+                                .and_then(|custom_type| custom_type.parse(text.trim()))
+                                .ok_or(Message::InvalidInput {
+                                    type_def,
+                                    word: text,
+                                })?
+                        }
+                        type_def => read(type_def)?,
+                    };
+                    environment
+                        .borrow()
+                        .budget
+                        .borrow_mut()
+                        .check_value(&value)?;
+                    environment.borrow_mut().assign_var(name, value)?;
+                }
             }
             Stmt::FunctionCall(call) => {
                 call.eval(environment)?;
@@ -499,11 +856,37 @@ impl Stmt {
 }
 
 impl BinaryOp {
-    pub fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<Literal, String> {
+    pub fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<Literal, Error> {
         let left_val = self.left.eval(environment)?;
 
         let right_val = self.right.eval(environment)?;
 
+        //Mixed `цел` and `вещ` operands are both `вещ`
+        let (left_val, right_val) = match (left_val, right_val) {
+            (Literal::Int(left), right @ Literal::Float(_)) => (Literal::Float(left as f32), right),
+            (left @ Literal::Float(_), Literal::Int(right)) => (left, Literal::Float(right as f32)),
+            values => values,
+        };
+
+        //Operators of namespace types come first, they may take numbers too
+        let custom_type = [&left_val, &right_val]
+            .into_iter()
+            .find_map(|value| match value {
+                Literal::Custom(value) => environment.borrow().get_type(value.type_name()),
+                _ => None,
+            });
+        if let Some(result) =
+            custom_type.and_then(|custom_type| custom_type.apply(&left_val, self.op, &right_val))
+        {
+            return result;
+        }
+
+        if self.op == Operator::Divide
+            && (right_val == Literal::Int(0) || right_val == Literal::Float(0.0))
+        {
+            return Err(Message::DivisionByZero.into());
+        }
+
         match (&left_val, self.op, &right_val) {
             //Equal operations
             (Literal::Bool(left), Operator::EqualBool, Literal::Bool(right)) => {
@@ -521,6 +904,9 @@ impl BinaryOp {
             (Literal::String(left), Operator::EqualBool, Literal::String(right)) => {
                 Ok(Literal::Bool(left == right))
             }
+            (Literal::Custom(left), Operator::EqualBool, Literal::Custom(right)) => {
+                Ok(Literal::Bool(left.equals(right.as_ref())))
+            }
             //Not equal operations
             (Literal::Bool(left), Operator::NotEqual, Literal::Bool(right)) => {
                 Ok(Literal::Bool(left != right))
@@ -537,6 +923,9 @@ impl BinaryOp {
             (Literal::String(left), Operator::NotEqual, Literal::String(right)) => {
                 Ok(Literal::Bool(left != right))
             }
+            (Literal::Custom(left), Operator::NotEqual, Literal::Custom(right)) => {
+                Ok(Literal::Bool(!left.equals(right.as_ref())))
+            }
             //Float operations
             (Literal::Float(left), Operator::Plus, Literal::Float(right)) => {
                 Ok(Literal::Float(left + right))
@@ -588,16 +977,17 @@ impl BinaryOp {
                 Ok(Literal::Bool(left <= right))
             }
 
-            _ => Err(format!(
-                "Invalid operation: {:?} {:?} {:?}",
+            _ => Err(Message::InvalidOperation(format!(
+                "{:?} {:?} {:?}",
                 left_val, self.op, right_val
-            )),
+            ))
+            .into()),
         }
     }
 }
 
 impl Expr {
-    pub fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<Literal, String> {
+    pub fn eval(&self, environment: &Rc<RefCell<Environment>>) -> Result<Literal, Error> {
         match self {
             Expr::Literal(literal) => Ok(literal.clone()),
             Expr::Identifier(name) => {
@@ -608,21 +998,21 @@ impl Expr {
                     };
                     match call.eval(environment)? {
                         FunctionResult::Literal(literal) => Ok(literal),
-                        FunctionResult::Procedure => {
-                            Err("This alg is procedure not function".to_string())
-                        }
+                        FunctionResult::Procedure => Err(Message::ProcedureAsFunction.into()),
                     }
                 } else if let Some(value) = environment.borrow().get_value(name) {
                     Ok(value.clone())
+                } else if environment.borrow().namespace_with(name).is_some() {
+                    Err(environment.borrow().undefined_function(name))
                 } else {
-                    Err(format!("Undefined variable: {name}"))
+                    Err(Message::UndefinedVariable(name.clone()).into())
                 }
             }
             Expr::BinaryOp(binary_op) => binary_op.eval(environment),
-            Expr::NewLine => Err("New line couldn't be Literal".to_string()),
+            Expr::NewLine => Err(Message::NewLineIsNotValue.into()),
             Expr::FunctionCall(call) => match call.eval(environment)? {
                 FunctionResult::Literal(literal) => Ok(literal),
-                FunctionResult::Procedure => Err("This alg is procedure not function".to_string()),
+                FunctionResult::Procedure => Err(Message::ProcedureAsFunction.into()),
             },
         }
     }
@@ -637,7 +1027,7 @@ pub struct Function {
 }
 
 pub type ClonableFnMut =
-    Rc<RefCell<dyn FnMut(&Rc<RefCell<Environment>>) -> Result<Option<Literal>, String>>>;
+    Rc<RefCell<dyn FnMut(&Rc<RefCell<Environment>>) -> Result<Option<Literal>, Error>>>;
 
 #[derive(Clone)]
 pub struct NativeFunction {
@@ -665,6 +1055,8 @@ pub enum FunctionResult {
 pub enum FunctionVariant {
     Native(NativeFunction),
     Kumir(Function),
+    /// Command of several imported namespaces, calling it is an error
+    Ambiguous(Vec<String>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -676,6 +1068,7 @@ pub struct Variable {
 #[derive(Debug, Clone, Default)]
 pub struct Namespace {
     functions: HashMap<String, FunctionVariant>,
+    types: Vec<CustomType>,
 }
 
 impl Namespace {
@@ -694,54 +1087,120 @@ impl Namespace {
     pub fn functions(&self) -> &HashMap<String, FunctionVariant> {
         &self.functions
     }
+
+    /// Adds a type to the language once the namespace is imported, see
+    /// [`crate::types`]
+    pub fn register_type(&mut self, custom_type: CustomType) {
+        self.types.push(custom_type);
+    }
+
+    pub fn types(&self) -> &[CustomType] {
+        &self.types
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Environment {
     pub environment: Option<Rc<RefCell<Environment>>>,
-    pub variables: HashMap<String, Variable>,
+    pub variables: IndexMap<String, Variable>,
     pub namespaces: HashMap<String, Namespace>,
     pub functions: HashMap<String, FunctionVariant>,
     pub kill_flag: Arc<AtomicBool>,
+    pub debugger: Rc<RefCell<Debugger>>,
+    pub io: Io,
+    pub budget: Rc<RefCell<Budget>>,
+    pub clock: Rc<dyn Clock>,
+    pub random: Rc<RefCell<Random>>,
+    /// Set when the run is profiled
+    pub profiler: Option<Rc<RefCell<Profiler>>>,
+    /// Set when the statements run are recorded
+    pub coverage: Option<Rc<RefCell<Coverage>>>,
+    /// Set when the run is recorded for stepping backwards
+    pub trace: Option<Rc<RefCell<Trace>>>,
+    /// Types of the imported namespaces
+    pub types: HashMap<String, CustomType>,
 }
 
 impl Default for Environment {
     fn default() -> Self {
         Self {
             environment: None,
-            variables: HashMap::new(),
+            variables: IndexMap::new(),
             namespaces: HashMap::new(),
             functions: HashMap::new(),
             kill_flag: Default::default(),
+            debugger: Default::default(),
+            io: Default::default(),
+            budget: Default::default(),
+            clock: Rc::new(SystemClock),
+            random: Default::default(),
+            profiler: None,
+            coverage: None,
+            trace: None,
+            types: HashMap::new(),
         }
     }
 }
 
 impl Environment {
+    /// Scope nested in `environment` that sees its variables
+    pub fn scope(environment: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        let parent = environment.borrow();
+        Rc::new(RefCell::new(Environment {
+            environment: Some(environment.clone()),
+            variables: Default::default(),
+            functions: parent.functions.clone(),
+            namespaces: parent.namespaces.clone(),
+            kill_flag: parent.kill_flag.clone(),
+            debugger: parent.debugger.clone(),
+            io: parent.io.clone(),
+            budget: parent.budget.clone(),
+            clock: parent.clock.clone(),
+            random: parent.random.clone(),
+            profiler: parent.profiler.clone(),
+            coverage: parent.coverage.clone(),
+            trace: parent.trace.clone(),
+            types: parent.types.clone(),
+        }))
+    }
+
     pub fn new_var(&mut self, name: &str, value: Option<Literal>, type_def: TypeDefinition) {
         self.variables
             .insert(name.to_string(), Variable { type_def, value });
     }
 
-    pub fn assign_var(&mut self, name: &str, value: Literal) -> Result<(), String> {
-        if let Some(variable) = self.variables.get_mut(name) {
-            if variable.type_def == value.get_type() {
-                variable.value = Some(value);
-                return Ok(());
-            } else {
-                return Err(format!(
-                    "Type mismatch on assignment, type expected: {}, type received: {}",
-                    variable.type_def,
-                    value.get_type()
-                ));
+    pub fn assign_var(&mut self, name: &str, value: Literal) -> Result<(), Error> {
+        if let Some(type_def) = self.variables.get(name).map(|variable| variable.type_def) {
+            let value = self.convert(value, type_def);
+            if type_def != value.get_type() {
+                return Err(Message::TypeMismatch {
+                    expected: type_def,
+                    received: value.get_type(),
+                }
+                .into());
             }
+            self.variables[name].value = Some(value);
+            return Ok(());
         }
 
         if let Some(parent) = self.environment.as_ref() {
             return parent.borrow_mut().assign_var(name, value);
         }
 
-        Err(format!("Cannot assign to undefined variable '{}'", name))
+        Err(Message::UndefinedVariable(name.to_string()).into())
+    }
+
+    /// `value` as a value of `type_def` where the language or the type
+    /// allows it, e.g. `цел` as `вещ`. Other values stay as they are
+    pub fn convert(&self, value: Literal, type_def: TypeDefinition) -> Literal {
+        let value = value.convert_to(type_def);
+        match type_def {
+            TypeDefinition::Custom(name) if value.get_type() != type_def => self
+                .get_type(name)
+                .and_then(|custom_type| custom_type.convert(&value))
+                .unwrap_or(value),
+            _ => value,
+        }
     }
 
     pub fn get_var_type(&self, name: &str) -> Option<TypeDefinition> {
@@ -796,14 +1255,39 @@ impl Environment {
         }
     }
 
-    pub fn import_namespace(&mut self, name: &str) -> Result<(), String> {
+    pub fn import_namespace(&mut self, name: &str) -> Result<(), Error> {
         let namespace = self
             .namespaces
             .get_mut(&name.to_string())
-            .ok_or(format!("Namespace with name: {:?} not found", name))?
+            .ok_or(Message::NamespaceNotFound(name.to_string()))?
             .clone();
-        for (name, function) in namespace.functions().into_iter() {
-            self.register_function(name, function.clone());
+        for (function_name, function) in namespace.functions().iter() {
+            let function = match self.functions.get(function_name) {
+                //Algorithms of the program win over commands of actors
+                Some(FunctionVariant::Kumir(_)) => continue,
+                Some(FunctionVariant::Ambiguous(providers)) => {
+                    let mut providers = providers.clone();
+                    if !providers.iter().any(|provider| provider == name) {
+                        providers.push(name.to_string());
+                    }
+                    FunctionVariant::Ambiguous(providers)
+                }
+                Some(existing) => {
+                    let mut providers = self.providers(function_name, existing);
+                    if providers.is_empty() || providers.iter().any(|provider| provider == name) {
+                        function.clone()
+                    } else {
+                        providers.push(name.to_string());
+                        FunctionVariant::Ambiguous(providers)
+                    }
+                }
+                None => function.clone(),
+            };
+            self.register_function(function_name, function);
+        }
+        for custom_type in namespace.types() {
+            self.types
+                .insert(custom_type.name.to_string(), custom_type.clone());
         }
         Ok(())
     }
@@ -812,6 +1296,64 @@ impl Environment {
         self.namespaces.insert(name.to_string(), namespace);
     }
 
+    /// Namespaces the imported native `function` called `name` comes from
+    fn providers(&self, name: &str, function: &FunctionVariant) -> Vec<String> {
+        let FunctionVariant::Native(function) = function else {
+            return vec![];
+        };
+        let mut providers: Vec<String> = self
+            .namespaces
+            .iter()
+            .filter(|(_, namespace)| {
+                matches!(
+                    namespace.functions().get(name),
+                    Some(FunctionVariant::Native(provided))
+                        if Rc::ptr_eq(&provided.native_function, &function.native_function)
+                )
+            })
+            .map(|(namespace_name, _)| namespace_name.clone())
+            .collect();
+        providers.sort();
+        providers
+    }
+
+    /// Namespace the command `name` the program calls was imported from,
+    /// `None` for algorithms of the program and names not imported
+    pub fn actor_of(&self, name: &str) -> Option<String> {
+        let function = self.get_function(name)?;
+        self.providers(name, &function).into_iter().next()
+    }
+
+    /// First by name of the registered namespaces that have `name`,
+    /// imported or not
+    fn namespace_with(&self, name: &str) -> Option<&String> {
+        self.namespaces
+            .iter()
+            .filter(|(_, namespace)| namespace.get_function(name).is_some())
+            .map(|(actor, _)| actor)
+            .min()
+    }
+
+    /// Whether `name` is a command of an actor, not of a namespace imported
+    /// without `использовать`
+    pub fn is_actor_command(&self, name: &str) -> bool {
+        self.actor_of(name)
+            .is_some_and(|actor| !crate::stdlib::BUILT_IN.contains(&actor.as_str()))
+    }
+
+    /// Error for a call of `name` that isn't defined, telling which actor
+    /// to import when one has the command
+    pub fn undefined_function(&self, name: &str) -> Error {
+        match self.namespace_with(name) {
+            Some(actor) => Message::ActorNotImported {
+                name: name.to_string(),
+                actor: actor.clone(),
+            }
+            .into(),
+            None => Message::UndefinedFunction(name.to_string()).into(),
+        }
+    }
+
     pub fn get_function(&self, name: &str) -> Option<FunctionVariant> {
         if let Some(func) = self.functions.get(name) {
             return Some(func.clone());
@@ -822,6 +1364,50 @@ impl Environment {
         None
     }
 
+    pub fn get_type(&self, name: &str) -> Option<CustomType> {
+        if let Some(custom_type) = self.types.get(name) {
+            return Some(custom_type.clone());
+        }
+        if let Some(parent) = self.environment.as_ref() {
+            return parent.borrow().get_type(name);
+        }
+        None
+    }
+
+    /// Types the parser knows before `использовать`, and the ones every
+    /// namespace adds once imported
+    pub fn known_types(&self) -> KnownTypes {
+        KnownTypes {
+            imported: self
+                .types
+                .values()
+                .map(|custom_type| custom_type.name)
+                .collect(),
+            namespaces: self
+                .namespaces
+                .iter()
+                .map(|(name, namespace)| {
+                    let types = namespace.types().iter().map(|custom_type| custom_type.name);
+                    (name.clone(), types.collect())
+                })
+                .collect(),
+        }
+    }
+
+    /// Names of registered functions and namespaces, used to combine
+    /// multi-word names while parsing
+    pub fn known_names(&self) -> HashSet<String> {
+        let mut names: HashSet<String> = self.functions.keys().cloned().collect();
+        for (name, namespace) in self.namespaces.iter() {
+            names.insert(name.clone());
+            names.extend(namespace.functions().keys().cloned());
+        }
+        if let Some(environment) = self.environment.as_ref() {
+            names.extend(environment.borrow().known_names());
+        }
+        names
+    }
+
     pub fn get_all_vars(&self) -> Vec<(String, Variable)> {
         let mut vars = vec![];
         vars.extend(
@@ -839,3 +1425,111 @@ impl Environment {
         self.kill_flag = kill_flag
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        interpreter::Interpreter,
+        io::testing::{interpreter, run},
+        stdlib::native,
+    };
+
+    /// Namespace with commands that do nothing
+    fn actor(commands: &[&str]) -> Namespace {
+        let mut namespace = Namespace::default();
+        for command in commands {
+            namespace.register_native_function(command, native(&[], None, |_| Ok(None)));
+        }
+        namespace
+    }
+
+    /// Interpreter of `source` with the actors `Альфа` and `Бета`, both have
+    /// `шаг`
+    fn with_actors(source: &str) -> (Interpreter, Rc<RefCell<String>>) {
+        let (mut interpreter, output) = interpreter(source, &[]);
+        interpreter.register_namespace("Альфа", actor(&["шаг", "прыжок"]));
+        interpreter.register_namespace("Бета", actor(&["шаг", "поворот"]));
+        (interpreter, output)
+    }
+
+    fn error(source: &str) -> Message {
+        let (mut interpreter, _) = with_actors(source);
+        match interpreter.run().unwrap_err().message {
+            Error::Message(message) => message,
+            Error::Text(text) => panic!("{text}"),
+        }
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        let division_by_zero = Err(Message::DivisionByZero.into());
+        assert_eq!(run("вывод 1 / 0", &[]), division_by_zero);
+        assert_eq!(run("цел а\nа := 0\nвывод 5 / а", &[]), division_by_zero);
+        assert_eq!(run("вывод 1.5 / 0.0", &[]), division_by_zero);
+        assert_eq!(run("вывод 0 / 2", &[]), Ok("0".to_string()));
+    }
+
+    #[test]
+    fn command_of_an_actor_not_imported_names_the_actor() {
+        let message = error("поворот");
+        assert_eq!(message.code(), "R028");
+        assert_eq!(
+            message,
+            Message::ActorNotImported {
+                name: "поворот".to_string(),
+                actor: "Бета".to_string(),
+            }
+        );
+        let message = error("использовать Альфа\nповорот");
+        assert_eq!(message.code(), "R028");
+        assert_eq!(
+            error("разворот(1)"),
+            Message::UndefinedFunction("разворот".to_string())
+        );
+    }
+
+    #[test]
+    fn command_of_two_imported_actors_is_ambiguous() {
+        let message = error("использовать Альфа\nиспользовать Бета\nшаг");
+        assert_eq!(message.code(), "R029");
+        assert_eq!(
+            message,
+            Message::AmbiguousCommand {
+                name: "шаг".to_string(),
+                actors: vec!["Альфа".to_string(), "Бета".to_string()],
+            }
+        );
+
+        let (mut interpreter, _) =
+            with_actors("использовать Альфа\nиспользовать Бета\nпрыжок\nповорот");
+        assert!(interpreter.run().is_ok());
+        let (mut interpreter, _) = with_actors("использовать Бета\nиспользовать Бета\nшаг");
+        assert!(interpreter.run().is_ok());
+    }
+
+    #[test]
+    fn algorithm_of_the_program_overrides_a_command() {
+        let source = "использовать Альфа\nиспользовать Бета\nалг\nнач\nшаг\nкон\n\
+            алг шаг\nнач\nвывод \"свой\"\nкон";
+        let (mut interpreter, output) = with_actors(source);
+        interpreter.run().unwrap();
+        assert_eq!(output.take(), "свой");
+    }
+
+    #[test]
+    fn command_is_of_the_actor_it_was_imported_from() {
+        let (mut interpreter, _) = with_actors("использовать Бета\nшаг\nповорот");
+        interpreter.enable_trace();
+        interpreter.run().unwrap();
+        let trace = interpreter.trace().unwrap().take();
+        let effects: Vec<_> = trace
+            .entries()
+            .iter()
+            .flat_map(|entry| &entry.effects)
+            .map(|effect| (effect.actor.as_str(), effect.command.as_str()))
+            .collect();
+        assert_eq!(effects, [("Бета", "шаг"), ("Бета", "поворот")]);
+        assert_eq!(trace.actions_before(trace.len(), "Альфа"), 0);
+    }
+}
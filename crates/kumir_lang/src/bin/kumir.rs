@@ -0,0 +1,40 @@
+use std::{env, fs, process::ExitCode};
+
+use kumir_lang::formatter;
+
+const USAGE: &str = "Использование:
+    kumir fmt [--write] <файл>...";
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|arg| arg.as_str()) {
+        Some("fmt") => fmt(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn fmt(args: &[String]) -> Result<(), String> {
+    let write = args.iter().any(|arg| arg == "--write");
+    let files: Vec<&String> = args.iter().filter(|arg| *arg != "--write").collect();
+    if files.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    for file in files {
+        let code = fs::read_to_string(file).map_err(|err| format!("{file}: {err}"))?;
+        let formatted = formatter::format(&code).map_err(|err| format!("{file}: {err}"))?;
+        if write {
+            fs::write(file, formatted).map_err(|err| format!("{file}: {err}"))?;
+        } else {
+            print!("{formatted}");
+        }
+    }
+    Ok(())
+}
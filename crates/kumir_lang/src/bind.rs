@@ -0,0 +1,300 @@
+//! Rust functions as native algorithms, used by [`crate::kumir_alg`].
+//!
+//! Parameters and results are Rust values of a [`KumirType`]: `i32` for
+//! `цел`, `f32` for `вещ`, `bool` for `лог`, `char` for `сим` and `String`
+//! for `лит`. A type added by a namespace can implement it too. The result
+//! of the function is an [`AlgResult`]: nothing, a value or a `Result` of
+//! those, whose error, a [`Message`] or a text, becomes the error of the
+//! call.
+//!
+//! ```
+//! #[kumir_lang::kumir_alg("длина")]
+//! fn length(text: String) -> i32 {
+//!     text.chars().count() as i32
+//! }
+//!
+//! assert_eq!(length_alg().0, "длина");
+//! ```
+//!
+//! Generic and `async` functions can't be bound:
+//!
+//! ```compile_fail
+//! #[kumir_lang::kumir_alg("длина")]
+//! fn length<T: AsRef<str>>(text: T) -> i32 {
+//!     text.as_ref().chars().count() as i32
+//! }
+//! ```
+//!
+//! ```compile_fail
+//! #[kumir_lang::kumir_alg("длина")]
+//! async fn length(text: String) -> i32 {
+//!     text.chars().count() as i32
+//! }
+//! ```
+//!
+//! Nor can parameters that are references other than `&Environment` and
+//! `&mut`:
+//!
+//! ```compile_fail
+//! #[kumir_lang::kumir_alg("длина")]
+//! fn length(text: &String) -> i32 {
+//!     text.chars().count() as i32
+//! }
+//! ```
+
+use crate::{
+    ast::{Environment, Literal, NativeFunction},
+    lexer::{FunctionParamType, TypeDefinition},
+    messages::{Error, Message},
+};
+
+/// Rust type of the values of a Kumir type
+pub trait KumirType: Sized {
+    const TYPE: TypeDefinition;
+
+    fn from_literal(value: &Literal) -> Result<Self, Error>;
+
+    fn into_literal(self) -> Literal;
+}
+
+macro_rules! kumir_type {
+    ($rust:ty, $kumir:ident) => {
+        impl KumirType for $rust {
+            const TYPE: TypeDefinition = TypeDefinition::$kumir;
+
+            fn from_literal(value: &Literal) -> Result<Self, Error> {
+                match value.clone().convert_to(TypeDefinition::$kumir) {
+                    Literal::$kumir(value) => Ok(value),
+                    value => Err(Message::TypeMismatch {
+                        expected: TypeDefinition::$kumir,
+                        received: value.get_type(),
+                    }
+                    .into()),
+                }
+            }
+
+            fn into_literal(self) -> Literal {
+                Literal::$kumir(self)
+            }
+        }
+
+        impl AlgResult for $rust {
+            const TYPE: Option<TypeDefinition> = Some(<$rust as KumirType>::TYPE);
+
+            fn into_result(self) -> Result<Option<Literal>, Error> {
+                Ok(Some(self.into_literal()))
+            }
+        }
+
+        impl<E: Into<Error>> AlgResult for Result<$rust, E> {
+            const TYPE: Option<TypeDefinition> = Some(<$rust as KumirType>::TYPE);
+
+            fn into_result(self) -> Result<Option<Literal>, Error> {
+                self.map(|value| Some(value.into_literal()))
+                    .map_err(Into::into)
+            }
+        }
+    };
+}
+
+/// What a bound function returns, `TYPE` is `None` for a procedure
+pub trait AlgResult {
+    const TYPE: Option<TypeDefinition>;
+
+    fn into_result(self) -> Result<Option<Literal>, Error>;
+}
+
+kumir_type!(i32, Int);
+kumir_type!(f32, Float);
+kumir_type!(bool, Bool);
+kumir_type!(char, Char);
+kumir_type!(String, String);
+
+impl AlgResult for () {
+    const TYPE: Option<TypeDefinition> = None;
+
+    fn into_result(self) -> Result<Option<Literal>, Error> {
+        Ok(None)
+    }
+}
+
+impl<E: Into<Error>> AlgResult for Result<(), E> {
+    const TYPE: Option<TypeDefinition> = None;
+
+    fn into_result(self) -> Result<Option<Literal>, Error> {
+        self.map(|_| None).map_err(Into::into)
+    }
+}
+
+/// Value of the parameter at `index`
+pub fn argument<T: KumirType>(values: &[Option<Literal>], index: usize) -> Result<T, Error> {
+    match values.get(index) {
+        Some(Some(value)) => T::from_literal(value),
+        _ => Err(Message::ValueNotAssigned.into()),
+    }
+}
+
+/// Native algorithm whose `body` gets the scope of the call and the values
+/// of all parameters in declaration order, `None` for `рез` ones, and sets
+/// the results in place
+pub fn alg(
+    params: &[(&str, FunctionParamType, TypeDefinition)],
+    return_type: Option<TypeDefinition>,
+    body: impl FnMut(&Environment, &mut [Option<Literal>]) -> Result<Option<Literal>, Error> + 'static,
+) -> NativeFunction {
+    crate::stdlib::native_function(params, return_type, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{io::testing::interpreter, kumir_alg};
+
+    /// `&Environment` first, so the values of the other parameters are
+    /// one position left of them
+    #[kumir_alg("разложить")]
+    fn split(environment: &Environment, x: i32, half: &mut i32, #[argres] total: &mut i32) -> i32 {
+        assert!(environment.get_value("x").is_some());
+        *half = x / 2;
+        *total += x;
+        x * 2
+    }
+
+    #[test]
+    fn environment_takes_no_value() {
+        let source = "цел п, с\nс := 10\nвывод разложить(6, п, с), \" \", п, \" \", с";
+        let (mut interpreter, output) = interpreter(source, &[]);
+        let (name, function) = split_alg();
+        interpreter.register_native_function(name, function);
+        interpreter.run().unwrap();
+        assert_eq!(output.take(), "12 3 16");
+    }
+
+    /// Output of `source` with `functions` registered, or the error of the run
+    fn run(source: &str, functions: Vec<(&str, NativeFunction)>) -> Result<String, Error> {
+        let (mut interpreter, output) = interpreter(source, &[]);
+        for (name, function) in functions {
+            interpreter.register_native_function(name, function);
+        }
+        interpreter.run().map_err(|err| err.message)?;
+        Ok(output.take())
+    }
+
+    /// Sets `рез` to nothing, so it keeps the default value
+    #[kumir_alg("сбросить")]
+    fn reset(_value: &mut i32) {}
+
+    #[kumir_alg("обменять")]
+    fn swap(#[argres] first: &mut String, #[argres] second: &mut String) {
+        std::mem::swap(first, second);
+    }
+
+    #[test]
+    fn results_are_written_back() {
+        let source = "цел ч := 5\nлит а := \"а\"\nлит б := \"б\"\nсбросить(ч)\nобменять(а, б)\nвывод ч, а, б";
+        assert_eq!(
+            run(source, vec![reset_alg(), swap_alg()]),
+            Ok("0ба".to_string())
+        );
+    }
+
+    #[kumir_alg("корень")]
+    fn root(x: f32) -> Result<f32, String> {
+        match x < 0.0 {
+            true => Err(format!("корень из {x}")),
+            false => Ok(x.sqrt()),
+        }
+    }
+
+    #[kumir_alg("проверить")]
+    fn check(x: i32) -> Result<(), Message> {
+        match x {
+            0 => Err(Message::DivisionByZero),
+            _ => Ok(()),
+        }
+    }
+
+    #[test]
+    fn errors_of_results_fail_the_call() {
+        assert_eq!(
+            run("вывод корень(4)", vec![root_alg()]),
+            Ok("2".to_string())
+        );
+        assert_eq!(
+            run("вывод корень(0 - 1)", vec![root_alg()]),
+            Err("корень из -1".into())
+        );
+        assert_eq!(run("проверить(1)", vec![check_alg()]), Ok(String::new()));
+        assert_eq!(
+            run("проверить(0)", vec![check_alg()]),
+            Err(Message::DivisionByZero.into())
+        );
+    }
+
+    #[derive(Clone)]
+    struct Counter {
+        count: i32,
+    }
+
+    impl Counter {
+        #[kumir_alg("следующий")]
+        fn next(&mut self) -> i32 {
+            self.count += 1;
+            self.count
+        }
+
+        #[kumir_alg("сейчас")]
+        fn now(&self) -> i32 {
+            self.count
+        }
+
+        #[kumir_alg("после")]
+        fn after(mut self) -> i32 {
+            self.count += 1;
+            self.count
+        }
+    }
+
+    #[test]
+    fn methods_run_on_a_clone() {
+        let mut counter = Counter { count: 10 };
+        let next = counter.next_alg();
+        counter.count = 20;
+        let functions = vec![next, counter.now_alg(), counter.after_alg()];
+        counter.count = 30;
+        //`&mut self` keeps its clone between calls, `self` gets a new one
+        assert_eq!(
+            run(
+                "вывод следующий, \" \", следующий, \" \", сейчас, \" \", после, \" \", после",
+                functions
+            ),
+            Ok("11 12 20 21 21".to_string())
+        );
+        assert_eq!(counter.count, 30);
+    }
+
+    #[test]
+    fn conversions_of_literals() {
+        assert_eq!(f32::from_literal(&Literal::Int(2)), Ok(2.0));
+        assert_eq!(
+            i32::from_literal(&Literal::Float(2.0)),
+            Err(Message::TypeMismatch {
+                expected: TypeDefinition::Int,
+                received: TypeDefinition::Float,
+            }
+            .into())
+        );
+        assert_eq!(
+            char::from_literal(&Literal::String("а".to_string())),
+            Err(Message::TypeMismatch {
+                expected: TypeDefinition::Char,
+                received: TypeDefinition::String,
+            }
+            .into())
+        );
+        assert_eq!(
+            argument::<bool>(&[None], 0),
+            Err(Message::ValueNotAssigned.into())
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Time as `ждать` and `время` see it, see `userdocs/md_output/sys_func.md`.
+//! [`SystemClock`] is the real time, [`VirtualClock`] only moves when the
+//! program waits, so waiting programs finish instantly and always print the
+//! same times.
+
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use chrono::Timelike;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub trait Clock {
+    /// Time since the local midnight
+    fn time_of_day(&self) -> Duration;
+
+    /// Blocks for `duration`, returns early once `kill_flag` is set
+    fn sleep(&self, duration: Duration, kill_flag: &AtomicBool);
+}
+
+impl fmt::Debug for dyn Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clock")
+            .field("time_of_day", &self.time_of_day())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn time_of_day(&self) -> Duration {
+        let time = chrono::Local::now().time();
+        Duration::new(
+            time.num_seconds_from_midnight().into(),
+            //A leap second is reported as nanoseconds past 10^9
+            time.nanosecond().min(999_999_999),
+        )
+    }
+
+    fn sleep(&self, duration: Duration, kill_flag: &AtomicBool) {
+        let end = web_time::Instant::now() + duration;
+        while !kill_flag.load(Ordering::Relaxed) {
+            let left = end.saturating_duration_since(web_time::Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            std::thread::sleep(left.min(Duration::from_millis(50)));
+        }
+    }
+}
+
+/// Clock that starts at a given time of day and moves only by `sleep`.
+/// Clones share the time
+#[derive(Debug, Clone, Default)]
+pub struct VirtualClock {
+    millis: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    pub fn new(time_of_day: Duration) -> Self {
+        Self {
+            millis: Arc::new(AtomicU64::new(time_of_day.as_millis() as u64)),
+        }
+    }
+
+    /// Time since the start, including the time of day it started at
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_millis(self.millis.load(Ordering::Relaxed))
+    }
+}
+
+impl Clock for VirtualClock {
+    fn time_of_day(&self) -> Duration {
+        Duration::from_millis(self.elapsed().as_millis() as u64 % DAY.as_millis() as u64)
+    }
+
+    fn sleep(&self, duration: Duration, _: &AtomicBool) {
+        self.millis
+            .fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,243 @@
+//! Which statements and branches of `если` a program ran, over one or more
+//! runs. Enabled with [`crate::interpreter::Interpreter::enable_coverage`],
+//! written as lcov by [`Coverage::to_lcov`] or read per line for the gutter
+//! of the editor with [`Coverage::lines`]. Branches of `выбор` are not
+//! counted, only the lines of its statements.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    ast::{AstNode, Stmt},
+    lexer::Position,
+};
+
+/// Hits of a line and of the `если` starting on it
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LineCoverage {
+    pub line: usize,
+    /// Statements entered on the line
+    pub hits: u64,
+    /// Times each `если` on the line went to `то` and to `иначе`, the
+    /// second one counts a missing `иначе` too
+    pub branches: Vec<[u64; 2]>,
+}
+
+impl LineCoverage {
+    /// Ran, and every branch of it ran
+    pub fn is_covered(&self) -> bool {
+        self.hits > 0 && self.branches.iter().flatten().all(|&hits| hits > 0)
+    }
+}
+
+/// Hits of every statement of the program, zero for those that never ran
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Coverage {
+    lines: BTreeMap<usize, u64>,
+    /// By line and column of the `если`
+    branches: BTreeMap<(usize, usize), [u64; 2]>,
+}
+
+impl Coverage {
+    /// Adds the statements of `program` that aren't known yet, with no hits.
+    /// Lines of `алг` have only their bodies, the definition never runs
+    pub fn add_program(&mut self, program: &AstNode) {
+        for statement in program.statements() {
+            if statement.is_definition() {
+                continue;
+            }
+            let start = statement.span.start;
+            self.lines.entry(start.line).or_default();
+            if let Stmt::Condition(_) = statement.stmt {
+                self.branches.entry((start.line, start.column)).or_default();
+            }
+        }
+    }
+
+    pub(crate) fn statement_entered(&mut self, line: usize) {
+        *self.lines.entry(line).or_default() += 1;
+    }
+
+    /// `если` at `position` went to `то` when `taken`
+    pub(crate) fn branch_taken(&mut self, position: Position, taken: bool) {
+        let hits = self
+            .branches
+            .entry((position.line, position.column))
+            .or_default();
+        hits[usize::from(!taken)] += 1;
+    }
+
+    /// Adds the hits of `other`, e.g. of the same program run on another
+    /// field
+    pub fn merge(&mut self, other: &Coverage) {
+        for (line, hits) in &other.lines {
+            *self.lines.entry(*line).or_default() += hits;
+        }
+        for (position, hits) in &other.branches {
+            let total = self.branches.entry(*position).or_default();
+            total[0] += hits[0];
+            total[1] += hits[1];
+        }
+    }
+
+    /// Every line with a statement, by number
+    pub fn lines(&self) -> Vec<LineCoverage> {
+        self.lines
+            .iter()
+            .map(|(&line, &hits)| LineCoverage {
+                line,
+                hits,
+                branches: self
+                    .branches
+                    .range((line, 0)..(line + 1, 0))
+                    .map(|(_, hits)| *hits)
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Lines that never ran or have a branch that never ran
+    pub fn uncovered_lines(&self) -> Vec<usize> {
+        self.lines()
+            .into_iter()
+            .filter(|line| !line.is_covered())
+            .map(|line| line.line)
+            .collect()
+    }
+
+    /// Report in the lcov tracefile format for the source file `path`
+    pub fn to_lcov(&self, path: &str) -> String {
+        let mut lcov = format!("TN:\nSF:{path}\n");
+        let lines = self.lines();
+        let (mut found, mut hit) = (0, 0);
+        for line in &lines {
+            for (block, hits) in line.branches.iter().enumerate() {
+                for (branch, taken) in hits.iter().enumerate() {
+                    let taken = match line.hits {
+                        0 => "-".to_string(),
+                        _ => taken.to_string(),
+                    };
+                    let _ = writeln!(lcov, "BRDA:{},{block},{branch},{taken}", line.line);
+                }
+                found += 2;
+                hit += hits.iter().filter(|&&hits| hits > 0).count();
+            }
+        }
+        let _ = writeln!(lcov, "BRF:{found}\nBRH:{hit}");
+        for line in &lines {
+            let _ = writeln!(lcov, "DA:{},{}", line.line, line.hits);
+        }
+        let _ = writeln!(
+            lcov,
+            "LF:{}\nLH:{}",
+            lines.len(),
+            lines.iter().filter(|line| line.hits > 0).count()
+        );
+        lcov.push_str("end_of_record\n");
+        lcov
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::interpreter;
+
+    const SIGN: &str = "алг\nнач\nцел n\nввод n\nесли n > 0 то\nвывод \"+\"\nиначе\nвывод \"-\"\nвсе\nесли n > 5 то\nвывод \"0\"\nвсе\nкон";
+
+    fn coverage(input: &str) -> Coverage {
+        let (mut interpreter, _) = interpreter(SIGN, &[input]);
+        interpreter.enable_coverage();
+        interpreter.run().unwrap();
+        interpreter.coverage().unwrap()
+    }
+
+    #[test]
+    fn uncalled_algorithm_has_no_hits() {
+        let source = "алг квадрат(арг цел х)\nнач\nвывод х * х\nкон\nалг куб(арг цел х)\nнач\nвывод х * х * х\nкон\nквадрат(2)";
+        let (mut interpreter, _) = interpreter(source, &[]);
+        interpreter.enable_coverage();
+        interpreter.run().unwrap();
+        let lcov = interpreter.coverage().unwrap().to_lcov("программа.kum");
+        let lines: Vec<&str> = lcov
+            .lines()
+            .filter(|line| line.starts_with("DA:"))
+            .collect();
+        assert_eq!(lines, ["DA:3,1", "DA:7,0", "DA:9,1"]);
+        assert!(lcov.contains("LF:3\nLH:2\n"));
+    }
+
+    #[test]
+    fn branches_of_a_run() {
+        let coverage = coverage("1");
+        let lcov = coverage.to_lcov("знак.kum");
+        let branches: Vec<&str> = lcov.lines().filter(|line| line.starts_with("BR")).collect();
+        assert_eq!(
+            branches,
+            [
+                "BRDA:5,0,0,1",
+                "BRDA:5,0,1,0",
+                "BRDA:10,0,0,0",
+                "BRDA:10,0,1,1",
+                "BRF:4",
+                "BRH:2"
+            ]
+        );
+        assert_eq!(coverage.uncovered_lines(), [5, 8, 10, 11]);
+    }
+
+    #[test]
+    fn merged_runs_add_up() {
+        let mut coverage = coverage("1");
+        coverage.merge(&self::coverage("-1"));
+        let hits: Vec<_> = coverage
+            .lines()
+            .into_iter()
+            .map(|line| (line.line, line.hits, line.branches))
+            .collect();
+        assert_eq!(
+            hits,
+            [
+                (3, 2, vec![]),
+                (4, 2, vec![]),
+                (5, 2, vec![[1, 1]]),
+                (6, 1, vec![]),
+                (8, 1, vec![]),
+                (10, 2, vec![[0, 2]]),
+                (11, 0, vec![]),
+            ]
+        );
+        assert_eq!(coverage.uncovered_lines(), [10, 11]);
+    }
+
+    #[test]
+    fn branches_on_one_line_in_order_of_columns() {
+        let mut coverage = Coverage::default();
+        coverage.statement_entered(2);
+        coverage.branch_taken(
+            Position {
+                line: 2,
+                column: 20,
+            },
+            false,
+        );
+        coverage.branch_taken(Position { line: 2, column: 1 }, true);
+        coverage.branch_taken(Position { line: 2, column: 1 }, true);
+        assert_eq!(coverage.lines()[0].branches, [[2, 0], [0, 1]]);
+        assert!(
+            coverage
+                .to_lcov("а.kum")
+                .contains("BRDA:2,0,0,2\nBRDA:2,0,1,0\nBRDA:2,1,0,0\nBRDA:2,1,1,1\nBRF:4\nBRH:2\n")
+        );
+    }
+
+    #[test]
+    fn branches_of_lines_that_never_ran() {
+        let source =
+            "алг\nнач\nвывод 1\nкон\nалг ф(арг цел х)\nнач\nесли х > 0 то\nвывод х\nвсе\nкон";
+        let (mut interpreter, _) = interpreter(source, &[]);
+        interpreter.enable_coverage();
+        interpreter.run().unwrap();
+        let lcov = interpreter.coverage().unwrap().to_lcov("а.kum");
+        assert!(lcov.contains("BRDA:7,0,0,-\nBRDA:7,0,1,-\nBRF:2\nBRH:0\n"));
+    }
+}
@@ -0,0 +1,651 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    fmt,
+    rc::Rc,
+    sync::{
+        Arc, Mutex,
+        atomic::Ordering,
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    time::Duration,
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    ast::{Environment, FunctionParameter, Literal},
+    error::RuntimeError,
+    inspector::{self, ProgramState},
+    lexer::{FunctionParamType, Span},
+    messages::{Error, Message},
+    snapshot,
+    trace::Trace,
+};
+
+/// How execution continues after a stop
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum StepMode {
+    /// Stop at the next statement, entering called algorithms
+    Statement,
+    /// Stop at the next statement of the current algorithm or its callers
+    Over,
+    /// Stop at the next statement after the current algorithm returns
+    Out,
+    /// Stop only at breakpoints
+    #[default]
+    Continue,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StopReason {
+    Step,
+    Breakpoint,
+    /// Statement a [`crate::snapshot::Snapshot`] was restored at
+    Restored,
+    /// Statement of the [`crate::trace::Trace`] stepped back to, the
+    /// program stays at a later one
+    Rewound,
+}
+
+/// Kumir algorithm call that is currently executing
+#[derive(Debug, PartialEq, Clone)]
+pub struct CallFrame {
+    pub name: String,
+    pub call_site: Span,
+    /// Parameter names with their `арг`/`рез`/`аргрез` kind, in declaration order
+    pub params: Vec<(String, FunctionParamType)>,
+    /// Values of the parameters at the call, `None` for `рез`
+    pub args: Vec<Option<Literal>>,
+}
+
+impl fmt::Display for CallFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| inspector::value_text(arg.as_ref()))
+            .collect::<Vec<_>>();
+        write!(f, "{}({})", self.name, args.join(", "))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Stop {
+    pub reason: StopReason,
+    /// Statement that is about to be executed
+    pub span: Span,
+    /// Algorithm calls, the innermost one is the last
+    pub call_stack: Vec<CallFrame>,
+}
+
+/// Decides how to continue every time execution stops.
+///
+/// Called on the interpreter thread, so a closure is enough for synchronous
+/// use, while [`DebugHandle`] drives the interpreter from another thread.
+pub trait StopHandler {
+    /// Called before the first statement, returns how to start
+    fn started(&mut self, _environment: &Rc<RefCell<Environment>>) -> StepMode {
+        StepMode::Continue
+    }
+
+    fn stopped(&mut self, stop: &Stop, environment: &Rc<RefCell<Environment>>) -> StepMode;
+
+    fn finished(&mut self, _result: &Result<(), RuntimeError>) {}
+
+    /// Breakpoints shared with the owner of the handler
+    fn breakpoints(&self) -> Option<Breakpoints> {
+        None
+    }
+}
+
+impl<F> StopHandler for F
+where
+    F: FnMut(&Stop, &Rc<RefCell<Environment>>) -> StepMode,
+{
+    fn stopped(&mut self, stop: &Stop, environment: &Rc<RefCell<Environment>>) -> StepMode {
+        self(stop, environment)
+    }
+}
+
+/// Line breakpoints, can be changed from any thread while the program runs
+#[derive(Debug, Clone, Default)]
+pub struct Breakpoints(Arc<Mutex<BTreeSet<usize>>>);
+
+impl Breakpoints {
+    pub fn set(&self, line: usize) {
+        self.0.lock().unwrap().insert(line);
+    }
+
+    pub fn clear(&self, line: usize) {
+        self.0.lock().unwrap().remove(&line);
+    }
+
+    pub fn clear_all(&self) {
+        self.0.lock().unwrap().clear();
+    }
+
+    pub fn contains(&self, line: usize) -> bool {
+        self.0.lock().unwrap().contains(&line)
+    }
+
+    pub fn lines(&self) -> Vec<usize> {
+        self.0.lock().unwrap().iter().copied().collect()
+    }
+}
+
+/// Stepping state of a running program, shared by all of its scopes
+#[derive(Default)]
+pub struct Debugger {
+    pub breakpoints: Breakpoints,
+    pub call_stack: Vec<CallFrame>,
+    pub current_span: Span,
+    mode: StepMode,
+    mode_depth: usize,
+    last_line: Option<usize>,
+    /// Innermost scope of every caller, parallel to `call_stack`
+    caller_scopes: Vec<Rc<RefCell<Environment>>>,
+    /// Where the error the program is failing with happened
+    error: Option<RuntimeError>,
+    handler: Option<Box<dyn StopHandler>>,
+    /// Statements entered since the start
+    steps: usize,
+    /// Statement count and state a snapshot being replayed stops at
+    restore: Option<(usize, Vec<String>)>,
+}
+
+impl std::fmt::Debug for Debugger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Debugger")
+            .field("breakpoints", &self.breakpoints)
+            .field("call_stack", &self.call_stack)
+            .field("current_span", &self.current_span)
+            .field("mode", &self.mode)
+            .finish()
+    }
+}
+
+impl Debugger {
+    pub fn attach(&mut self, handler: Box<dyn StopHandler>) {
+        if let Some(breakpoints) = handler.breakpoints() {
+            self.breakpoints = breakpoints;
+        }
+        self.handler = Some(handler);
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.handler.is_some()
+    }
+
+    /// `caller` is the scope the call is made from, it is kept so the
+    /// caller's variables can be inspected while the call runs. `callee`
+    /// holds the passed arguments
+    pub fn enter_call(
+        &mut self,
+        name: &str,
+        params: &IndexMap<String, FunctionParameter>,
+        caller: &Rc<RefCell<Environment>>,
+        callee: &Rc<RefCell<Environment>>,
+    ) {
+        self.call_stack.push(CallFrame {
+            name: name.to_string(),
+            call_site: self.current_span,
+            params: params
+                .iter()
+                .map(|(name, param)| (name.clone(), param.result_type))
+                .collect(),
+            args: params
+                .keys()
+                .map(|name| callee.borrow().get_value(name))
+                .collect(),
+        });
+        self.caller_scopes.push(caller.clone());
+    }
+
+    pub fn exit_call(&mut self) {
+        self.caller_scopes.pop();
+        if let Some(frame) = self.call_stack.pop() {
+            self.current_span = frame.call_site;
+        }
+    }
+
+    pub fn caller_scopes(&self) -> &[Rc<RefCell<Environment>>] {
+        &self.caller_scopes
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Runs without stops until the `steps`th statement, where the program
+    /// must be in `state`, see [`crate::snapshot`]
+    pub(crate) fn restore_at(&mut self, steps: usize, state: Vec<String>) {
+        self.restore = Some((steps, state));
+    }
+
+    /// Whether a snapshot was still being replayed, it is given up
+    pub(crate) fn abandon_restore(&mut self) -> bool {
+        self.restore.take().is_some()
+    }
+
+    /// Remembers where an error happened, only the innermost statement
+    /// that fails with it is kept
+    pub fn error_raised(&mut self, span: Span, message: Error) {
+        if self.error.is_none() {
+            self.error = Some(RuntimeError {
+                message,
+                span,
+                call_stack: self.call_stack.clone(),
+            });
+        }
+    }
+
+    /// Error the program failed with, located where it was raised
+    pub fn take_error(&mut self, message: Error) -> RuntimeError {
+        let mut error = self.error.take().unwrap_or_else(|| RuntimeError {
+            message: message.clone(),
+            span: self.current_span,
+            call_stack: self.call_stack.clone(),
+        });
+        error.message = message;
+        error
+    }
+
+    /// Loop body is entered again, its lines count as new ones
+    pub(crate) fn next_iteration(&mut self) {
+        self.last_line = None;
+    }
+
+    fn stop_reason(&mut self, span: Span) -> Option<StopReason> {
+        self.handler.as_ref()?;
+        let line = span.start.line;
+        let same_line = self.last_line == Some(line);
+        self.last_line = Some(line);
+        if !same_line && self.breakpoints.contains(line) {
+            return Some(StopReason::Breakpoint);
+        }
+        let depth = self.call_stack.len();
+        match self.mode {
+            StepMode::Statement => Some(StopReason::Step),
+            StepMode::Over if depth <= self.mode_depth => Some(StopReason::Step),
+            StepMode::Out if depth < self.mode_depth => Some(StopReason::Step),
+            _ => None,
+        }
+    }
+
+    fn resume(&mut self, mode: StepMode) {
+        self.mode = mode;
+        self.mode_depth = self.call_stack.len();
+    }
+
+    /// Handler is taken out while it runs, so it may freely inspect the
+    /// environment and the debugger itself
+    fn with_handler(
+        debugger: &Rc<RefCell<Debugger>>,
+        call: impl FnOnce(&mut Box<dyn StopHandler>) -> StepMode,
+    ) {
+        let handler = debugger.borrow_mut().handler.take();
+        if let Some(mut handler) = handler {
+            let mode = call(&mut handler);
+            let mut debugger = debugger.borrow_mut();
+            debugger.resume(mode);
+            debugger.handler = Some(handler);
+        }
+    }
+
+    pub fn started(debugger: &Rc<RefCell<Debugger>>, environment: &Rc<RefCell<Environment>>) {
+        debugger.borrow_mut().steps = 0;
+        Self::with_handler(debugger, |handler| handler.started(environment));
+    }
+
+    /// Fails when a replayed snapshot reaches its statement in another state
+    pub fn statement_entered(
+        debugger: &Rc<RefCell<Debugger>>,
+        span: Span,
+        environment: &Rc<RefCell<Environment>>,
+    ) -> Result<(), Error> {
+        let (stop, restored) = {
+            let mut debugger = debugger.borrow_mut();
+            debugger.current_span = span;
+            debugger.steps += 1;
+            match debugger.restore.take() {
+                Some((steps, state)) if debugger.steps < steps => {
+                    debugger.restore = Some((steps, state));
+                    return Ok(());
+                }
+                Some((_, state)) => (Some(StopReason::Restored), Some(state)),
+                None => (debugger.stop_reason(span), None),
+            }
+        };
+        if let Some(state) = restored {
+            environment
+                .borrow()
+                .io
+                .journal()
+                .borrow_mut()
+                .finish_replay();
+            if snapshot::state_lines(&inspector::inspect(environment)) != state {
+                return Err(Message::SnapshotMismatch.into());
+            }
+            debugger.borrow_mut().last_line = Some(span.start.line);
+        }
+        if let Some(reason) = stop {
+            let stop = Stop {
+                reason,
+                span,
+                call_stack: debugger.borrow().call_stack.clone(),
+            };
+            Self::with_handler(debugger, |handler| handler.stopped(&stop, environment));
+        }
+        Ok(())
+    }
+
+    pub fn finished(debugger: &Rc<RefCell<Debugger>>, result: &Result<(), RuntimeError>) {
+        let handler = debugger.borrow_mut().handler.take();
+        if let Some(mut handler) = handler {
+            handler.finished(result);
+            debugger.borrow_mut().handler = Some(handler);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DebugEvent {
+    Stopped(Stop),
+    /// Recorded statement shown after stepping back, with the actions of
+    /// every actor run before it, see [`DebugHandle::step_back`]
+    Rewound {
+        stop: Stop,
+        actions: IndexMap<String, usize>,
+    },
+    Inspected(ProgramState),
+    Finished(Result<(), RuntimeError>),
+}
+
+enum DebugCommand {
+    Resume(StepMode),
+    StepBack,
+    Inspect,
+}
+
+/// Controls a program running on another thread. Every command resumes the
+/// program and blocks until it stops again or finishes.
+pub struct DebugHandle {
+    commands: Sender<DebugCommand>,
+    events: Mutex<Receiver<DebugEvent>>,
+    finished: Mutex<Option<Result<(), RuntimeError>>>,
+    pub breakpoints: Breakpoints,
+}
+
+impl DebugHandle {
+    /// Handle and the [`StopHandler`] to attach to the interpreter with
+    /// [`crate::interpreter::Interpreter::attach_debugger`]
+    pub fn new() -> (Self, RemoteStopHandler) {
+        let (commands_tx, commands_rx) = mpsc::channel();
+        let (events_tx, events_rx) = mpsc::channel();
+        let breakpoints = Breakpoints::default();
+        (
+            Self {
+                commands: commands_tx,
+                events: Mutex::new(events_rx),
+                finished: Mutex::new(None),
+                breakpoints: breakpoints.clone(),
+            },
+            RemoteStopHandler {
+                commands: commands_rx,
+                events: events_tx,
+                breakpoints,
+                stop: None,
+                rewound: None,
+            },
+        )
+    }
+
+    pub fn step_statement(&self) -> DebugEvent {
+        self.resume(StepMode::Statement)
+    }
+
+    pub fn step_over(&self) -> DebugEvent {
+        self.resume(StepMode::Over)
+    }
+
+    pub fn step_out(&self) -> DebugEvent {
+        self.resume(StepMode::Out)
+    }
+
+    pub fn continue_until_breakpoint(&self) -> DebugEvent {
+        self.resume(StepMode::Continue)
+    }
+
+    /// Shows the statement before the one shown, needs
+    /// [`crate::interpreter::Interpreter::enable_trace`]. The program stays
+    /// where it is: [`DebugHandle::step_statement`] goes forward through the
+    /// recorded statements back to it, other commands continue from it. At
+    /// the first statement, or without a trace, the stop is sent again
+    pub fn step_back(&self) -> DebugEvent {
+        self.send(DebugCommand::StepBack)
+    }
+
+    pub fn set_breakpoint(&self, line: usize) {
+        self.breakpoints.set(line);
+    }
+
+    pub fn clear_breakpoint(&self, line: usize) {
+        self.breakpoints.clear(line);
+    }
+
+    /// Variables and call stack of the stopped program, or of the statement
+    /// stepped back to, `None` when it isn't running
+    pub fn inspect(&self) -> Option<ProgramState> {
+        match self.send(DebugCommand::Inspect) {
+            DebugEvent::Inspected(state) => Some(state),
+            _ => None,
+        }
+    }
+
+    fn resume(&self, mode: StepMode) -> DebugEvent {
+        self.send(DebugCommand::Resume(mode))
+    }
+
+    fn send(&self, command: DebugCommand) -> DebugEvent {
+        let mut finished = self.finished.lock().unwrap();
+        if let Some(result) = finished.as_ref() {
+            return DebugEvent::Finished(result.clone());
+        }
+        let event = match self.commands.send(command) {
+            Ok(()) => self.events.lock().unwrap().recv().ok(),
+            Err(_) => None,
+        }
+        .unwrap_or(DebugEvent::Finished(Err(RuntimeError::new(
+            Message::NotRunning,
+        ))));
+        if let DebugEvent::Finished(result) = &event {
+            *finished = Some(result.clone());
+        }
+        event
+    }
+}
+
+/// [`StopHandler`] counterpart of [`DebugHandle`]
+pub struct RemoteStopHandler {
+    commands: Receiver<DebugCommand>,
+    events: Sender<DebugEvent>,
+    breakpoints: Breakpoints,
+    /// Where the program is stopped
+    stop: Option<Stop>,
+    /// Statement of the trace stepped back to
+    rewound: Option<usize>,
+}
+
+impl RemoteStopHandler {
+    /// Waits for the next command, gives up when the program is killed or
+    /// the handle is dropped
+    fn next_command(&mut self, environment: &Rc<RefCell<Environment>>) -> StepMode {
+        let kill_flag = environment.borrow().kill_flag.clone();
+        let trace = environment.borrow().trace.clone();
+        loop {
+            match self.commands.recv_timeout(Duration::from_millis(50)) {
+                Ok(DebugCommand::Resume(mode)) => {
+                    let forward = match (self.rewound.take(), &trace) {
+                        (Some(index), Some(trace)) if mode == StepMode::Statement => {
+                            Some((index + 1, trace))
+                        }
+                        _ => None,
+                    };
+                    match forward {
+                        None => return mode,
+                        Some((index, trace)) => self.show(index, &trace.borrow()),
+                    }
+                }
+                Ok(DebugCommand::StepBack) => {
+                    let index = self.rewound.or_else(|| {
+                        let trace = trace.as_ref()?.borrow();
+                        self.stop.as_ref().and(trace.len().checked_sub(1))
+                    });
+                    match (index, &trace) {
+                        (Some(index), Some(trace)) => {
+                            self.show(index.saturating_sub(1), &trace.borrow())
+                        }
+                        _ => self.show_stop(environment),
+                    }
+                }
+                Ok(DebugCommand::Inspect) => {
+                    let state = self
+                        .rewound
+                        .zip(trace.as_ref())
+                        .and_then(|(index, trace)| trace.borrow().state_at(index))
+                        .unwrap_or_else(|| inspector::inspect(environment));
+                    let _ = self.events.send(DebugEvent::Inspected(state));
+                }
+                Err(RecvTimeoutError::Disconnected) => return StepMode::Continue,
+                Err(RecvTimeoutError::Timeout) if kill_flag.load(Ordering::Relaxed) => {
+                    return StepMode::Continue;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        }
+    }
+}
+
+impl RemoteStopHandler {
+    /// Sends the recorded statement at `index`, or the stop once `index`
+    /// reaches the statement the program is at
+    fn show(&mut self, index: usize, trace: &Trace) {
+        let present = trace.len().saturating_sub(1);
+        let Some(state) = trace.state_at(index).filter(|_| index < present) else {
+            self.rewound = None;
+            if let Some(stop) = &self.stop {
+                let _ = self.events.send(DebugEvent::Stopped(stop.clone()));
+            }
+            return;
+        };
+        self.rewound = Some(index);
+        let mut actions = IndexMap::new();
+        for effect in trace.entries()[..index]
+            .iter()
+            .flat_map(|entry| &entry.effects)
+        {
+            *actions.entry(effect.actor.clone()).or_default() += 1;
+        }
+        let call_stack = state
+            .call_stack
+            .iter()
+            .map(|frame| CallFrame {
+                name: frame.name.clone(),
+                call_site: frame.call_site,
+                params: frame
+                    .parameters
+                    .iter()
+                    .map(|parameter| (parameter.variable.name.clone(), parameter.kind))
+                    .collect(),
+                args: frame
+                    .parameters
+                    .iter()
+                    .map(|parameter| match parameter.kind {
+                        FunctionParamType::ResultParam => None,
+                        _ => parameter.variable.value.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        let stop = Stop {
+            reason: StopReason::Rewound,
+            span: state.span,
+            call_stack,
+        };
+        let _ = self.events.send(DebugEvent::Rewound { stop, actions });
+    }
+
+    /// Answers a step back that has nothing to go back to
+    fn show_stop(&self, environment: &Rc<RefCell<Environment>>) {
+        let event = match &self.stop {
+            Some(stop) => DebugEvent::Stopped(stop.clone()),
+            None => DebugEvent::Inspected(inspector::inspect(environment)),
+        };
+        let _ = self.events.send(event);
+    }
+}
+
+impl StopHandler for RemoteStopHandler {
+    fn started(&mut self, environment: &Rc<RefCell<Environment>>) -> StepMode {
+        self.stop = None;
+        self.rewound = None;
+        self.next_command(environment)
+    }
+
+    fn stopped(&mut self, stop: &Stop, environment: &Rc<RefCell<Environment>>) -> StepMode {
+        self.stop = Some(stop.clone());
+        self.rewound = None;
+        let _ = self.events.send(DebugEvent::Stopped(stop.clone()));
+        self.next_command(environment)
+    }
+
+    fn finished(&mut self, result: &Result<(), RuntimeError>) {
+        let _ = self.events.send(DebugEvent::Finished(result.clone()));
+    }
+
+    fn breakpoints(&self) -> Option<Breakpoints> {
+        Some(self.breakpoints.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    /// Lines the program stops at with the breakpoints at `lines`
+    fn breakpoint_stops(source: &str, lines: &[usize]) -> Vec<usize> {
+        let mut interpreter =
+            Interpreter::new_from_string(source, Arc::new(AtomicBool::new(false))).unwrap();
+        for line in lines {
+            interpreter.set_breakpoint(*line);
+        }
+        let stops = Rc::new(RefCell::new(vec![]));
+        let recorded = stops.clone();
+        interpreter.attach_debugger(move |stop: &Stop, _: &Rc<RefCell<Environment>>| {
+            assert_eq!(stop.reason, StopReason::Breakpoint);
+            recorded.borrow_mut().push(stop.span.start.line);
+            StepMode::Continue
+        });
+        interpreter.run().unwrap();
+        stops.take()
+    }
+
+    #[test]
+    fn breakpoint_in_a_loop_stops_on_every_iteration() {
+        let source = "цел а\nа := 0\nнц пока а < 3\n    а := а + 1\nкц\n";
+        assert_eq!(breakpoint_stops(source, &[4]), [4, 4, 4]);
+
+        let source = "цел а\nнц для и от 0 до 3\n    а := и\nкц\n";
+        assert_eq!(breakpoint_stops(source, &[3]), [3, 3, 3]);
+    }
+
+    #[test]
+    fn breakpoint_stops_once_per_line() {
+        let source = "цел а, б\nа := 1; б := 2\nвывод \"\"\n";
+        assert_eq!(breakpoint_stops(source, &[2, 3]), [2, 3]);
+    }
+}
@@ -0,0 +1,165 @@
+//! Text encodings of files, see `установить кодировку` in
+//! `userdocs/md_output/files.md`. The single-byte encodings share ASCII and
+//! differ in the upper half
+
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Cp1251,
+    Cp866,
+    Koi8R,
+}
+
+/// Names as Kumir accepts them, in any case and with or without the hyphen
+impl FromStr for Encoding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().replace('-', "").as_str() {
+            "UTF8" | "UTF" | "LINUX" => Ok(Encoding::Utf8),
+            "CP1251" | "WINDOWS1251" | "WINDOWS" => Ok(Encoding::Cp1251),
+            "CP866" | "IBM866" | "DOS" => Ok(Encoding::Cp866),
+            "KOI8R" | "KOI8" | "КОИ8" | "КОИ8Р" => Ok(Encoding::Koi8R),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Encoding {
+    /// Characters missing from the encoding are written as `?`
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+            _ => text
+                .chars()
+                .map(|char| self.encode_char(char).unwrap_or(b'?'))
+                .collect(),
+        }
+    }
+
+    /// Bytes that aren't text in the encoding are read as `�`
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            _ => bytes
+                .iter()
+                .map(|byte| {
+                    self.decode_byte(*byte)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER)
+                })
+                .collect(),
+        }
+    }
+
+    /// Byte of `char` in a single-byte encoding, `None` for UTF-8
+    pub fn encode_char(self, char: char) -> Option<u8> {
+        if char.is_ascii() {
+            return (self != Encoding::Utf8).then_some(char as u8);
+        }
+        self.upper_half()?
+            .iter()
+            .position(|upper| *upper == char)
+            .map(|position| position as u8 + 0x80)
+    }
+
+    /// Character of `byte` in a single-byte encoding, `None` for UTF-8
+    pub fn decode_byte(self, byte: u8) -> Option<char> {
+        if byte.is_ascii() {
+            return (self != Encoding::Utf8).then_some(byte as char);
+        }
+        Some(self.upper_half()?[byte as usize - 0x80]).filter(|char| *char != '\0')
+    }
+
+    /// Characters 0x80..=0xFF, `\0` where the byte is unassigned or a
+    /// pseudographic symbol Kumir doesn't read
+    fn upper_half(self) -> Option<&'static [char; 128]> {
+        match self {
+            Encoding::Utf8 => None,
+            Encoding::Cp1251 => Some(&CP1251),
+            Encoding::Cp866 => Some(&CP866),
+            Encoding::Koi8R => Some(&KOI8R),
+        }
+    }
+}
+
+#[rustfmt::skip]
+const CP1251: [char; 128] = [
+    'Ђ', 'Ѓ', '‚', 'ѓ', '„', '…', '†', '‡', '€', '‰', 'Љ', '‹', 'Њ', 'Ќ', 'Ћ', 'Џ',
+    'ђ', '‘', '’', '“', '”', '•', '–', '—', '\0', '™', 'љ', '›', 'њ', 'ќ', 'ћ', 'џ',
+    '\u{A0}', 'Ў', 'ў', 'Ј', '¤', 'Ґ', '¦', '§', 'Ё', '©', 'Є', '«', '¬', '\u{AD}', '®', 'Ї',
+    '°', '±', 'І', 'і', 'ґ', 'µ', '¶', '·', 'ё', '№', 'є', '»', 'ј', 'Ѕ', 'ѕ', 'ї',
+    'А', 'Б', 'В', 'Г', 'Д', 'Е', 'Ж', 'З', 'И', 'Й', 'К', 'Л', 'М', 'Н', 'О', 'П',
+    'Р', 'С', 'Т', 'У', 'Ф', 'Х', 'Ц', 'Ч', 'Ш', 'Щ', 'Ъ', 'Ы', 'Ь', 'Э', 'Ю', 'Я',
+    'а', 'б', 'в', 'г', 'д', 'е', 'ж', 'з', 'и', 'й', 'к', 'л', 'м', 'н', 'о', 'п',
+    'р', 'с', 'т', 'у', 'ф', 'х', 'ц', 'ч', 'ш', 'щ', 'ъ', 'ы', 'ь', 'э', 'ю', 'я',
+];
+
+#[rustfmt::skip]
+const CP866: [char; 128] = [
+    'А', 'Б', 'В', 'Г', 'Д', 'Е', 'Ж', 'З', 'И', 'Й', 'К', 'Л', 'М', 'Н', 'О', 'П',
+    'Р', 'С', 'Т', 'У', 'Ф', 'Х', 'Ц', 'Ч', 'Ш', 'Щ', 'Ъ', 'Ы', 'Ь', 'Э', 'Ю', 'Я',
+    'а', 'б', 'в', 'г', 'д', 'е', 'ж', 'з', 'и', 'й', 'к', 'л', 'м', 'н', 'о', 'п',
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0',
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0',
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0',
+    'р', 'с', 'т', 'у', 'ф', 'х', 'ц', 'ч', 'ш', 'щ', 'ъ', 'ы', 'ь', 'э', 'ю', 'я',
+    'Ё', 'ё', 'Є', 'є', 'Ї', 'ї', 'Ў', 'ў', '°', '∙', '·', '√', '№', '¤', '■', '\u{A0}',
+];
+
+#[rustfmt::skip]
+const KOI8R: [char; 128] = [
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0',
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\u{A0}', '\0', '°', '²', '·', '÷',
+    '\0', '\0', '\0', 'ё', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0',
+    '\0', '\0', '\0', 'Ё', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '©',
+    'ю', 'а', 'б', 'ц', 'д', 'е', 'ф', 'г', 'х', 'и', 'й', 'к', 'л', 'м', 'н', 'о',
+    'п', 'я', 'р', 'с', 'т', 'у', 'ж', 'в', 'ь', 'ы', 'з', 'ш', 'э', 'щ', 'ч', 'ъ',
+    'Ю', 'А', 'Б', 'Ц', 'Д', 'Е', 'Ф', 'Г', 'Х', 'И', 'Й', 'К', 'Л', 'М', 'Н', 'О',
+    'П', 'Я', 'Р', 'С', 'Т', 'У', 'Ж', 'В', 'Ь', 'Ы', 'З', 'Ш', 'Э', 'Щ', 'Ч', 'Ъ',
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUSSIAN: &str = "Съешь же ещё этих мягких французских булок, да выпей чаю! \
+                           ЁЖ, 1234567890";
+
+    #[test]
+    fn russian_text_round_trips() {
+        for (encoding, bytes) in [
+            (Encoding::Cp1251, [0xC0, 0xA8, 0xE6]),
+            (Encoding::Cp866, [0x80, 0xF0, 0xA6]),
+            (Encoding::Koi8R, [0xE1, 0xB3, 0xD6]),
+        ] {
+            assert_eq!(encoding.encode("АЁж"), bytes, "{encoding:?}");
+            let encoded = encoding.encode(RUSSIAN);
+            assert_eq!(encoded.len(), RUSSIAN.chars().count(), "{encoding:?}");
+            assert_eq!(encoding.decode(&encoded), RUSSIAN, "{encoding:?}");
+        }
+        assert_eq!(
+            Encoding::Utf8.decode(&Encoding::Utf8.encode(RUSSIAN)),
+            RUSSIAN
+        );
+    }
+
+    #[test]
+    fn missing_characters_are_replaced() {
+        assert_eq!(Encoding::Cp866.encode("€1"), b"?1");
+        assert_eq!(Encoding::Koi8R.encode("№"), b"?");
+        assert_eq!(Encoding::Cp1251.decode(&[0x98, b'a']), "\u{FFFD}a");
+        assert_eq!(Encoding::Cp866.decode(&[0xB0]), "\u{FFFD}");
+    }
+
+    #[test]
+    fn names_are_read_in_any_case() {
+        assert_eq!("utf-8".parse(), Ok(Encoding::Utf8));
+        assert_eq!("Windows-1251".parse(), Ok(Encoding::Cp1251));
+        assert_eq!("dos".parse(), Ok(Encoding::Cp866));
+        assert_eq!("кои8-р".parse(), Ok(Encoding::Koi8R));
+        assert_eq!("latin1".parse::<Encoding>(), Err(()));
+    }
+}
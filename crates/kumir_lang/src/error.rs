@@ -0,0 +1,107 @@
+use std::fmt;
+
+use crate::{
+    debugger::CallFrame,
+    lexer::Span,
+    messages::{self, Error, Locale},
+    parser::MAIN,
+};
+
+/// Error that stopped a running program, with the algorithm calls that led
+/// to it. Displayed the way Kumir reports it, [`RuntimeError::text`] gives
+/// it in another language:
+///
+/// ```text
+/// Ошибка выполнения в строке 3: [R009] Деление на ноль
+///     в алг делить(5, 0), вызван в строке 7
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuntimeError {
+    pub message: Error,
+    /// Statement that failed
+    pub span: Span,
+    /// Algorithm calls, the innermost one is the last
+    pub call_stack: Vec<CallFrame>,
+}
+
+impl RuntimeError {
+    /// Error without a known position
+    pub fn new(message: impl Into<Error>) -> Self {
+        Self {
+            message: message.into(),
+            span: Default::default(),
+            call_stack: vec![],
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        self.span.start.line
+    }
+
+    /// Stable code of the error, see [`crate::messages`]
+    pub fn code(&self) -> Option<&str> {
+        self.message.code()
+    }
+
+    /// Error with its backtrace in `locale`
+    pub fn text(&self, locale: Locale) -> String {
+        render(Some(self.line()), &self.message, &self.call_stack, locale)
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text(Locale::Ru))
+    }
+}
+
+/// Error at `line` with one line per call after it, the innermost call
+/// first. The algorithm without a name is the program, not a call
+pub fn render(
+    line: Option<usize>,
+    message: &Error,
+    call_stack: &[CallFrame],
+    locale: Locale,
+) -> String {
+    let mut text = format!(
+        "{}: {}",
+        messages::runtime_error_at(line, locale),
+        message.text(locale)
+    );
+    for frame in call_stack.iter().rev().filter(|frame| frame.name != MAIN) {
+        text += "\n    ";
+        text += &messages::called_at(frame, frame.call_site.start.line, locale);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::interpreter;
+
+    #[test]
+    fn backtrace_of_nested_calls() {
+        let source = "алг\nнач\nвещ в\nвывод внешний(10, в)\nкон\nалг вещ внешний(арг цел а, рез вещ р)\nнач\nр := 1\nзнач := делить(а, 0)\nкон\nалг вещ делить(арг цел а, арг цел б)\nнач\nзнач := а / б\nкон";
+        let (mut interpreter, _) = interpreter(source, &[]);
+        let error = interpreter.run().unwrap_err();
+        let call_sites: Vec<_> = error
+            .call_stack
+            .iter()
+            .map(|frame| format!("{} {}", frame.name, frame.call_site))
+            .collect();
+        assert_eq!(call_sites, ["main 1:1", "внешний 4:1", "делить 9:1"]);
+        assert_eq!(
+            render(
+                Some(error.line()),
+                &error.message,
+                &error.call_stack,
+                Locale::Ru
+            ),
+            "Ошибка выполнения в строке 13: [R009] Деление на ноль\n    \
+             в алг делить(10, 0), вызван в строке 9\n    \
+             в алг внешний(10, не определено), вызван в строке 4"
+        );
+        assert_eq!(error.to_string(), error.text(Locale::Ru));
+    }
+}
@@ -0,0 +1,1091 @@
+//! Flowcharts (блок-схемы) after GOST 19.701: one for the main program and
+//! one per `алг`, with terminators, process blocks, data blocks for `ввод`
+//! and `вывод`, decisions for `если` and the conditions of `нц` and
+//! preparation hexagons for `нц для` and `нц N раз`. Made by [`flowcharts`],
+//! written as SVG by [`Flowchart::to_svg`] and as Graphviz DOT by
+//! [`Flowchart::to_dot`]. [`Flowchart::layout`] places the blocks for drawing
+//! them elsewhere, the IDE highlights [`Flowchart::block_at`] while stepping.
+
+use std::fmt::Write;
+
+use crate::{
+    ast::{AstNode, Statement, Stmt},
+    lexer::Span,
+};
+
+const BLOCK_HEIGHT: f32 = 40.0;
+const ROW_GAP: f32 = 30.0;
+const COLUMN_GAP: f32 = 50.0;
+const MARGIN: f32 = 20.0;
+/// Between the lines of loops going back up
+const LANE: f32 = 10.0;
+const CHAR_WIDTH: f32 = 7.0;
+const MIN_WIDTH: f32 = 120.0;
+const MAX_WIDTH: f32 = 280.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Shape {
+    /// Start and end
+    Terminator,
+    Process,
+    /// `ввод` and `вывод`
+    Data,
+    Decision,
+    /// Call of an algorithm of the program
+    PredefinedProcess,
+    /// Hexagon of `нц для` and `нц N раз`
+    Preparation,
+}
+
+impl Shape {
+    /// Corners of the shape in `bounds`, clockwise from the top left.
+    /// Terminators are drawn with round ends over their rectangle
+    pub fn outline(&self, bounds: &Bounds) -> Vec<(f32, f32)> {
+        let Bounds {
+            x,
+            y,
+            width,
+            height,
+        } = *bounds;
+        let (right, bottom) = (x + width, y + height);
+        let (center_x, center_y) = bounds.center();
+        match self {
+            Shape::Data => {
+                let skew = height / 4.0;
+                vec![
+                    (x + skew, y),
+                    (right, y),
+                    (right - skew, bottom),
+                    (x, bottom),
+                ]
+            }
+            Shape::Decision => vec![
+                (center_x, y),
+                (right, center_y),
+                (center_x, bottom),
+                (x, center_y),
+            ],
+            Shape::Preparation => {
+                let corner = height / 2.0;
+                vec![
+                    (x + corner, y),
+                    (right - corner, y),
+                    (right, center_y),
+                    (right - corner, bottom),
+                    (x + corner, bottom),
+                    (x, center_y),
+                ]
+            }
+            Shape::Terminator | Shape::Process | Shape::PredefinedProcess => {
+                vec![(x, y), (right, y), (right, bottom), (x, bottom)]
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+    pub shape: Shape,
+    pub text: String,
+    /// Statement of the block or the condition of `кц при`, `None` for
+    /// terminators
+    pub span: Option<Span>,
+    pub column: usize,
+    pub row: usize,
+}
+
+/// Side of the block an edge leaves from
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Side {
+    Bottom,
+    Right,
+    Left,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    /// `да` or `нет` from a decision
+    pub label: Option<String>,
+    pub side: Side,
+    /// For edges going back up to a loop, the loops inside that one, so
+    /// outer loops go around inner ones
+    pub lane: usize,
+}
+
+/// Flowchart of the main program or of one algorithm
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Flowchart {
+    /// Name of the algorithm, `None` for the main program
+    pub name: Option<String>,
+    pub blocks: Vec<Block>,
+    pub edges: Vec<Edge>,
+}
+
+/// Rectangle with the top left corner at `x`, `y`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Bounds {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Bounds {
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlacedBlock {
+    pub bounds: Bounds,
+    /// Text of the block, shortened to fit
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlacedEdge {
+    /// Line from the block to the one it goes to, the arrow is at the end
+    pub points: Vec<(f32, f32)>,
+    pub label: Option<String>,
+    /// Start of the label, next to the first point
+    pub label_at: (f32, f32),
+}
+
+/// Where [`Flowchart::layout`] puts everything
+#[derive(Debug, PartialEq, Clone)]
+pub struct Layout {
+    pub width: f32,
+    pub height: f32,
+    /// In the order of [`Flowchart::blocks`]
+    pub blocks: Vec<PlacedBlock>,
+    /// In the order of [`Flowchart::edges`]
+    pub edges: Vec<PlacedEdge>,
+}
+
+/// Flowcharts of the main program, unless it only has algorithms, and of
+/// every algorithm of `program` loaded from `source`
+pub fn flowcharts(program: &AstNode, source: &str) -> Vec<Flowchart> {
+    let body = statements_of(program);
+    let algorithms: Vec<_> = body
+        .iter()
+        .filter_map(|statement| match &statement.stmt {
+            Stmt::Alg(function) => Some((statement, function)),
+            _ => None,
+        })
+        .collect();
+    let names: Vec<_> = algorithms
+        .iter()
+        .map(|(_, function)| function.name.as_str())
+        .collect();
+    let builder = |name| Builder {
+        source,
+        algorithms: &names,
+        chart: Flowchart {
+            name,
+            ..Default::default()
+        },
+        breaks: vec![vec![]],
+    };
+    let main: Vec<_> = body
+        .iter()
+        .filter(|statement| !matches!(statement.stmt, Stmt::Alg(_) | Stmt::ImportNamespace(_)))
+        .collect();
+    let mut charts = vec![];
+    if !main.is_empty() {
+        charts.push(builder(None).build("начало".to_string(), main));
+    }
+    for (statement, function) in &algorithms {
+        let header = first_line(source, statement.span);
+        let chart = builder(Some(function.name.clone()));
+        charts.push(chart.build(header, statements_of(&function.body)));
+    }
+    charts
+}
+
+impl Flowchart {
+    /// Block of the statement or the `кц при` condition at `span`
+    pub fn block_at(&self, span: Span) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.span == Some(span))
+    }
+
+    /// Blocks in a grid by [`Block::column`] and [`Block::row`], all as
+    /// wide as the longest text needs, and edges going around them
+    pub fn layout(&self) -> Layout {
+        let width = self
+            .blocks
+            .iter()
+            .map(|block| text_width(block.shape, &block.text))
+            .fold(MIN_WIDTH, f32::max)
+            .min(MAX_WIDTH);
+        let columns = self.blocks.iter().map(|block| block.column + 1).max();
+        let rows = self.blocks.iter().map(|block| block.row + 1).max();
+        let lanes = self.edges.iter().map(|edge| edge.lane + 1).max();
+        let left = MARGIN + LANE * (lanes.unwrap_or_default() + 1) as f32;
+        let bounds = |block: &Block| Bounds {
+            x: left + block.column as f32 * (width + COLUMN_GAP),
+            y: MARGIN + block.row as f32 * (BLOCK_HEIGHT + ROW_GAP),
+            width,
+            height: BLOCK_HEIGHT,
+        };
+        let blocks: Vec<_> = self
+            .blocks
+            .iter()
+            .map(|block| PlacedBlock {
+                bounds: bounds(block),
+                text: fit(block.shape, &block.text, width),
+            })
+            .collect();
+        let edges = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let (from, to) = (&self.blocks[edge.from], &self.blocks[edge.to]);
+                //Right of the blocks the edge passes
+                let passed = self
+                    .blocks
+                    .iter()
+                    .filter(|block| {
+                        block.row > from.row && block.row < to.row && block.column >= from.column
+                    })
+                    .map(|block| bounds(block).x + width);
+                let right = passed.fold(bounds(from).x + width, f32::max) + COLUMN_GAP / 2.0;
+                route(edge, bounds(from), bounds(to), from, to, right)
+            })
+            .collect();
+        Layout {
+            width: left + columns.unwrap_or_default() as f32 * (width + COLUMN_GAP) + MARGIN,
+            height: 2.0 * MARGIN + rows.unwrap_or_default() as f32 * (BLOCK_HEIGHT + ROW_GAP)
+                - ROW_GAP,
+            blocks,
+            edges,
+        }
+    }
+
+    pub fn to_svg(&self) -> String {
+        let layout = self.layout();
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+             viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\" font-size=\"12\">\n",
+            layout.width, layout.height
+        );
+        svg += "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+                markerWidth=\"8\" markerHeight=\"8\" orient=\"auto\">\
+                <path d=\"M 0 0 L 10 5 L 0 10 z\"/></marker></defs>\n";
+        for edge in &layout.edges {
+            let points: Vec<_> = edge
+                .points
+                .iter()
+                .map(|(x, y)| format!("{x},{y}"))
+                .collect();
+            let _ = writeln!(
+                svg,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"black\" \
+                 marker-end=\"url(#arrow)\"/>",
+                points.join(" ")
+            );
+            if let Some(label) = &edge.label {
+                let (x, y) = edge.label_at;
+                let _ = writeln!(svg, "<text x=\"{x}\" y=\"{y}\">{}</text>", xml_text(label));
+            }
+        }
+        for (block, placed) in self.blocks.iter().zip(&layout.blocks) {
+            let Bounds {
+                x,
+                y,
+                width,
+                height,
+            } = placed.bounds;
+            let style = "fill=\"white\" stroke=\"black\"";
+            match block.shape {
+                Shape::Terminator => {
+                    let radius = height / 2.0;
+                    let _ = writeln!(
+                        svg,
+                        "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" \
+                         rx=\"{radius}\" {style}/>"
+                    );
+                }
+                Shape::Process | Shape::PredefinedProcess => {
+                    let _ = writeln!(
+                        svg,
+                        "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" {style}/>"
+                    );
+                }
+                shape => {
+                    let points: Vec<_> = shape
+                        .outline(&placed.bounds)
+                        .iter()
+                        .map(|(x, y)| format!("{x},{y}"))
+                        .collect();
+                    let _ = writeln!(svg, "<polygon points=\"{}\" {style}/>", points.join(" "));
+                }
+            }
+            if block.shape == Shape::PredefinedProcess {
+                for line_x in [x + LANE, x + width - LANE] {
+                    let _ = writeln!(
+                        svg,
+                        "<line x1=\"{line_x}\" y1=\"{y}\" x2=\"{line_x}\" y2=\"{}\" \
+                         stroke=\"black\"/>",
+                        y + height
+                    );
+                }
+            }
+            let (center_x, center_y) = placed.bounds.center();
+            let _ = writeln!(
+                svg,
+                "<text x=\"{center_x}\" y=\"{center_y}\" text-anchor=\"middle\" \
+                 dominant-baseline=\"middle\">{}</text>",
+                xml_text(&placed.text)
+            );
+        }
+        svg += "</svg>\n";
+        svg
+    }
+
+    /// Graph for Graphviz, which places the blocks itself
+    pub fn to_dot(&self) -> String {
+        let name = self.name.as_deref().unwrap_or("main");
+        let mut dot = format!("digraph {} {{\n", dot_string(name));
+        dot += "    node [fontname=\"sans-serif\"];\n";
+        for (i, block) in self.blocks.iter().enumerate() {
+            let shape = match block.shape {
+                Shape::Terminator => "box, style=rounded",
+                Shape::Process => "box",
+                Shape::Data => "parallelogram",
+                Shape::Decision => "diamond",
+                Shape::PredefinedProcess => "box, peripheries=2",
+                Shape::Preparation => "hexagon",
+            };
+            let _ = writeln!(
+                dot,
+                "    n{i} [shape={shape}, label={}];",
+                dot_string(&block.text)
+            );
+        }
+        for edge in &self.edges {
+            let _ = write!(dot, "    n{} -> n{}", edge.from, edge.to);
+            if let Some(label) = &edge.label {
+                let _ = write!(dot, " [label={}]", dot_string(label));
+            }
+            dot += ";\n";
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+/// Edge still to be connected to the next block
+#[derive(Debug, Clone)]
+struct Port {
+    from: usize,
+    label: Option<&'static str>,
+    side: Side,
+}
+
+impl Port {
+    fn new(from: usize, label: Option<&'static str>, side: Side) -> Self {
+        Port { from, label, side }
+    }
+}
+
+/// Cell of the grid
+#[derive(Debug, Clone, Copy)]
+struct Cell {
+    column: usize,
+    row: usize,
+}
+
+/// Grid cells a statement took
+#[derive(Debug, Clone, Copy)]
+struct Extent {
+    /// First free row below it
+    row: usize,
+    /// First free column right of it
+    column: usize,
+    /// Loops inside it, nested ones counted once
+    loops: usize,
+}
+
+impl Extent {
+    /// Nothing taken at `at`
+    fn empty(at: Cell) -> Self {
+        Extent {
+            row: at.row,
+            column: at.column + 1,
+            loops: 0,
+        }
+    }
+}
+
+/// Makes the flowchart of one algorithm
+struct Builder<'a> {
+    source: &'a str,
+    /// Names of the algorithms of the program
+    algorithms: &'a [&'a str],
+    chart: Flowchart,
+    /// Ports left by `выход`, one list per loop, the first one for the
+    /// algorithm
+    breaks: Vec<Vec<Port>>,
+}
+
+impl<'a> Builder<'a> {
+    fn build(mut self, start: String, body: impl IntoIterator<Item = &'a Statement>) -> Flowchart {
+        let start = self.block(Shape::Terminator, start, None, Cell { column: 0, row: 0 });
+        let start = vec![Port::new(start, None, Side::Bottom)];
+        let (mut ports, extent) = self.body(body, start, Cell { column: 0, row: 1 });
+        let at = Cell {
+            column: 0,
+            row: extent.row,
+        };
+        let end = self.block(Shape::Terminator, "конец".to_string(), None, at);
+        ports.extend(self.breaks.pop().unwrap_or_default());
+        self.connect(ports, end, 0);
+        self.chart
+    }
+
+    fn body(
+        &mut self,
+        body: impl IntoIterator<Item = &'a Statement>,
+        mut ports: Vec<Port>,
+        mut at: Cell,
+    ) -> (Vec<Port>, Extent) {
+        let mut extent = Extent::empty(at);
+        for statement in body {
+            let (next, taken) = self.statement(statement, ports, at);
+            ports = next;
+            at.row = taken.row;
+            extent = Extent {
+                row: taken.row,
+                column: extent.column.max(taken.column),
+                loops: extent.loops.max(taken.loops),
+            };
+        }
+        (ports, extent)
+    }
+
+    fn statement(
+        &mut self,
+        statement: &'a Statement,
+        ports: Vec<Port>,
+        at: Cell,
+    ) -> (Vec<Port>, Extent) {
+        let span = statement.span;
+        let lines = span.lines(self.source);
+        let first = lines.first().cloned().unwrap_or_default();
+        let shape = match &statement.stmt {
+            Stmt::Alg(_) | Stmt::ImportNamespace(_) => return (ports, Extent::empty(at)),
+            Stmt::VarDecl(var_decl) if var_decl.value.is_none() => {
+                return (ports, Extent::empty(at));
+            }
+            Stmt::VarsDecl(var_decls) if var_decls.iter().all(|var| var.value.is_none()) => {
+                return (ports, Extent::empty(at));
+            }
+            Stmt::Break => {
+                if let Some(breaks) = self.breaks.last_mut() {
+                    breaks.extend(ports);
+                }
+                return (vec![], Extent::empty(at));
+            }
+            Stmt::Condition(condition) => {
+                let text = self.text(condition.condition_span);
+                let decision = self.block(Shape::Decision, text, Some(span), at);
+                self.connect(ports, decision, 0);
+                let branch = Cell {
+                    row: at.row + 1,
+                    ..at
+                };
+                let yes = Port::new(decision, Some("да"), Side::Bottom);
+                let (mut ports, left) =
+                    self.body(statements_of(&condition.left), vec![yes], branch);
+                let no = Port::new(decision, Some("нет"), Side::Right);
+                let (right_ports, right) = match &condition.right {
+                    Some(right) => {
+                        let branch = Cell {
+                            column: left.column,
+                            ..branch
+                        };
+                        self.body(statements_of(right), vec![no], branch)
+                    }
+                    None => (vec![no], left),
+                };
+                ports.extend(right_ports);
+                return (
+                    ports,
+                    Extent {
+                        row: left.row.max(right.row),
+                        column: left.column.max(right.column),
+                        loops: left.loops.max(right.loops),
+                    },
+                );
+            }
+            Stmt::Loop(loop_stmt) => {
+                let words: Vec<_> = first.split_whitespace().take(2).collect();
+                let text = self.text(loop_stmt.condition_span);
+                let condition = match (&loop_stmt.condition, words[..] == ["нц", "пока"]) {
+                    (Some(_), true) => Condition::Before(text),
+                    (Some(_), false) => Condition::After(text, loop_stmt.condition_span),
+                    (None, _) => Condition::None,
+                };
+                return self.conditional_loop(span, condition, &loop_stmt.body, ports, at);
+            }
+            Stmt::ForLoop(for_loop) => {
+                let text = strip(&first, &["нц"], "");
+                return self.counted_loop(span, text, None, &for_loop.body, ports, at);
+            }
+            Stmt::RepeatLoop(repeat_loop) => {
+                let text = strip(&first, &["нц"], "");
+                let until = repeat_loop.condition.as_ref().map(|_| {
+                    let span = repeat_loop.condition_span;
+                    (self.text(span), span)
+                });
+                return self.counted_loop(span, text, until, &repeat_loop.body, ports, at);
+            }
+            Stmt::Input { .. } | Stmt::Output { .. } => Shape::Data,
+            Stmt::FunctionCall(call) if self.algorithms.contains(&call.name.as_str()) => {
+                Shape::PredefinedProcess
+            }
+            _ => Shape::Process,
+        };
+        let block = self.block(shape, first, Some(span), at);
+        self.connect(ports, block, 0);
+        (
+            vec![Port::new(block, None, Side::Bottom)],
+            Extent {
+                row: at.row + 1,
+                ..Extent::empty(at)
+            },
+        )
+    }
+
+    /// `нц пока`, `нц ... кц при` and `нц ... кц`
+    fn conditional_loop(
+        &mut self,
+        span: Span,
+        condition: Condition,
+        body: &'a AstNode,
+        ports: Vec<Port>,
+        at: Cell,
+    ) -> (Vec<Port>, Extent) {
+        self.breaks.push(vec![]);
+        let (mut exits, extent) = match condition {
+            Condition::Before(text) => {
+                let decision = self.block(Shape::Decision, text, Some(span), at);
+                self.connect(ports, decision, 0);
+                let yes = Port::new(decision, Some("да"), Side::Bottom);
+                let body_at = Cell {
+                    row: at.row + 1,
+                    ..at
+                };
+                let (ports, extent) = self.body(statements_of(body), vec![yes], body_at);
+                self.connect(ports, decision, extent.loops);
+                (vec![Port::new(decision, Some("нет"), Side::Right)], extent)
+            }
+            Condition::After(text, condition_span) => {
+                let first = self.chart.blocks.len();
+                let (ports, extent) = self.body(statements_of(body), ports, at);
+                let check = Cell {
+                    row: extent.row,
+                    ..at
+                };
+                let decision = self.block(Shape::Decision, text, Some(condition_span), check);
+                self.connect(ports, decision, 0);
+                let no = Port::new(decision, Some("нет"), Side::Left);
+                self.connect(vec![no], first, extent.loops);
+                let extent = Extent {
+                    row: extent.row + 1,
+                    ..extent
+                };
+                (vec![Port::new(decision, Some("да"), Side::Bottom)], extent)
+            }
+            Condition::None => {
+                let first = self.chart.blocks.len();
+                let (ports, extent) = self.body(statements_of(body), ports, at);
+                if first < self.chart.blocks.len() {
+                    self.connect(ports, first, extent.loops);
+                }
+                (vec![], extent)
+            }
+        };
+        exits.extend(self.breaks.pop().unwrap_or_default());
+        (
+            exits,
+            Extent {
+                loops: extent.loops + 1,
+                ..extent
+            },
+        )
+    }
+
+    /// `нц для` and `нц N раз`, left from the hexagon when the count is
+    /// over or from the condition of `кц при` when it holds
+    fn counted_loop(
+        &mut self,
+        span: Span,
+        text: String,
+        until: Option<(String, Span)>,
+        body: &'a AstNode,
+        ports: Vec<Port>,
+        at: Cell,
+    ) -> (Vec<Port>, Extent) {
+        let hexagon = self.block(Shape::Preparation, text, Some(span), at);
+        self.connect(ports, hexagon, 0);
+        self.breaks.push(vec![]);
+        let body_at = Cell {
+            row: at.row + 1,
+            ..at
+        };
+        let next = vec![Port::new(hexagon, None, Side::Bottom)];
+        let (mut ports, mut extent) = self.body(statements_of(body), next, body_at);
+        let mut exits = vec![Port::new(hexagon, None, Side::Right)];
+        if let Some((text, condition_span)) = until {
+            let check = Cell {
+                row: extent.row,
+                ..at
+            };
+            let decision = self.block(Shape::Decision, text, Some(condition_span), check);
+            self.connect(ports, decision, 0);
+            ports = vec![Port::new(decision, Some("нет"), Side::Left)];
+            exits.push(Port::new(decision, Some("да"), Side::Bottom));
+            extent.row += 1;
+        }
+        self.connect(ports, hexagon, extent.loops);
+        exits.extend(self.breaks.pop().unwrap_or_default());
+        (
+            exits,
+            Extent {
+                loops: extent.loops + 1,
+                ..extent
+            },
+        )
+    }
+
+    /// Source of `span` on one line
+    fn text(&self, span: Span) -> String {
+        span.lines(self.source).join(" ")
+    }
+
+    fn block(&mut self, shape: Shape, text: String, span: Option<Span>, at: Cell) -> usize {
+        self.chart.blocks.push(Block {
+            shape,
+            text,
+            span,
+            column: at.column,
+            row: at.row,
+        });
+        self.chart.blocks.len() - 1
+    }
+
+    fn connect(&mut self, ports: Vec<Port>, to: usize, lane: usize) {
+        for port in ports {
+            self.chart.edges.push(Edge {
+                from: port.from,
+                to,
+                label: port.label.map(str::to_string),
+                side: port.side,
+                lane,
+            });
+        }
+    }
+}
+
+/// Where a `нц` checks its condition, with the text of it. The check of
+/// `кц при` is a block of its own, with the span of the condition
+enum Condition {
+    Before(String),
+    After(String, Span),
+    None,
+}
+
+fn statements_of(node: &AstNode) -> &[Statement] {
+    match node {
+        AstNode::Program(body) => body,
+        AstNode::Stmt(_) => &[],
+    }
+}
+
+fn first_line(source: &str, span: Span) -> String {
+    span.lines(source).into_iter().next().unwrap_or_default()
+}
+
+/// `text` without the first of `prefixes` it starts with and `suffix`
+fn strip(text: &str, prefixes: &[&str], suffix: &str) -> String {
+    let text = prefixes
+        .iter()
+        .find_map(|prefix| text.strip_prefix(prefix))
+        .unwrap_or(text)
+        .trim();
+    text.strip_suffix(suffix).unwrap_or(text).trim().to_string()
+}
+
+/// Width a block needs for `text`, a diamond has room only in the middle
+fn text_width(shape: Shape, text: &str) -> f32 {
+    let width = text.chars().count() as f32 * CHAR_WIDTH;
+    match shape {
+        Shape::Decision => width * 2.0 + 2.0 * LANE,
+        _ => width + 4.0 * LANE,
+    }
+}
+
+fn fit(shape: Shape, text: &str, width: f32) -> String {
+    if text_width(shape, text) <= width {
+        return text.to_string();
+    }
+    let mut fitted: String = text.chars().collect();
+    while !fitted.is_empty() && text_width(shape, &format!("{fitted}…")) > width {
+        fitted.pop();
+    }
+    format!("{}…", fitted.trim_end())
+}
+
+fn route(edge: &Edge, from: Bounds, to: Bounds, a: &Block, b: &Block, right: f32) -> PlacedEdge {
+    let (from_x, from_y) = from.center();
+    let (to_x, to_y) = to.center();
+    let (bottom, top) = (from.y + from.height, to.y);
+    //Just above the target, between the rows
+    let above = top - ROW_GAP / 2.0;
+    let left = to.x - LANE * (edge.lane + 1) as f32;
+    let points = match edge.side {
+        Side::Left => vec![(from.x, from_y), (left, from_y), (left, to_y), (to.x, to_y)],
+        _ if b.row <= a.row => {
+            let below = bottom + ROW_GAP / 3.0;
+            vec![
+                (from_x, bottom),
+                (from_x, below),
+                (left, below),
+                (left, to_y),
+                (to.x, to_y),
+            ]
+        }
+        Side::Right if b.column > a.column => {
+            vec![(from.x + from.width, from_y), (to_x, from_y), (to_x, top)]
+        }
+        Side::Right => vec![
+            (from.x + from.width, from_y),
+            (right, from_y),
+            (right, above),
+            (to_x, above),
+            (to_x, top),
+        ],
+        Side::Bottom if a.column == b.column => vec![(from_x, bottom), (to_x, top)],
+        Side::Bottom => vec![
+            (from_x, bottom),
+            (from_x, above),
+            (to_x, above),
+            (to_x, top),
+        ],
+    };
+    let (x, y) = points[0];
+    let label_at = match edge.side {
+        Side::Bottom => (x + 4.0, y + 12.0),
+        Side::Right => (x + 4.0, y - 4.0),
+        Side::Left => (x - 4.0 * LANE, y - 4.0),
+    };
+    PlacedEdge {
+        points,
+        label: edge.label.clone(),
+        label_at,
+    }
+}
+
+fn xml_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::interpreter;
+
+    fn chart(source: &str) -> Flowchart {
+        interpreter(source, &[]).0.flowcharts().remove(0)
+    }
+
+    /// `shape text column,row` of every block
+    fn blocks(chart: &Flowchart) -> Vec<String> {
+        chart
+            .blocks
+            .iter()
+            .map(|block| {
+                let Block {
+                    shape,
+                    text,
+                    column,
+                    row,
+                    ..
+                } = block;
+                format!("{shape:?} {text} {column},{row}")
+            })
+            .collect()
+    }
+
+    /// `from -> to label side lane` of every edge
+    fn edges(chart: &Flowchart) -> Vec<String> {
+        chart
+            .edges
+            .iter()
+            .map(|edge| {
+                let label = edge.label.as_deref().unwrap_or("-");
+                let Edge {
+                    from,
+                    to,
+                    side,
+                    lane,
+                    ..
+                } = edge;
+                format!("{from} -> {to} {label} {side:?} {lane}")
+            })
+            .collect()
+    }
+
+    /// `выход` is not a keyword of the lexer, so it is parsed as a call
+    fn with_breaks(node: &mut AstNode) {
+        let AstNode::Program(statements) = node else {
+            return;
+        };
+        for statement in statements {
+            match &mut statement.stmt {
+                Stmt::FunctionCall(call) if call.name == "выход" => {
+                    statement.stmt = Stmt::Break
+                }
+                Stmt::Loop(loop_stmt) => with_breaks(&mut loop_stmt.body),
+                Stmt::Condition(condition) => with_breaks(&mut condition.left),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn one_line_condition_shows_only_its_condition() {
+        let chart = chart("цел а := 1\nесли а > 0 то а := 1 все\n");
+        assert_eq!(
+            blocks(&chart),
+            [
+                "Terminator начало 0,0",
+                "Process цел а := 1 0,1",
+                "Decision а > 0 0,2",
+                "Process а := 1 0,3",
+                "Terminator конец 0,4",
+            ]
+        );
+        assert_eq!(
+            edges(&chart),
+            [
+                "0 -> 1 - Bottom 0",
+                "1 -> 2 - Bottom 0",
+                "2 -> 3 да Bottom 0",
+                "3 -> 4 - Bottom 0",
+                "2 -> 4 нет Right 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn else_branch_is_right_of_then() {
+        let chart = chart("цел а := 1\nесли а > 0\nто\nа := 2\nиначе\nа := 3\nвсе\nвывод а\n");
+        assert_eq!(
+            blocks(&chart),
+            [
+                "Terminator начало 0,0",
+                "Process цел а := 1 0,1",
+                "Decision а > 0 0,2",
+                "Process а := 2 0,3",
+                "Process а := 3 1,3",
+                "Data вывод а 0,4",
+                "Terminator конец 0,5",
+            ]
+        );
+        assert_eq!(
+            edges(&chart),
+            [
+                "0 -> 1 - Bottom 0",
+                "1 -> 2 - Bottom 0",
+                "2 -> 3 да Bottom 0",
+                "2 -> 4 нет Right 0",
+                "3 -> 5 - Bottom 0",
+                "4 -> 5 - Bottom 0",
+                "5 -> 6 - Bottom 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn while_loop_checks_before_the_body() {
+        let chart = chart("цел а := 1\nнц пока а < 3\nа := а + 1\nкц\n");
+        assert_eq!(
+            blocks(&chart)[2..],
+            [
+                "Decision а < 3 0,2",
+                "Process а := а + 1 0,3",
+                "Terminator конец 0,4"
+            ]
+        );
+        assert_eq!(
+            edges(&chart)[2..],
+            [
+                "2 -> 3 да Bottom 0",
+                "3 -> 2 - Bottom 0",
+                "2 -> 4 нет Right 0"
+            ]
+        );
+    }
+
+    #[test]
+    fn outer_loops_go_around_inner_ones() {
+        let chart = chart("цел а := 1\nнц пока а < 3\nнц для i от 1 до 3\nа := а + i\nкц\nкц\n");
+        assert_eq!(
+            blocks(&chart)[2..],
+            [
+                "Decision а < 3 0,2",
+                "Preparation для i от 1 до 3 0,3",
+                "Process а := а + i 0,4",
+                "Terminator конец 0,5",
+            ]
+        );
+        assert_eq!(
+            edges(&chart)[2..],
+            [
+                "2 -> 3 да Bottom 0",
+                "3 -> 4 - Bottom 0",
+                "4 -> 3 - Bottom 0",
+                "3 -> 2 - Right 1",
+                "2 -> 5 нет Right 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn loop_until_checks_after_the_body() {
+        let source = "цел а := 1\nнц\nа := а + 1\nкц_при а > 5\n";
+        let chart = chart(source);
+        assert_eq!(
+            blocks(&chart)[2..],
+            [
+                "Process а := а + 1 0,2",
+                "Decision а > 5 0,3",
+                "Terminator конец 0,4"
+            ]
+        );
+        assert_eq!(
+            edges(&chart)[2..],
+            [
+                "2 -> 3 - Bottom 0",
+                "3 -> 2 нет Left 0",
+                "3 -> 4 да Bottom 0"
+            ]
+        );
+        let span = chart.blocks[3].span.unwrap();
+        assert_eq!(span.lines(source), ["а > 5"]);
+    }
+
+    #[test]
+    fn counted_loop_and_its_condition_are_highlighted_apart() {
+        let source = "цел а := 1\nнц 3 раз\nа := а + 1\nкц_при а > 5\n";
+        let interpreter = interpreter(source, &[]).0;
+        let chart = interpreter.flowcharts().remove(0);
+        assert_eq!(
+            blocks(&chart)[2..],
+            [
+                "Preparation 3 раз 0,2",
+                "Process а := а + 1 0,3",
+                "Decision а > 5 0,4",
+                "Terminator конец 0,5",
+            ]
+        );
+        assert_eq!(
+            edges(&chart)[2..],
+            [
+                "2 -> 3 - Bottom 0",
+                "3 -> 4 - Bottom 0",
+                "4 -> 2 нет Left 0",
+                "2 -> 5 - Right 0",
+                "4 -> 5 да Bottom 0",
+            ]
+        );
+        let loop_span = statements_of(&interpreter.ast)[1].span;
+        assert_eq!(chart.block_at(loop_span), Some(2));
+        let condition = chart.blocks[4].span.unwrap();
+        assert_eq!(condition.lines(source), ["а > 5"]);
+        assert_eq!(chart.block_at(condition), Some(4));
+    }
+
+    #[test]
+    fn break_leaves_the_loop() {
+        let source = "цел а := 1\nнц пока а < 3\nесли а > 1 то\nвыход\nвсе\nа := а + 1\nкц\n";
+        let mut program = interpreter(source, &[]).0.ast;
+        with_breaks(&mut program);
+        let chart = flowcharts(&program, source).remove(0);
+        assert_eq!(
+            blocks(&chart)[2..],
+            [
+                "Decision а < 3 0,2",
+                "Decision а > 1 0,3",
+                "Process а := а + 1 0,4",
+                "Terminator конец 0,5",
+            ]
+        );
+        assert_eq!(
+            edges(&chart)[2..],
+            [
+                "2 -> 3 да Bottom 0",
+                "3 -> 4 нет Right 0",
+                "4 -> 2 - Bottom 0",
+                "2 -> 5 нет Right 0",
+                "3 -> 5 да Bottom 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn algorithm_to_dot() {
+        let chart = chart("алг цел квадрат(цел х)\nнач\nзнач := х * х\nкон\n");
+        assert_eq!(
+            chart.to_dot(),
+            "digraph \"квадрат\" {\n    node [fontname=\"sans-serif\"];\n    \
+             n0 [shape=box, style=rounded, label=\"алг цел квадрат(цел х)\"];\n    \
+             n1 [shape=box, label=\"знач := х * х\"];\n    \
+             n2 [shape=box, style=rounded, label=\"конец\"];\n    \
+             n0 -> n1;\n    n1 -> n2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn program_to_svg() {
+        assert_eq!(
+            chart("вывод 1\n").to_svg(),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"230\" height=\"220\" \
+             viewBox=\"0 0 230 220\" font-family=\"sans-serif\" font-size=\"12\">\n\
+             <defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+             markerWidth=\"8\" markerHeight=\"8\" orient=\"auto\">\
+             <path d=\"M 0 0 L 10 5 L 0 10 z\"/></marker></defs>\n\
+             <polyline points=\"100,60 100,90\" fill=\"none\" stroke=\"black\" \
+             marker-end=\"url(#arrow)\"/>\n\
+             <polyline points=\"100,130 100,160\" fill=\"none\" stroke=\"black\" \
+             marker-end=\"url(#arrow)\"/>\n\
+             <rect x=\"40\" y=\"20\" width=\"120\" height=\"40\" rx=\"20\" \
+             fill=\"white\" stroke=\"black\"/>\n\
+             <text x=\"100\" y=\"40\" text-anchor=\"middle\" \
+             dominant-baseline=\"middle\">начало</text>\n\
+             <polygon points=\"50,90 160,90 150,130 40,130\" fill=\"white\" stroke=\"black\"/>\n\
+             <text x=\"100\" y=\"110\" text-anchor=\"middle\" \
+             dominant-baseline=\"middle\">вывод 1</text>\n\
+             <rect x=\"40\" y=\"160\" width=\"120\" height=\"40\" rx=\"20\" \
+             fill=\"white\" stroke=\"black\"/>\n\
+             <text x=\"100\" y=\"180\" text-anchor=\"middle\" \
+             dominant-baseline=\"middle\">конец</text>\n\
+             </svg>\n"
+        );
+        let svg = chart("цел а := 1\nесли а < 3 то а := 2 все\n").to_svg();
+        assert!(svg.contains(">а &lt; 3</text>"), "{svg}");
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{
+    ast::AstNode,
+    lexer::{Condition, Delimiter, Function, Keyword, Lexer, Loop, Operator, Token},
+    messages::{Error, Message},
+    parser::Parser,
+};
+
+const INDENT: &str = "    ";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Block {
+    ///нач ... кон
+    Alg,
+    ///нц ... кц
+    Loop,
+    ///если ... все
+    Condition,
+}
+
+/// Formats Kumir source code: one statement per line, indentation inside
+/// `нач/кон`, `нц/кц` and `если/все`, single spaces around `:=` and
+/// operators and canonical keyword spelling. `|` comments are kept.
+///
+/// Fails if the input doesn't lex or parse, or if the formatted program
+/// would parse into a different AST than the original one.
+pub fn format(input: &str) -> Result<String, Error> {
+    let tokens = tokenize(input)?;
+    let original = parse(tokens.clone())?;
+
+    let mut output = String::new();
+    let mut blocks: Vec<Block> = vec![];
+    let mut blank_lines = 0;
+    for line in tokens.split(|token| *token == Token::NewLine) {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if blank_lines > 0 && !output.is_empty() {
+            output.push('\n');
+        }
+        blank_lines = 0;
+
+        let depth = line_depth(&line[0], &mut blocks);
+        output.push_str(&INDENT.repeat(depth));
+        output.push_str(&format_line(line));
+        output.push('\n');
+
+        for token in &line[1..] {
+            update_blocks(token, &mut blocks);
+        }
+    }
+
+    let formatted = parse(tokenize(&output)?)?;
+    if formatted != original {
+        return Err(Message::FormattingChangesMeaning.into());
+    }
+    Ok(output)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut lexer = Lexer::with_trivia(input);
+    let mut tokens = vec![];
+    loop {
+        match lexer.next_token()? {
+            Token::Eof => break,
+            token => tokens.push(token),
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse(tokens: Vec<Token>) -> Result<AstNode, Error> {
+    Parser::new(tokens).parse().map_err(|(_, err)| err)
+}
+
+/// Indentation depth of a line starting with `token`, block closers and
+/// openers on the first token are applied to `blocks`
+fn line_depth(token: &Token, blocks: &mut Vec<Block>) -> usize {
+    match token {
+        Token::Keyword(Keyword::Function(Function::Stop)) => {
+            close_block(Block::Alg, blocks);
+            blocks.len()
+        }
+        Token::Keyword(Keyword::Loop(Loop::End | Loop::EndIf)) => {
+            close_block(Block::Loop, blocks);
+            blocks.len()
+        }
+        Token::Keyword(Keyword::Condition(Condition::EndCondition)) => {
+            close_block(Block::Condition, blocks);
+            blocks.len()
+        }
+        Token::Keyword(Keyword::Condition(Condition::Then | Condition::Else))
+            if blocks.last() == Some(&Block::Condition) =>
+        {
+            blocks.len() - 1
+        }
+        _ => {
+            let depth = blocks.len();
+            update_blocks(token, blocks);
+            depth
+        }
+    }
+}
+
+fn update_blocks(token: &Token, blocks: &mut Vec<Block>) {
+    match token {
+        Token::Keyword(Keyword::Function(Function::Start)) => blocks.push(Block::Alg),
+        Token::Keyword(Keyword::Loop(Loop::Start)) => blocks.push(Block::Loop),
+        Token::Keyword(Keyword::Condition(Condition::If)) => blocks.push(Block::Condition),
+        Token::Keyword(Keyword::Function(Function::Stop)) => close_block(Block::Alg, blocks),
+        Token::Keyword(Keyword::Loop(Loop::End | Loop::EndIf)) => close_block(Block::Loop, blocks),
+        Token::Keyword(Keyword::Condition(Condition::EndCondition)) => {
+            close_block(Block::Condition, blocks)
+        }
+        _ => {}
+    }
+}
+
+fn close_block(block: Block, blocks: &mut Vec<Block>) {
+    if blocks.last() == Some(&block) {
+        blocks.pop();
+    }
+}
+
+fn format_line(line: &[Token]) -> String {
+    let mut output = String::new();
+    let mut previous: Option<&Token> = None;
+    let mut previous_is_unary = false;
+    for token in line {
+        if let Some(previous) = previous
+            && needs_space(previous, previous_is_unary, token)
+        {
+            output.push(' ');
+        }
+        previous_is_unary = is_unary(previous, token);
+        output.push_str(&format_token(token));
+        previous = Some(token);
+    }
+    output
+}
+
+/// `+` and `-` are unary at the start of an expression
+fn is_unary(previous: Option<&Token>, token: &Token) -> bool {
+    if !token.is_operator(Operator::Plus) && !token.is_operator(Operator::Minus) {
+        return false;
+    }
+    matches!(
+        previous,
+        None | Some(
+            Token::Operator(_)
+                | Token::Keyword(_)
+                | Token::Delimiter(Delimiter::ParenthesisOpen | Delimiter::Comma)
+        )
+    )
+}
+
+fn needs_space(previous: &Token, previous_is_unary: bool, token: &Token) -> bool {
+    match (previous, token) {
+        (_, Token::Delimiter(Delimiter::Comma | Delimiter::ParenthesisClose)) => false,
+        (Token::Delimiter(Delimiter::ParenthesisOpen), _) => false,
+        (Token::Identifier(_), Token::Delimiter(Delimiter::ParenthesisOpen)) => false,
+        (Token::Delimiter(Delimiter::Period), _) | (_, Token::Delimiter(Delimiter::Period)) => {
+            false
+        }
+        (Token::Operator(_), _) if previous_is_unary => false,
+        _ => true,
+    }
+}
+
+fn format_token(token: &Token) -> String {
+    match token {
+        Token::Keyword(keyword) => keyword.to_string(),
+        Token::Identifier(name) => name.clone(),
+        Token::Int(value) => value.to_string(),
+        //Debug keeps the fractional part, so `3.0` doesn't turn into an integer
+        Token::Float(value) => format!("{value:?}"),
+        Token::Operator(operator) => operator.to_string(),
+        Token::Delimiter(delimiter) => delimiter.to_string(),
+        Token::String(value) => format!("\"{value}\""),
+        Token::Char(value) => format!("'{value}'"),
+        Token::Bool(true) => "да".to_string(),
+        Token::Bool(false) => "нет".to_string(),
+        Token::Comment(comment) if comment.is_empty() => "|".to_string(),
+        Token::Comment(comment) if comment.starts_with(' ') => format!("|{comment}"),
+        Token::Comment(comment) => format!("| {comment}"),
+        Token::NewLine | Token::Eof => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSY: &str = "алг тест\nнач\nцел а,б\nа:=1\n\n\nнц 3 раз\nб:=а*(б+2) |счёт\nесли б>10 то\nвывод б,нс\nиначе\nвывод \"мало\",нс\nвсе\nкц\nкон\n";
+
+    #[test]
+    fn formats_blocks_and_spacing() {
+        let expected = "алг тест\nнач\n    цел а, б\n    а := 1\n\n    нц 3 раз\n        б := а * (б + 2) | счёт\n        если б > 10 то\n            вывод б, нс\n        иначе\n            вывод \"мало\", нс\n        все\n    кц\nкон\n";
+        assert_eq!(format(MESSY).unwrap(), expected);
+    }
+
+    #[test]
+    fn keeps_the_meaning() {
+        let formatted = format(MESSY).unwrap();
+        assert_eq!(
+            parse(tokenize(&formatted).unwrap()).unwrap(),
+            parse(tokenize(MESSY).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn is_idempotent() {
+        let once = format(MESSY).unwrap();
+        assert_eq!(format(&once).unwrap(), once);
+    }
+}
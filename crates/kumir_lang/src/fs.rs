@@ -0,0 +1,862 @@
+//! Files a program can reach, see `userdocs/md_output/files.md`.
+//!
+//! Programs never touch the host filesystem directly, they see a
+//! [`VirtualFs`] whose root is `/`. Names are resolved against the working
+//! directory and normalized before they reach it, so `..` can't leave the
+//! root. [`NativeFs`] maps the root to a host directory, [`MemoryFs`] keeps
+//! everything in memory for tests and the web build.
+
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    ast::Literal,
+    encoding::Encoding,
+    io::parse_value,
+    lexer::TypeDefinition,
+    messages::{Error, Message},
+    types::CustomValue,
+};
+
+/// Filesystem behind the `Файлы` actor. Paths are absolute and normalized:
+/// `/`, `/data/in.txt`
+pub trait VirtualFs {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+    /// Replaces or extends the contents, creating the file if there is none
+    fn write(&mut self, path: &str, data: &[u8], append: bool) -> io::Result<()>;
+
+    /// `None` if nothing exists at `path`
+    fn metadata(&self, path: &str) -> Option<Metadata>;
+
+    fn create_dir(&mut self, path: &str) -> io::Result<()>;
+
+    fn remove_file(&mut self, path: &str) -> io::Result<()>;
+
+    /// Removes an empty directory
+    fn remove_dir(&mut self, path: &str) -> io::Result<()>;
+}
+
+impl<T: VirtualFs + ?Sized> VirtualFs for Box<T> {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        (**self).read(path)
+    }
+
+    fn write(&mut self, path: &str, data: &[u8], append: bool) -> io::Result<()> {
+        (**self).write(path, data, append)
+    }
+
+    fn metadata(&self, path: &str) -> Option<Metadata> {
+        (**self).metadata(path)
+    }
+
+    fn create_dir(&mut self, path: &str) -> io::Result<()> {
+        (**self).create_dir(path)
+    }
+
+    fn remove_file(&mut self, path: &str) -> io::Result<()> {
+        (**self).remove_file(path)
+    }
+
+    fn remove_dir(&mut self, path: &str) -> io::Result<()> {
+        (**self).remove_dir(path)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Metadata {
+    pub is_dir: bool,
+    pub readonly: bool,
+}
+
+/// `name` as an absolute path, relative names start at `working_dir`
+pub fn resolve(working_dir: &str, name: &str) -> Result<String, Error> {
+    let mut components: Vec<&str> = vec![];
+    let start = if name.starts_with(['/', '\\']) {
+        ""
+    } else {
+        working_dir
+    };
+    for component in start.split(['/', '\\']).chain(name.split(['/', '\\'])) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components
+                    .pop()
+                    .ok_or(Message::OutsideSandbox(name.to_string()))?;
+            }
+            component => components.push(component),
+        }
+    }
+    Ok(format!("/{}", components.join("/")))
+}
+
+/// Host directory as the root
+pub struct NativeFs {
+    root: PathBuf,
+}
+
+impl NativeFs {
+    /// Fails if `root` is not an existing directory
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                "not a directory",
+            ));
+        }
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Host path of `path`. Symbolic links inside the root may point out of
+    /// it, so the longest existing part of the path is checked after
+    /// following them
+    fn host_path(&self, path: &str) -> io::Result<PathBuf> {
+        let host_path = path
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .fold(self.root.clone(), |host_path, component| {
+                host_path.join(component)
+            });
+        let existing = host_path
+            .ancestors()
+            .find(|ancestor| ancestor.symlink_metadata().is_ok())
+            .unwrap_or(&self.root);
+        if !existing.canonicalize()?.starts_with(&self.root) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "outside of the root",
+            ));
+        }
+        Ok(host_path)
+    }
+}
+
+impl VirtualFs for NativeFs {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(self.host_path(path)?)
+    }
+
+    fn write(&mut self, path: &str, data: &[u8], append: bool) -> io::Result<()> {
+        use std::io::Write;
+        std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(self.host_path(path)?)?
+            .write_all(data)
+    }
+
+    fn metadata(&self, path: &str) -> Option<Metadata> {
+        let metadata = std::fs::metadata(self.host_path(path).ok()?).ok()?;
+        Some(Metadata {
+            is_dir: metadata.is_dir(),
+            readonly: metadata.permissions().readonly(),
+        })
+    }
+
+    fn create_dir(&mut self, path: &str) -> io::Result<()> {
+        std::fs::create_dir(self.host_path(path)?)
+    }
+
+    fn remove_file(&mut self, path: &str) -> io::Result<()> {
+        std::fs::remove_file(self.host_path(path)?)
+    }
+
+    fn remove_dir(&mut self, path: &str) -> io::Result<()> {
+        std::fs::remove_dir(self.host_path(path)?)
+    }
+}
+
+/// Filesystem in memory. Clones share the files, so the host can prepare
+/// them before the run and check them afterwards
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFs {
+    entries: Arc<Mutex<BTreeMap<String, MemoryEntry>>>,
+}
+
+#[derive(Debug, Clone)]
+enum MemoryEntry {
+    File(Vec<u8>),
+    Dir,
+}
+
+impl MemoryFs {
+    /// Adds a file, creating the directories on the way
+    pub fn insert(&self, path: &str, contents: impl Into<Vec<u8>>) {
+        let Ok(path) = resolve("/", path) else {
+            return;
+        };
+        if path == "/" {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let mut dir = String::new();
+        let components = path.split('/').skip(1).collect::<Vec<_>>();
+        for component in &components[..components.len() - 1] {
+            dir = format!("{dir}/{component}");
+            entries.insert(dir.clone(), MemoryEntry::Dir);
+        }
+        entries.insert(path, MemoryEntry::File(contents.into()));
+    }
+
+    /// Contents of the file at `path`
+    pub fn get(&self, path: &str) -> Option<Vec<u8>> {
+        match self.entries.lock().unwrap().get(&resolve("/", path).ok()?) {
+            Some(MemoryEntry::File(contents)) => Some(contents.clone()),
+            _ => None,
+        }
+    }
+
+    fn check_parent(&self, path: &str) -> io::Result<()> {
+        let parent = &path[..path.rfind('/').unwrap_or(0)];
+        match self.metadata(if parent.is_empty() { "/" } else { parent }) {
+            Some(Metadata { is_dir: true, .. }) => Ok(()),
+            _ => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+impl VirtualFs for MemoryFs {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        match self.entries.lock().unwrap().get(path) {
+            Some(MemoryEntry::File(contents)) => Ok(contents.clone()),
+            Some(MemoryEntry::Dir) => Err(io::ErrorKind::IsADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn write(&mut self, path: &str, data: &[u8], append: bool) -> io::Result<()> {
+        self.check_parent(path)?;
+        let mut entries = self.entries.lock().unwrap();
+        match entries
+            .entry(path.to_string())
+            .or_insert(MemoryEntry::File(vec![]))
+        {
+            MemoryEntry::File(contents) => {
+                if !append {
+                    contents.clear();
+                }
+                contents.extend_from_slice(data);
+                Ok(())
+            }
+            MemoryEntry::Dir => Err(io::ErrorKind::IsADirectory.into()),
+        }
+    }
+
+    fn metadata(&self, path: &str) -> Option<Metadata> {
+        let is_dir = match self.entries.lock().unwrap().get(path) {
+            _ if path == "/" => true,
+            Some(MemoryEntry::Dir) => true,
+            Some(MemoryEntry::File(_)) => false,
+            None => return None,
+        };
+        Some(Metadata {
+            is_dir,
+            readonly: false,
+        })
+    }
+
+    fn create_dir(&mut self, path: &str) -> io::Result<()> {
+        self.check_parent(path)?;
+        if self.metadata(path).is_some() {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), MemoryEntry::Dir);
+        Ok(())
+    }
+
+    fn remove_file(&mut self, path: &str) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(path) {
+            Some(MemoryEntry::File(_)) => {
+                entries.remove(path);
+                Ok(())
+            }
+            Some(MemoryEntry::Dir) => Err(io::ErrorKind::IsADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn remove_dir(&mut self, path: &str) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(path) {
+            Some(MemoryEntry::Dir) => {
+                let prefix = format!("{path}/");
+                if entries.keys().any(|key| key.starts_with(&prefix)) {
+                    return Err(io::ErrorKind::DirectoryNotEmpty.into());
+                }
+                entries.remove(path);
+                Ok(())
+            }
+            Some(MemoryEntry::File(_)) => Err(io::ErrorKind::NotADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+/// Value of a `файл` variable
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct FileHandle(pub usize);
+
+impl FileHandle {
+    /// Pseudo-file of `консоль`, always open
+    pub const CONSOLE: FileHandle = FileHandle(0);
+
+    /// Type of the values, registered by `Файлы`
+    pub const TYPE: &'static str = "файл";
+}
+
+impl CustomValue for FileHandle {
+    fn type_name(&self) -> &'static str {
+        Self::TYPE
+    }
+
+    fn equals(&self, other: &dyn CustomValue) -> bool {
+        other.downcast_ref() == Some(self)
+    }
+
+    fn to_output_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for FileHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FileHandle::CONSOLE => write!(f, "консоль"),
+            FileHandle(handle) => write!(f, "файл {handle}"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OpenMode {
+    Read,
+    Write,
+    Append,
+}
+
+/// Files opened by a program, and where `ввод` and `вывод` are redirected
+pub struct Files {
+    fs: Box<dyn VirtualFs>,
+    working_dir: String,
+    encoding: Encoding,
+    open: IndexMap<usize, OpenFile>,
+    last_handle: usize,
+    input: Option<TextReader>,
+    output: Option<(String, Encoding)>,
+}
+
+struct OpenFile {
+    path: String,
+    /// `None` for files open for writing
+    reader: Option<TextReader>,
+    encoding: Encoding,
+}
+
+impl Default for Files {
+    fn default() -> Self {
+        Self::new(MemoryFs::default())
+    }
+}
+
+impl fmt::Debug for Files {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Files")
+            .field("working_dir", &self.working_dir)
+            .field("open", &self.open_files())
+            .finish()
+    }
+}
+
+impl Files {
+    pub fn new(fs: impl VirtualFs + 'static) -> Self {
+        Self {
+            fs: Box::new(fs),
+            working_dir: "/".to_string(),
+            encoding: Default::default(),
+            open: Default::default(),
+            last_handle: 0,
+            input: None,
+            output: None,
+        }
+    }
+
+    pub fn set_fs(&mut self, fs: impl VirtualFs + 'static) {
+        *self = Self::new(fs);
+    }
+
+    pub fn working_dir(&self) -> &str {
+        &self.working_dir
+    }
+
+    pub fn full_path(&self, name: &str) -> Result<String, Error> {
+        resolve(&self.working_dir, name)
+    }
+
+    /// Paths of the files that are still open
+    pub fn open_files(&self) -> Vec<String> {
+        self.open.values().map(|file| file.path.clone()).collect()
+    }
+
+    /// Encoding of the files opened from now on, see [`Encoding::from_str`]
+    pub fn set_encoding(&mut self, name: &str) -> Result<(), Error> {
+        self.encoding = name
+            .parse()
+            .map_err(|_| Message::UnknownEncoding(name.to_string()))?;
+        Ok(())
+    }
+
+    pub fn open(&mut self, name: &str, mode: OpenMode) -> Result<FileHandle, Error> {
+        let path = self.full_path(name)?;
+        if self.open.values().any(|file| file.path == path) {
+            return Err(Message::FileAlreadyOpen(name.to_string()).into());
+        }
+        let reader = match mode {
+            OpenMode::Read => Some(self.reader(name, &path)?),
+            OpenMode::Write | OpenMode::Append => {
+                self.fs
+                    .write(&path, &[], mode == OpenMode::Append)
+                    .map_err(|err| cannot_open(name, err))?;
+                None
+            }
+        };
+        self.last_handle += 1;
+        self.open.insert(
+            self.last_handle,
+            OpenFile {
+                path,
+                reader,
+                encoding: self.encoding,
+            },
+        );
+        Ok(FileHandle(self.last_handle))
+    }
+
+    pub fn close(&mut self, handle: FileHandle) -> Result<(), Error> {
+        if handle == FileHandle::CONSOLE {
+            return Err(Message::CloseConsole.into());
+        }
+        self.open
+            .shift_remove(&handle.0)
+            .map(|_| ())
+            .ok_or(Message::FileNotOpen.into())
+    }
+
+    /// Reads a file open for reading from its start again
+    pub fn rewind(&mut self, handle: FileHandle) -> Result<(), Error> {
+        self.reader_of(handle)?.position = 0;
+        Ok(())
+    }
+
+    pub fn at_end(&mut self, handle: FileHandle) -> Result<bool, Error> {
+        Ok(self.reader_of(handle)?.at_end())
+    }
+
+    /// Whether anything but whitespace is left to read
+    pub fn has_data(&mut self, handle: FileHandle) -> Result<bool, Error> {
+        Ok(self.reader_of(handle)?.has_data())
+    }
+
+    pub fn read(&mut self, handle: FileHandle, type_def: TypeDefinition) -> Result<Literal, Error> {
+        self.reader_of(handle)?.read(type_def)
+    }
+
+    pub fn write(&mut self, handle: FileHandle, text: &str) -> Result<(), Error> {
+        let file = self.open.get(&handle.0).ok_or(Message::FileNotOpen)?;
+        if file.reader.is_some() {
+            return Err(Message::FileNotWritable(file.path.clone()).into());
+        }
+        self.fs
+            .write(&file.path, &file.encoding.encode(text), true)
+            .map_err(|err| cannot_open(&file.path, err))
+    }
+
+    pub fn can_read(&self, name: &str) -> bool {
+        self.full_path(name)
+            .is_ok_and(|path| self.fs.read(&path).is_ok())
+    }
+
+    pub fn can_write(&self, name: &str) -> bool {
+        self.metadata(name)
+            .is_some_and(|metadata| !metadata.is_dir && !metadata.readonly)
+    }
+
+    pub fn metadata(&self, name: &str) -> Option<Metadata> {
+        self.fs.metadata(&self.full_path(name).ok()?)
+    }
+
+    pub fn create_dir(&mut self, name: &str) -> bool {
+        self.full_path(name)
+            .is_ok_and(|path| self.fs.create_dir(&path).is_ok())
+    }
+
+    pub fn remove_file(&mut self, name: &str) -> bool {
+        self.full_path(name).is_ok_and(|path| {
+            !self.open.values().any(|file| file.path == path) && self.fs.remove_file(&path).is_ok()
+        })
+    }
+
+    pub fn remove_dir(&mut self, name: &str) -> bool {
+        self.full_path(name)
+            .is_ok_and(|path| self.fs.remove_dir(&path).is_ok())
+    }
+
+    /// `ввод` without a file reads `name` from now on, the keyboard again if
+    /// `name` is empty
+    pub fn redirect_input(&mut self, name: &str) -> Result<(), Error> {
+        self.input = match name {
+            "" => None,
+            name => Some(self.reader(name, &self.full_path(name)?)?),
+        };
+        Ok(())
+    }
+
+    /// `вывод` without a file writes to `name` from now on, to the screen
+    /// again if `name` is empty
+    pub fn redirect_output(&mut self, name: &str) -> Result<(), Error> {
+        self.output = match name {
+            "" => None,
+            name => {
+                let path = self.full_path(name)?;
+                self.fs
+                    .write(&path, &[], false)
+                    .map_err(|err| cannot_open(name, err))?;
+                Some((path, self.encoding))
+            }
+        };
+        Ok(())
+    }
+
+    /// Reads from the file set by `НАЗНАЧИТЬ ВВОД`, `None` if there is none
+    pub fn read_redirected(&mut self, type_def: TypeDefinition) -> Option<Result<Literal, Error>> {
+        Some(self.input.as_mut()?.read(type_def))
+    }
+
+    /// Writes to the file set by `НАЗНАЧИТЬ ВЫВОД`, `None` if there is none
+    pub fn write_redirected(&mut self, text: &str) -> Option<Result<(), Error>> {
+        let (path, encoding) = self.output.as_ref()?;
+        Some(
+            self.fs
+                .write(path, &encoding.encode(text), true)
+                .map_err(|err| cannot_open(path, err)),
+        )
+    }
+
+    fn reader(&self, name: &str, path: &str) -> Result<TextReader, Error> {
+        let bytes = self.fs.read(path).map_err(|err| cannot_open(name, err))?;
+        Ok(TextReader::new(&self.encoding.decode(&bytes)))
+    }
+
+    fn reader_of(&mut self, handle: FileHandle) -> Result<&mut TextReader, Error> {
+        let file = self.open.get_mut(&handle.0).ok_or(Message::FileNotOpen)?;
+        file.reader
+            .as_mut()
+            .ok_or(Message::FileNotReadable(file.path.clone()).into())
+    }
+}
+
+fn cannot_open(name: &str, err: io::Error) -> Error {
+    Message::CannotOpenFile {
+        name: name.to_string(),
+        reason: err.to_string(),
+    }
+    .into()
+}
+
+/// Reads values the way `ввод` does: words separated by whitespace, `лит`
+/// takes the rest of the line and `сим` the next character
+struct TextReader {
+    text: Vec<char>,
+    position: usize,
+}
+
+impl TextReader {
+    fn new(text: &str) -> Self {
+        Self {
+            text: text.chars().collect(),
+            position: 0,
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.text.len()
+    }
+
+    fn has_data(&self) -> bool {
+        self.text[self.position.min(self.text.len())..]
+            .iter()
+            .any(|char| !char.is_whitespace())
+    }
+
+    fn read(&mut self, type_def: TypeDefinition) -> Result<Literal, Error> {
+        if self.at_end() {
+            return Err(Message::InputIsOver.into());
+        }
+        match type_def {
+            TypeDefinition::Char => {
+                self.position += 1;
+                Ok(Literal::Char(self.text[self.position - 1]))
+            }
+            TypeDefinition::String => {
+                let line_end = |from: usize| {
+                    self.text[from..]
+                        .iter()
+                        .position(|char| *char == '\n')
+                        .map_or(self.text.len(), |end| from + end)
+                };
+                //A line already read up to its whitespace is skipped
+                let end = line_end(self.position);
+                if self.position > 0
+                    && self.text[self.position - 1] != '\n'
+                    && self.text[self.position..end]
+                        .iter()
+                        .all(|char| char.is_whitespace())
+                    && end < self.text.len()
+                {
+                    self.position = end + 1;
+                }
+                let end = line_end(self.position);
+                let line = self.text[self.position..end].iter().collect::<String>();
+                self.position = (end + 1).min(self.text.len());
+                Ok(Literal::String(line.trim_end_matches('\r').to_string()))
+            }
+            type_def => {
+                while self
+                    .text
+                    .get(self.position)
+                    .is_some_and(|char| char.is_whitespace())
+                {
+                    self.position += 1;
+                }
+                if self.at_end() {
+                    return Err(Message::InputIsOver.into());
+                }
+                let start = self.position;
+                while self
+                    .text
+                    .get(self.position)
+                    .is_some_and(|char| !char.is_whitespace())
+                {
+                    self.position += 1;
+                }
+                parse_value(
+                    &self.text[start..self.position].iter().collect::<String>(),
+                    type_def,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn names_stay_inside_the_root() {
+        let resolved = |working_dir, name| resolve(working_dir, name).unwrap();
+        assert_eq!(resolved("/data", "in.txt"), "/data/in.txt");
+        assert_eq!(resolved("/data", "./a/../in.txt"), "/data/in.txt");
+        assert_eq!(resolved("/data", "../in.txt"), "/in.txt");
+        assert_eq!(resolved("/data", "/tmp//in.txt"), "/tmp/in.txt");
+        assert_eq!(resolved("/data", "\\tmp\\in.txt"), "/tmp/in.txt");
+        assert_eq!(resolved("/", "a\\..\\b"), "/b");
+        for name in [
+            "..",
+            "/..",
+            "../../etc/passwd",
+            "a/../../b",
+            "\\..\\b",
+            "a\\..\\..\\b",
+        ] {
+            assert_eq!(
+                resolve("/", name),
+                Err(Message::OutsideSandbox(name.to_string()).into()),
+                "{name}"
+            );
+        }
+        assert!(resolve("/data", "../../b").is_err());
+    }
+
+    fn error<T: fmt::Debug>(result: io::Result<T>) -> io::ErrorKind {
+        result.unwrap_err().kind()
+    }
+
+    #[test]
+    fn memory_files_are_shared_by_clones() {
+        let fs = MemoryFs::default();
+        fs.insert("data/in.txt", "12");
+        let mut clone = fs.clone();
+        assert_eq!(clone.read("/data/in.txt").unwrap(), b"12");
+        clone.write("/data/in.txt", b" 13", true).unwrap();
+        assert_eq!(fs.get("/data/in.txt").unwrap(), b"12 13");
+        clone.write("/data/in.txt", b"14", false).unwrap();
+        assert_eq!(fs.get("data/in.txt").unwrap(), b"14");
+
+        assert_eq!(error(clone.read("/data")), io::ErrorKind::IsADirectory);
+        assert_eq!(error(clone.read("/out.txt")), io::ErrorKind::NotFound);
+        assert_eq!(
+            error(clone.write("/missing/out.txt", b"", false)),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            error(clone.remove_dir("/data")),
+            io::ErrorKind::DirectoryNotEmpty
+        );
+        clone.remove_file("/data/in.txt").unwrap();
+        clone.remove_dir("/data").unwrap();
+        assert_eq!(fs.metadata("/data"), None);
+        assert!(fs.metadata("/").unwrap().is_dir);
+    }
+
+    /// Directory of a test, removed afterwards
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("kumir_{name}_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(path.join("root/data")).unwrap();
+            fs::create_dir_all(path.join("outside")).unwrap();
+            fs::write(path.join("outside/secret.txt"), "секрет").unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn native_files_are_under_the_root() {
+        let dir = TempDir::new("native");
+        let mut fs = NativeFs::new(dir.0.join("root")).unwrap();
+        fs.write("/data/out.txt", b"1", false).unwrap();
+        fs.write("/data/out.txt", b"2", true).unwrap();
+        assert_eq!(fs.read("/data/out.txt").unwrap(), b"12");
+        assert_eq!(fs::read(dir.0.join("root/data/out.txt")).unwrap(), b"12");
+        fs.create_dir("/data/sub").unwrap();
+        assert!(fs.metadata("/data/sub").unwrap().is_dir);
+        fs.remove_dir("/data/sub").unwrap();
+        fs.remove_file("/data/out.txt").unwrap();
+        assert_eq!(fs.metadata("/data/out.txt"), None);
+        assert!(NativeFs::new(dir.0.join("outside/secret.txt")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_out_of_the_root_are_refused() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("links");
+        let (root, outside) = (dir.0.join("root"), dir.0.join("outside"));
+        symlink(&outside, root.join("out")).unwrap();
+        symlink(outside.join("secret.txt"), root.join("secret.txt")).unwrap();
+        symlink(outside.join("new.txt"), root.join("dangling")).unwrap();
+        symlink(root.join("data"), root.join("inner")).unwrap();
+        let mut fs = NativeFs::new(&root).unwrap();
+
+        let denied = io::ErrorKind::PermissionDenied;
+        assert_eq!(error(fs.read("/out/secret.txt")), denied);
+        assert_eq!(error(fs.read("/secret.txt")), denied);
+        assert!(fs.write("/out/new.txt", b"1", false).is_err());
+        assert!(fs.write("/dangling", b"1", false).is_err());
+        assert!(fs.create_dir("/out/sub").is_err());
+        assert_eq!(fs.metadata("/out"), None);
+        assert!(!outside.join("new.txt").exists());
+        assert!(!outside.join("sub").exists());
+
+        fs.write("/inner/in.txt", b"1", false).unwrap();
+        assert_eq!(fs::read(root.join("data/in.txt")).unwrap(), b"1");
+    }
+
+    fn files(contents: &str) -> (Files, FileHandle) {
+        let fs = MemoryFs::default();
+        fs.insert("/in.txt", contents);
+        let mut files = Files::new(fs);
+        let file = files.open("in.txt", OpenMode::Read).unwrap();
+        (files, file)
+    }
+
+    #[test]
+    fn words_lines_and_characters_are_read_like_input() {
+        let (mut files, file) = files("12 абв\nвторая строка\r\nя5\n");
+        let mut read = |type_def| files.read(file, type_def);
+        assert_eq!(read(TypeDefinition::Int), Ok(Literal::Int(12)));
+        assert_eq!(
+            read(TypeDefinition::String),
+            Ok(Literal::String(" абв".into()))
+        );
+        assert_eq!(
+            read(TypeDefinition::String),
+            Ok(Literal::String("вторая строка".into()))
+        );
+        assert_eq!(read(TypeDefinition::Char), Ok(Literal::Char('я')));
+        assert_eq!(read(TypeDefinition::Int), Ok(Literal::Int(5)));
+        assert_eq!(read(TypeDefinition::Int), Err(Message::InputIsOver.into()));
+        assert!(!files.has_data(file).unwrap());
+        files.rewind(file).unwrap();
+        assert_eq!(files.read(file, TypeDefinition::Int), Ok(Literal::Int(12)));
+    }
+
+    #[test]
+    fn line_after_a_number_is_the_next_one() {
+        let (mut files, file) = files("5  \nстрока");
+        assert_eq!(files.read(file, TypeDefinition::Int), Ok(Literal::Int(5)));
+        assert_eq!(
+            files.read(file, TypeDefinition::String),
+            Ok(Literal::String("строка".into()))
+        );
+        assert!(files.at_end(file).unwrap());
+    }
+
+    #[test]
+    fn files_are_used_as_opened() {
+        let (mut files, file) = files("1");
+        assert_eq!(
+            files.open("/in.txt", OpenMode::Append),
+            Err(Message::FileAlreadyOpen("/in.txt".into()).into())
+        );
+        assert_eq!(
+            files.write(file, "2"),
+            Err(Message::FileNotWritable("/in.txt".into()).into())
+        );
+        let out = files.open("out.txt", OpenMode::Write).unwrap();
+        assert_eq!(
+            files.read(out, TypeDefinition::Int),
+            Err(Message::FileNotReadable("/out.txt".into()).into())
+        );
+        assert_eq!(files.open_files(), ["/in.txt", "/out.txt"]);
+        assert_eq!(
+            files.close(FileHandle::CONSOLE),
+            Err(Message::CloseConsole.into())
+        );
+        files.close(file).unwrap();
+        assert_eq!(files.close(file), Err(Message::FileNotOpen.into()));
+        assert!(matches!(
+            files.open("missing.txt", OpenMode::Read),
+            Err(Error::Message(Message::CannotOpenFile { .. }))
+        ));
+    }
+}
@@ -0,0 +1,260 @@
+//! Read-only view of a paused program: the call stack with parameters and
+//! locals of every algorithm, and the global variables.
+//!
+//! Scopes of blocks (`нц`, `если`) are merged into the algorithm or the
+//! program they belong to, variables are listed in declaration order and an
+//! inner declaration hides an outer one with the same name.
+
+use std::{cell::RefCell, rc::Rc};
+
+use indexmap::IndexMap;
+
+use crate::{
+    ast::{Environment, Literal},
+    lexer::{FunctionParamType, Keyword, Span, TypeDefinition},
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct VariableView {
+    pub name: String,
+    pub type_def: TypeDefinition,
+    /// `None` until the variable is assigned
+    pub value: Option<Literal>,
+}
+
+impl VariableView {
+    /// Kumir name of the type, e.g. `цел`
+    pub fn type_name(&self) -> String {
+        Keyword::TypeDef(self.type_def).to_string()
+    }
+
+    /// Value as shown to the student, `не определено` if it isn't assigned
+    pub fn value_text(&self) -> String {
+        value_text(self.value.as_ref())
+    }
+}
+
+/// Value as shown to the student, `не определено` for `None`
+pub fn value_text(value: Option<&Literal>) -> String {
+    match value {
+        None => "не определено".to_string(),
+        Some(Literal::Bool(true)) => "да".to_string(),
+        Some(Literal::Bool(false)) => "нет".to_string(),
+        Some(value) => value.to_string(),
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParameterView {
+    pub kind: FunctionParamType,
+    pub variable: VariableView,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FrameView {
+    pub name: String,
+    pub call_site: Span,
+    pub parameters: Vec<ParameterView>,
+    /// Locals, including `знач` of functions
+    pub locals: Vec<VariableView>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ProgramState {
+    /// Statement that is about to be executed
+    pub span: Span,
+    /// Algorithm calls, the innermost one is the last
+    pub call_stack: Vec<FrameView>,
+    /// Variables of the main program
+    pub globals: Vec<VariableView>,
+}
+
+/// State of the program `environment` belongs to, meant to be called from a
+/// [`crate::debugger::StopHandler`] with the environment it was given
+pub fn inspect(environment: &Rc<RefCell<Environment>>) -> ProgramState {
+    let debugger = environment.borrow().debugger.clone();
+    let debugger = debugger.borrow();
+
+    //Innermost scope of the main program followed by one for every call
+    let mut scopes = debugger.caller_scopes().to_vec();
+    scopes.push(environment.clone());
+    let mut scopes = scopes.iter();
+
+    let globals = scopes
+        .next()
+        .map(|scope| visible_variables(scope).into_values().collect())
+        .unwrap_or_default();
+
+    let call_stack = debugger
+        .call_stack
+        .iter()
+        .zip(scopes)
+        .map(|(frame, scope)| {
+            let mut variables = visible_variables(scope);
+            let parameters = frame
+                .params
+                .iter()
+                .filter_map(|(name, kind)| {
+                    Some(ParameterView {
+                        kind: *kind,
+                        variable: variables.shift_remove(name)?,
+                    })
+                })
+                .collect();
+            FrameView {
+                name: frame.name.clone(),
+                call_site: frame.call_site,
+                parameters,
+                locals: variables.into_values().collect(),
+            }
+        })
+        .collect();
+
+    ProgramState {
+        span: debugger.current_span,
+        call_stack,
+        globals,
+    }
+}
+
+/// Variables of `scope` and its parents, outermost declarations first
+fn visible_variables(scope: &Rc<RefCell<Environment>>) -> IndexMap<String, VariableView> {
+    let mut chain = vec![scope.clone()];
+    let mut parent = scope.borrow().environment.clone();
+    while let Some(scope) = parent {
+        parent = scope.borrow().environment.clone();
+        chain.push(scope);
+    }
+
+    let mut variables = IndexMap::new();
+    for scope in chain.iter().rev() {
+        for (name, variable) in scope.borrow().variables.iter() {
+            variables.shift_remove(name);
+            variables.insert(
+                name.clone(),
+                VariableView {
+                    name: name.clone(),
+                    type_def: variable.type_def,
+                    value: variable.value.clone(),
+                },
+            );
+        }
+    }
+    variables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        debugger::{StepMode, Stop},
+        io::testing::interpreter,
+    };
+
+    /// State at every stop at the breakpoint on `line`
+    fn states_at(source: &str, line: usize) -> Vec<ProgramState> {
+        let (mut interpreter, _) = interpreter(source, &[]);
+        interpreter.set_breakpoint(line);
+        let states = Rc::new(RefCell::new(vec![]));
+        let recorded = states.clone();
+        interpreter.attach_debugger(move |_: &Stop, environment: &Rc<RefCell<Environment>>| {
+            recorded.borrow_mut().push(inspect(environment));
+            StepMode::Continue
+        });
+        interpreter.run().unwrap();
+        states.take()
+    }
+
+    const SOURCE: &str = "цел г := 1\nалг\nнач\nцел п, к, с\nк := 2\nс := сумма(3, п, к)\nкон\nалг цел сумма(арг цел а, рез цел р, аргрез цел к)\nнач\nцел х\nлит имя\nх := 2\nнц для и от 1 до 2\nцел в := а\nвывод в\nкц\nр := х\nзнач := а\nкон";
+
+    /// Names and values as shown to the student
+    fn shown(variables: &[VariableView]) -> Vec<(&str, String)> {
+        variables
+            .iter()
+            .map(|variable| (variable.name.as_str(), variable.value_text()))
+            .collect()
+    }
+
+    #[test]
+    fn frames_with_parameters_and_locals() {
+        let states = states_at(SOURCE, 15);
+        let [state] = states.as_slice() else {
+            panic!("{states:?}");
+        };
+        assert_eq!(state.span.start.line, 15);
+        assert_eq!(shown(&state.globals), [("г", "1".to_string())]);
+
+        let [main, sum] = state.call_stack.as_slice() else {
+            panic!("{:?}", state.call_stack);
+        };
+        assert_eq!(main.name, "main");
+        assert!(main.parameters.is_empty());
+        let undefined = "не определено".to_string();
+        assert_eq!(
+            shown(&main.locals),
+            [
+                ("п", undefined.clone()),
+                ("к", "2".to_string()),
+                ("с", undefined.clone())
+            ]
+        );
+
+        assert_eq!(sum.name, "сумма");
+        assert_eq!(sum.call_site.to_string(), "6:1");
+        let parameters: Vec<_> = sum
+            .parameters
+            .iter()
+            .map(|parameter| (parameter.kind, parameter.variable.name.as_str()))
+            .collect();
+        assert_eq!(
+            parameters,
+            [
+                (FunctionParamType::ArgumentParam, "а"),
+                (FunctionParamType::ResultParam, "р"),
+                (FunctionParamType::ArgumentResultParam, "к")
+            ]
+        );
+        assert_eq!(sum.parameters[1].variable.value_text(), undefined);
+        //Variables of the loop come after the ones of the algorithm
+        assert_eq!(
+            shown(&sum.locals),
+            [
+                ("знач", undefined.clone()),
+                ("х", "2".to_string()),
+                ("имя", undefined),
+                ("и", "1".to_string()),
+                ("в", "3".to_string())
+            ]
+        );
+        assert_eq!(sum.locals[2].type_name(), "лит");
+    }
+
+    #[test]
+    fn recursive_calls_innermost_last() {
+        let source =
+            "алг ф(арг цел н)\nнач\nесли н > 0 то\nф(н - 1)\nиначе\nвывод н\nвсе\nкон\nф(2)";
+        let states = states_at(source, 6);
+        let [state] = states.as_slice() else {
+            panic!("{states:?}");
+        };
+        let frames: Vec<_> = state
+            .call_stack
+            .iter()
+            .map(|frame| {
+                (
+                    frame.name.as_str(),
+                    frame.call_site.to_string(),
+                    frame.parameters[0].variable.value_text(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                ("ф", "9:1".to_string(), "2".to_string()),
+                ("ф", "4:1".to_string(), "1".to_string()),
+                ("ф", "4:1".to_string(), "0".to_string())
+            ]
+        );
+    }
+}
@@ -4,35 +4,388 @@ use std::{
     sync::{Arc, atomic::AtomicBool},
 };
 
+use indexmap::IndexMap;
 use log::{error, info};
 
 use crate::{
-    ast::{AstNode, Environment, Namespace, NativeFunction, Stmt},
-    lexer::{Lexer, Token},
+    ast::{
+        AstNode, Environment, EvalResult, Expr, FunctionCall, FunctionResult, FunctionVariant,
+        Literal, Namespace, NativeFunction, Statement, Stmt,
+    },
+    clock::Clock,
+    coverage::Coverage,
+    debugger::{Debugger, StopHandler},
+    error::RuntimeError,
+    flowchart::{self, Flowchart},
+    fs::VirtualFs,
+    inspector,
+    io::ProgramIo,
+    lexer::{FunctionParamType, Lexer, Token},
+    limits::{Budget, ExecutionLimits},
+    messages::{Error, Message},
     parser::Parser,
+    profiler::ProfileReport,
+    random::Random,
+    snapshot::Snapshot,
+    stdlib,
+    trace::Trace,
+    trace_table::TraceTable,
 };
 
 pub struct Interpreter {
     pub ast: AstNode,
     pub environment: Rc<RefCell<Environment>>,
+    /// Scope of the variables of the program, kept after it ran
+    globals: Option<Rc<RefCell<Environment>>>,
+    /// Values set with [`Interpreter::set_global`] before the variables
+    /// are declared
+    presets: IndexMap<String, Literal>,
+}
+
+/// What [`Interpreter::call`] gets back from an algorithm
+#[derive(Debug, PartialEq, Clone)]
+pub struct CallResult {
+    /// `знач` of a function, `None` for a procedure
+    pub value: Option<Literal>,
+    /// Values of the `рез` and `аргрез` parameters by name, unassigned ones
+    /// are missing
+    pub results: IndexMap<String, Literal>,
 }
 
 impl Interpreter {
-    pub fn run(&mut self) -> Result<(), String> {
-        self.register_functions();
-        match self.ast.eval(&self.environment) {
-            Ok(_) => {
-                info!("Program finished successfully");
-                Ok(())
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        self.environment.borrow().io.journal().borrow_mut().clear();
+        //After `execute` registered the algorithms, so the scope has them
+        self.execute(|interpreter| {
+            let globals = Environment::scope(&interpreter.environment);
+            interpreter.globals = Some(globals.clone());
+            interpreter.run_program(&globals)
+        })
+    }
+
+    /// Tells that the host put the actors and files back into their
+    /// starting state, so a snapshot can be restored
+    pub fn actors_reset(&mut self) {
+        self.environment
+            .borrow()
+            .io
+            .journal()
+            .borrow_mut()
+            .actors_reset();
+    }
+
+    /// Loads the program of `snapshot`, the next [`Interpreter::run`] replays
+    /// it up to where the snapshot was taken and stops there with
+    /// [`crate::debugger::StopReason::Restored`], see [`crate::snapshot`].
+    /// Fails when actors ran commands since [`Interpreter::actors_reset`]
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+        if !self
+            .environment
+            .borrow()
+            .io
+            .journal()
+            .borrow()
+            .actors_untouched()
+        {
+            return Err(Message::ActorsNotReset.into());
+        }
+        self.load_string(&snapshot.source)?;
+        self.set_seed(snapshot.seed);
+        let environment = self.environment.borrow();
+        environment.io.journal().borrow_mut().start_replay(snapshot);
+        environment
+            .debugger
+            .borrow_mut()
+            .restore_at(snapshot.steps, snapshot.state.clone());
+        Ok(())
+    }
+
+    /// Calls the algorithm `name` without running the program. `args` are
+    /// the values of its `арг` and `аргрез` parameters in order, `рез` ones
+    /// are skipped. Imports and variables of the last run stay visible
+    pub fn call(&mut self, name: &str, args: &[Literal]) -> Result<CallResult, RuntimeError> {
+        self.execute(|interpreter| {
+            let parent = interpreter
+                .globals
+                .as_ref()
+                .unwrap_or(&interpreter.environment);
+            let scope = Environment::scope(parent);
+            let function = scope.borrow().get_function(name);
+            let params = match function {
+                Some(FunctionVariant::Native(function)) => function.params,
+                Some(FunctionVariant::Kumir(function)) => function.params,
+                _ => IndexMap::new(),
+            };
+            let mut args = args.iter();
+            let mut call = FunctionCall {
+                name: name.to_string(),
+                args: vec![],
+            };
+            for (param_name, param) in params.iter() {
+                let value = match param.result_type {
+                    FunctionParamType::ResultParam => None,
+                    _ => match args.next() {
+                        Some(value) => Some(value.clone()),
+                        None => break,
+                    },
+                };
+                call.args.push(match (param.result_type, value) {
+                    (FunctionParamType::ArgumentParam, Some(value)) => Expr::Literal(value),
+                    (_, value) => {
+                        let value =
+                            value.map(|value| scope.borrow().convert(value, param.type_definition));
+                        scope
+                            .borrow_mut()
+                            .new_var(param_name, value, param.type_definition);
+                        Expr::Identifier(param_name.clone())
+                    }
+                });
+            }
+            call.args.extend(args.cloned().map(Expr::Literal));
+
+            let value = match call.eval(&scope)? {
+                FunctionResult::Literal(value) => Some(value),
+                FunctionResult::Procedure => None,
+            };
+            let results = params
+                .iter()
+                .filter(|(_, param)| param.result_type != FunctionParamType::ArgumentParam)
+                .filter_map(|(name, _)| Some((name.clone(), scope.borrow().get_value(name)?)))
+                .collect();
+            Ok(CallResult { value, results })
+        })
+    }
+
+    /// Value of the variable `name` of the program after it ran
+    pub fn global(&self, name: &str) -> Option<Literal> {
+        self.globals.as_ref()?.borrow().get_value(name)
+    }
+
+    /// Sets the variable `name` of the program. Before the program runs,
+    /// the value replaces the initial one when the program declares `name`
+    pub fn set_global(&mut self, name: &str, value: Literal) -> Result<(), Error> {
+        if let Some(globals) = &self.globals
+            && globals.borrow().variables.contains_key(name)
+        {
+            return globals.borrow_mut().assign_var(name, value);
+        }
+        self.presets.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Statements of the program in `globals`, presets are assigned right
+    /// after their variable is declared
+    fn run_program(&self, globals: &Rc<RefCell<Environment>>) -> Result<(), Error> {
+        let AstNode::Program(body) = &self.ast else {
+            return self.ast.eval(globals).map(|_| ());
+        };
+        for statement in body {
+            let result = statement.eval(globals)?;
+            for name in declared_names(statement) {
+                if let Some(value) = self.presets.get(name) {
+                    globals.borrow_mut().assign_var(name, value.clone())?;
+                }
+            }
+            if let EvalResult::Literal(_) | EvalResult::Break = result {
+                break;
             }
-            Err(e) => Err(format!("Runtime error: {e}")),
         }
+        Ok(())
+    }
+
+    /// Runs `body` like a program: with the budget, the debugger and the
+    /// check for files left open
+    fn execute<T>(
+        &mut self,
+        body: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, RuntimeError> {
+        self.register_functions();
+        //Every run draws the numbers of the seed from the first one on
+        let seed = self.seed();
+        self.set_seed(seed);
+        self.environment.borrow().budget.borrow_mut().start();
+        let debugger = self.environment.borrow().debugger.clone();
+        let profiler = self.environment.borrow().profiler.clone();
+        if let Some(profiler) = &profiler {
+            profiler.borrow_mut().start();
+        }
+        if let Some(coverage) = &self.environment.borrow().coverage {
+            coverage.borrow_mut().add_program(&self.ast);
+        }
+        let trace = self.environment.borrow().trace.clone();
+        if let Some(trace) = &trace {
+            trace.borrow_mut().clear();
+        }
+        Debugger::started(&debugger, &self.environment);
+        let mut result = body(self);
+        if let Some(profiler) = &profiler {
+            profiler.borrow_mut().finish();
+        }
+        if let Some(trace) = &trace {
+            let scope = self.globals.as_ref().unwrap_or(&self.environment);
+            trace.borrow_mut().finish(inspector::inspect(scope));
+        }
+        //The program ended or failed before the statement of the snapshot
+        if debugger.borrow_mut().abandon_restore() {
+            self.environment
+                .borrow()
+                .io
+                .journal()
+                .borrow_mut()
+                .finish_replay();
+            result = result.and(Err(Message::SnapshotMismatch.into()));
+        }
+        let result = match result {
+            Ok(value) => {
+                let open_files = self.environment.borrow().io.files().borrow().open_files();
+                if open_files.is_empty() {
+                    info!("Program finished successfully");
+                    Ok(value)
+                } else {
+                    Err(RuntimeError::new(Message::FilesLeftOpen(open_files)))
+                }
+            }
+            Err(message) => Err(debugger.borrow_mut().take_error(message)),
+        };
+        let finished = result.as_ref().map(|_| ()).map_err(Clone::clone);
+        Debugger::finished(&debugger, &finished);
+        result
+    }
+
+    /// Every statement is reported to `handler` before it runs, which
+    /// decides whether execution stops there. See [`crate::debugger`]
+    pub fn attach_debugger(&mut self, handler: impl StopHandler + 'static) {
+        self.environment
+            .borrow()
+            .debugger
+            .borrow_mut()
+            .attach(Box::new(handler));
+    }
+
+    /// Sends `вывод` to and reads `ввод` from `io` instead of the standard
+    /// output and input
+    pub fn set_io(&mut self, io: impl ProgramIo + 'static) {
+        self.environment.borrow_mut().io.set_program_io(io);
+    }
+
+    /// Clock of `ждать` and `время`, by default [`crate::clock::SystemClock`]
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.environment.borrow_mut().clock = clock;
+    }
+
+    /// Seeds `rnd`, `rand`, `irnd` and `irand`, a program run with the same
+    /// seed and input gets the same numbers
+    pub fn set_seed(&mut self, seed: u64) {
+        *self.environment.borrow().random.borrow_mut() = Random::new(seed);
+    }
+
+    /// Seed of the random functions, picked from the current time unless
+    /// [`Interpreter::set_seed`] was called
+    pub fn seed(&self) -> u64 {
+        self.environment.borrow().random.borrow().seed()
+    }
+
+    /// Files the program reaches with `Файлы`, by default an empty
+    /// [`crate::fs::MemoryFs`]
+    pub fn set_fs(&mut self, fs: impl VirtualFs + 'static) {
+        self.environment.borrow().io.files().borrow_mut().set_fs(fs);
+    }
+
+    /// The program fails once it exceeds `limits`, see
+    /// [`Budget::exceeded`] for which one
+    pub fn set_limits(&mut self, limits: ExecutionLimits) {
+        *self.environment.borrow().budget.borrow_mut() = Budget::new(limits);
+    }
+
+    /// Measures the next runs and calls, see [`crate::profiler`]
+    pub fn enable_profiler(&mut self) {
+        self.environment.borrow_mut().profiler = Some(Default::default());
+    }
+
+    pub fn disable_profiler(&mut self) {
+        self.environment.borrow_mut().profiler = None;
+    }
+
+    /// Measurements of the last run or call, `None` unless
+    /// [`Interpreter::enable_profiler`] was called
+    pub fn profile(&self) -> Option<ProfileReport> {
+        self.environment
+            .borrow()
+            .profiler
+            .as_ref()
+            .map(|profiler| profiler.borrow().report())
+    }
+
+    /// Records the statements and branches the next runs and calls enter,
+    /// adding up over all of them, see [`crate::coverage`]
+    pub fn enable_coverage(&mut self) {
+        self.environment.borrow_mut().coverage = Some(Default::default());
+    }
+
+    /// Statements and branches run since [`Interpreter::enable_coverage`]
+    pub fn coverage(&self) -> Option<Coverage> {
+        self.environment
+            .borrow()
+            .coverage
+            .as_ref()
+            .map(|coverage| coverage.borrow().clone())
+    }
+
+    /// Records the next runs and calls for stepping backwards, see
+    /// [`crate::trace`]
+    pub fn enable_trace(&mut self) {
+        self.environment.borrow_mut().trace = Some(Default::default());
+    }
+
+    /// Record of the last run or call, `None` unless
+    /// [`Interpreter::enable_trace`] was called
+    pub fn trace(&self) -> Option<Rc<RefCell<Trace>>> {
+        self.environment.borrow().trace.clone()
+    }
+
+    /// Flowcharts of the loaded program, see [`crate::flowchart`]
+    pub fn flowcharts(&self) -> Vec<Flowchart> {
+        let io = self.environment.borrow().io.clone();
+        let source = io.journal().borrow().source.clone();
+        flowchart::flowcharts(&self.ast, &source)
+    }
+
+    /// Runs the program with a trace and makes its trace table with the
+    /// columns `variables`, all of them when empty, see
+    /// [`crate::trace_table`]
+    pub fn trace_table(&mut self, variables: &[String]) -> TraceTable {
+        self.enable_trace();
+        let result = self.run();
+        let io = self.environment.borrow().io.clone();
+        let source = io.journal().borrow().source.clone();
+        let trace = self.trace().unwrap_or_default();
+        let mut table = TraceTable::new(&trace.borrow(), &self.ast, &source, variables);
+        table.error = result.err().map(|err| err.to_string());
+        table
+    }
+
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.environment
+            .borrow()
+            .debugger
+            .borrow()
+            .breakpoints
+            .set(line);
+    }
+
+    pub fn clear_breakpoint(&mut self, line: usize) {
+        self.environment
+            .borrow()
+            .debugger
+            .borrow()
+            .breakpoints
+            .clear(line);
     }
 
     pub fn register_functions(&mut self) {
         if let AstNode::Program(body) = &self.ast {
-            for stmt in body {
-                if let Stmt::Alg(alg) = stmt {
+            for statement in body {
+                if let Stmt::Alg(alg) = &statement.stmt {
                     self.environment.borrow_mut().register_function(
                         &alg.name,
                         crate::ast::FunctionVariant::Kumir(alg.clone()),
@@ -72,11 +425,17 @@ impl Interpreter {
     pub fn new(ast: AstNode, kill_flag: Arc<AtomicBool>) -> Self {
         let mut environment: Environment = Default::default();
         environment.kill_flag = kill_flag;
+        stdlib::register(&mut environment);
         let environment = Rc::new(RefCell::new(environment));
-        Interpreter { ast, environment }
+        Interpreter {
+            ast,
+            environment,
+            globals: None,
+            presets: IndexMap::new(),
+        }
     }
 
-    pub fn new_from_tokens(tokens: Vec<Token>, kill_flag: Arc<AtomicBool>) -> Result<Self, String> {
+    pub fn new_from_tokens(tokens: Vec<Token>, kill_flag: Arc<AtomicBool>) -> Result<Self, Error> {
         let mut parser = Parser::new(tokens);
         match parser.parse() {
             Ok(ast) => {
@@ -89,17 +448,27 @@ impl Interpreter {
                 error!("Error parsing AST: {}", err);
                 error!("Statements parsed: {:#?}", statements);
                 error!("AST generator stopped at token: {}", parser.position);
-                Err("Error creating interpreter".to_string())
+                Err(err)
             }
         }
     }
 
-    pub fn new_from_string(input: &str, kill_flag: Arc<AtomicBool>) -> Result<Self, String> {
+    pub fn new_from_string(input: &str, kill_flag: Arc<AtomicBool>) -> Result<Self, Error> {
+        let mut interpreter = Interpreter::new(AstNode::Program(vec![]), kill_flag);
+        interpreter.load_string(input)?;
+        Ok(interpreter)
+    }
+
+    /// Parses `input` into the program of this interpreter. Native functions
+    /// and namespaces registered before loading are known to the parser, so
+    /// multi-word names like `слева свободно` resolve correctly
+    pub fn load_string(&mut self, input: &str) -> Result<(), Error> {
+        self.environment.borrow().io.journal().borrow_mut().source = input.to_string();
         let mut lexer = Lexer::new(input);
         let mut tokens = vec![];
         loop {
-            match lexer.next_token() {
-                Ok(Token::Eof) => break,
+            match lexer.next_spanned_token() {
+                Ok((Token::Eof, _)) => break,
                 Ok(token) => {
                     tokens.push(token);
                 }
@@ -108,6 +477,7 @@ impl Interpreter {
                         "Error: {e}, tokens parsed {:#?}",
                         tokens.iter().enumerate().collect::<Vec<_>>()
                     );
+                    return Err(e);
                 }
             }
         }
@@ -115,6 +485,90 @@ impl Interpreter {
             "tokens parsed: {:#?}",
             tokens.iter().enumerate().collect::<Vec<_>>()
         );
-        Self::new_from_tokens(tokens, kill_flag)
+        let known_names = self.environment.borrow().known_names();
+        let known_types = self.environment.borrow().known_types();
+        let mut parser = Parser::with_names(tokens, &known_names, &known_types);
+        match parser.parse() {
+            Ok(ast) => {
+                info!("AST generated: {ast:#?}");
+                self.ast = ast;
+                Ok(())
+            }
+            Err(err) => {
+                let (statements, err) = err;
+                error!("Error parsing AST: {}", err);
+                error!("Statements parsed: {:#?}", statements);
+                error!("AST generator stopped at token: {}", parser.position);
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Variables `statement` declares
+fn declared_names(statement: &Statement) -> Vec<&String> {
+    match &statement.stmt {
+        Stmt::VarDecl(var_decl) => vec![&var_decl.name],
+        Stmt::VarsDecl(var_decls) => var_decls.iter().map(|var_decl| &var_decl.name).collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::interpreter;
+
+    const ALGORITHMS: &str = "алг цел удв(цел х)
+нач
+  знач := х * 2
+кон
+
+алг цел учетв(цел х)
+нач
+  знач := удв(удв(х))
+кон
+
+алг разложить(цел х, рез цел двойное, аргрез цел сумма)
+нач
+  двойное := учетв(х) / 2
+  сумма := сумма + двойное
+кон
+";
+
+    #[test]
+    fn called_algorithm_calls_others() {
+        let (mut interpreter, _) = interpreter(ALGORITHMS, &[]);
+        let result = interpreter.call("учетв", &[Literal::Int(3)]).unwrap();
+        assert_eq!(result.value, Some(Literal::Int(12)));
+        assert!(result.results.is_empty());
+    }
+
+    #[test]
+    fn called_algorithm_returns_results() {
+        let (mut interpreter, _) = interpreter(ALGORITHMS, &[]);
+        let result = interpreter
+            .call("разложить", &[Literal::Int(3), Literal::Int(10)])
+            .unwrap();
+        assert_eq!(result.value, None);
+        assert_eq!(
+            result.results,
+            IndexMap::from([
+                ("двойное".to_string(), Literal::Int(6)),
+                ("сумма".to_string(), Literal::Int(16)),
+            ])
+        );
+    }
+
+    #[test]
+    fn globals_are_preset_and_read_back() {
+        let (mut interpreter, output) = interpreter("цел н = 1\nвывод н\nн := н + 1", &[]);
+        interpreter.set_global("н", Literal::Int(5)).unwrap();
+        interpreter.run().unwrap();
+        assert_eq!(output.take(), "5");
+        assert_eq!(interpreter.global("н"), Some(Literal::Int(6)));
+
+        interpreter.set_global("н", Literal::Int(1)).unwrap();
+        assert_eq!(interpreter.global("н"), Some(Literal::Int(1)));
     }
 }
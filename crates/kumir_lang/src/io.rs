@@ -0,0 +1,308 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{BufRead, Write},
+    rc::Rc,
+};
+
+use crate::{
+    ast::Literal,
+    fs::{FileHandle, Files},
+    lexer::TypeDefinition,
+    messages::{Error, Message},
+    snapshot::Journal,
+};
+
+/// Where `вывод` writes to and `ввод` reads from
+pub trait ProgramIo {
+    fn write(&mut self, text: &str);
+
+    /// Next line of input without the line break
+    fn read_line(&mut self) -> Result<String, Error>;
+}
+
+/// Standard output and input of the process
+pub struct StdIo;
+
+impl ProgramIo for StdIo {
+    fn write(&mut self, text: &str) {
+        print!("{text}");
+        let _ = std::io::stdout().flush();
+    }
+
+    fn read_line(&mut self) -> Result<String, Error> {
+        let mut line = String::new();
+        match std::io::stdin().lock().read_line(&mut line) {
+            Ok(0) => Err(Message::InputIsOver.into()),
+            Ok(_) => Ok(line.trim_end_matches(['\r', '\n']).to_string()),
+            Err(err) => Err(err.to_string().into()),
+        }
+    }
+}
+
+/// [`ProgramIo`] of a program, shared by all of its scopes. Keeps the rest
+/// of the input line, so `ввод` can read several values from one line.
+/// Also holds the program's [`Files`], which `ввод` and `вывод` may be
+/// redirected to, and the [`Journal`] of what it read
+#[derive(Clone)]
+pub struct Io {
+    io: Rc<RefCell<dyn ProgramIo>>,
+    pending: Rc<RefCell<VecDeque<String>>>,
+    files: Rc<RefCell<Files>>,
+    journal: Rc<RefCell<Journal>>,
+}
+
+impl Default for Io {
+    fn default() -> Self {
+        Self::new(StdIo)
+    }
+}
+
+impl std::fmt::Debug for Io {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Io")
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+impl Io {
+    pub fn new(io: impl ProgramIo + 'static) -> Self {
+        Self {
+            io: Rc::new(RefCell::new(io)),
+            pending: Default::default(),
+            files: Default::default(),
+            journal: Default::default(),
+        }
+    }
+
+    /// Replaces where the screen and the keyboard are, keeps the files
+    pub fn set_program_io(&mut self, io: impl ProgramIo + 'static) {
+        self.io = Rc::new(RefCell::new(io));
+        self.pending = Default::default();
+    }
+
+    pub fn files(&self) -> Rc<RefCell<Files>> {
+        self.files.clone()
+    }
+
+    pub fn journal(&self) -> Rc<RefCell<Journal>> {
+        self.journal.clone()
+    }
+
+    /// Writes to the screen unless a snapshot is being replayed
+    fn write_screen(&self, text: &str) {
+        if !self.journal.borrow().is_replaying() {
+            self.io.borrow_mut().write(text);
+        }
+    }
+
+    /// Writes to the screen or to the file set by `НАЗНАЧИТЬ ВЫВОД`
+    pub fn write(&self, text: &str) -> Result<(), Error> {
+        if let Some(result) = self.files.borrow_mut().write_redirected(text) {
+            return result;
+        }
+        self.write_screen(text);
+        Ok(())
+    }
+
+    /// Reads from the keyboard or from the file set by `НАЗНАЧИТЬ ВВОД`,
+    /// see [`Io::read_console`]
+    pub fn read(&self, type_def: TypeDefinition) -> Result<Literal, Error> {
+        if let Some(result) = self.files.borrow_mut().read_redirected(type_def) {
+            return result;
+        }
+        self.read_console(type_def)
+    }
+
+    /// `вывод` to a file, the console is always the screen
+    pub fn write_to(&self, handle: FileHandle, text: &str) -> Result<(), Error> {
+        if handle == FileHandle::CONSOLE {
+            self.write_screen(text);
+            return Ok(());
+        }
+        self.files.borrow_mut().write(handle, text)
+    }
+
+    /// `ввод` from a file, the console is always the keyboard
+    pub fn read_from(
+        &self,
+        handle: FileHandle,
+        type_def: TypeDefinition,
+    ) -> Result<Literal, Error> {
+        if handle == FileHandle::CONSOLE {
+            return self.read_console(type_def);
+        }
+        self.files.borrow_mut().read(handle, type_def)
+    }
+
+    /// Reads a value of `type_def` from the keyboard. Values are separated by
+    /// spaces, `лит` takes the rest of the line
+    pub fn read_console(&self, type_def: TypeDefinition) -> Result<Literal, Error> {
+        if self.pending.borrow().is_empty() {
+            let line = self
+                .journal
+                .borrow_mut()
+                .read_line(|| self.io.borrow_mut().read_line())?;
+            self.pending
+                .borrow_mut()
+                .extend(line.split_whitespace().map(str::to_string));
+        }
+        let mut pending = self.pending.borrow_mut();
+        let word = match type_def {
+            TypeDefinition::String => pending.drain(..).collect::<Vec<_>>().join(" "),
+            _ => pending.pop_front().unwrap_or_default(),
+        };
+        parse_value(&word, type_def)
+    }
+}
+
+pub(crate) fn parse_value(word: &str, type_def: TypeDefinition) -> Result<Literal, Error> {
+    let error = || {
+        Message::InvalidInput {
+            type_def,
+            word: word.to_string(),
+        }
+        .into()
+    };
+    match type_def {
+        TypeDefinition::Int => word.parse().map(Literal::Int).map_err(|_| error()),
+        TypeDefinition::Float => word.parse().map(Literal::Float).map_err(|_| error()),
+        TypeDefinition::Bool => match word {
+            "да" => Ok(Literal::Bool(true)),
+            "нет" => Ok(Literal::Bool(false)),
+            _ => Err(error()),
+        },
+        TypeDefinition::Char => {
+            let mut chars = word.chars();
+            match (chars.next(), chars.next()) {
+                (Some(char), None) => Ok(Literal::Char(char)),
+                _ => Err(error()),
+            }
+        }
+        TypeDefinition::String => Ok(Literal::String(word.to_string())),
+        TypeDefinition::Custom(_) => Err(error()),
+    }
+}
+
+/// Runs test programs on scripted input
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::sync::{Arc, atomic::AtomicBool};
+
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    /// Reads `input` line by line, collects the output
+    pub(crate) struct ScriptedIo {
+        input: VecDeque<String>,
+        output: Rc<RefCell<String>>,
+    }
+
+    impl ProgramIo for ScriptedIo {
+        fn write(&mut self, text: &str) {
+            self.output.borrow_mut().push_str(text);
+        }
+
+        fn read_line(&mut self) -> Result<String, Error> {
+            self.input.pop_front().ok_or(Message::InputIsOver.into())
+        }
+    }
+
+    /// Interpreter of `source` reading `input`, with its future output
+    pub(crate) fn interpreter(source: &str, input: &[&str]) -> (Interpreter, Rc<RefCell<String>>) {
+        let mut interpreter =
+            Interpreter::new_from_string(source, Arc::new(AtomicBool::new(false))).unwrap();
+        let output = Rc::new(RefCell::new(String::new()));
+        interpreter.set_io(ScriptedIo {
+            input: input.iter().map(|line| line.to_string()).collect(),
+            output: output.clone(),
+        });
+        (interpreter, output)
+    }
+
+    /// Output of `source` run on `input`, or the error it failed with
+    pub(crate) fn run(source: &str, input: &[&str]) -> Result<String, Error> {
+        let (mut interpreter, output) = interpreter(source, input);
+        interpreter.run().map_err(|err| err.message)?;
+        Ok(output.take())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::{interpreter, run};
+
+    /// Globals of `source` after it read `input`
+    fn read(source: &str, names: &[&str], input: &[&str]) -> Vec<Option<Literal>> {
+        let (mut interpreter, _) = interpreter(source, input);
+        interpreter.run().unwrap();
+        names.iter().map(|name| interpreter.global(name)).collect()
+    }
+
+    #[test]
+    fn input_reads_words_of_a_line_and_the_next_lines() {
+        let source = "цел а, б\nвещ в\nлог г\nсим д\nввод а, б\nввод в, г\nввод д";
+        assert_eq!(
+            read(source, &["а", "б", "в", "г", "д"], &["1 2 2.5", "да", "ё"]),
+            [
+                Some(Literal::Int(1)),
+                Some(Literal::Int(2)),
+                Some(Literal::Float(2.5)),
+                Some(Literal::Bool(true)),
+                Some(Literal::Char('ё')),
+            ]
+        );
+    }
+
+    #[test]
+    fn string_input_takes_the_rest_of_the_line() {
+        let source = "цел а\nлит с\nввод а, с";
+        assert_eq!(
+            read(source, &["а", "с"], &["3 два  слова"]),
+            [
+                Some(Literal::Int(3)),
+                Some(Literal::String("два слова".to_string()))
+            ]
+        );
+    }
+
+    #[test]
+    fn output_prints_values_without_quotes() {
+        let source = "вывод \"текст\", 'c', да, \" \", нет, 1, 2.5";
+        assert_eq!(run(source, &[]), Ok("текстcда нет12.5".to_string()));
+    }
+
+    #[test]
+    fn statement_after_a_line_break_runs() {
+        let source = "вывод \"а\", нс\nвывод \"б\", нс, нс, \"в\"\nвывод нс";
+        assert_eq!(run(source, &[]), Ok("а\nб\n\nв\n".to_string()));
+    }
+
+    #[test]
+    fn input_takes_variable_names() {
+        let parsed = crate::interpreter::Interpreter::new_from_string(
+            "ввод 5",
+            std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        );
+        assert_eq!(parsed.err().map(|err| err.code()), Some(Some("P004")));
+    }
+
+    #[test]
+    fn input_fails_on_a_wrong_value_or_without_input() {
+        assert_eq!(
+            run("цел а\nввод а", &["пять"]),
+            Err(Message::InvalidInput {
+                type_def: TypeDefinition::Int,
+                word: "пять".to_string(),
+            }
+            .into())
+        );
+        assert_eq!(run("цел а\nввод а", &[]), Err(Message::InputIsOver.into()));
+        assert_eq!(
+            run("ввод б", &["1"]),
+            Err(Message::UndefinedVariable("б".to_string()).into())
+        );
+    }
+}
@@ -2,6 +2,8 @@ use std::fmt;
 
 use log::info;
 
+use crate::messages::{Error, Message};
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Keyword(Keyword),
@@ -13,6 +15,10 @@ pub enum Token {
     String(String),
     Char(char),
     Bool(bool),
+    /// `| ...` comment, only produced when the lexer keeps trivia
+    Comment(String),
+    /// Line break or `;`
+    NewLine,
     Eof,
 }
 
@@ -40,6 +46,10 @@ impl Token {
         self == &Token::Operator(operator)
     }
 
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Comment(_) | Token::NewLine)
+    }
+
     pub fn identifier(&self) -> Option<String> {
         if let Token::Identifier(name) = self {
             Some(name.clone())
@@ -79,6 +89,26 @@ pub enum Operator {
     Assignment,
 }
 
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::GreaterOrEqual => ">=",
+            Operator::Greater => ">",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Equal => "=",
+            Operator::NotEqual => "<>",
+            Operator::EqualBool => "==",
+            Operator::Colon => ":",
+            Operator::Assignment => ":=",
+        })
+    }
+}
+
 impl Operator {
     pub fn precedence(&self) -> i32 {
         match self {
@@ -108,6 +138,18 @@ pub enum Delimiter {
     ParenthesisClose,
 }
 
+impl fmt::Display for Delimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Delimiter::Period => ".",
+            Delimiter::Comma => ",",
+            Delimiter::Semicolon => ";",
+            Delimiter::ParenthesisOpen => "(",
+            Delimiter::ParenthesisClose => ")",
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Operation {}
 
@@ -202,6 +244,8 @@ pub enum TypeDefinition {
     Float,
     String,
     Char,
+    /// Type added by a namespace, see [`crate::types`]
+    Custom(&'static str),
 }
 
 impl fmt::Display for TypeDefinition {
@@ -212,6 +256,49 @@ impl fmt::Display for TypeDefinition {
             TypeDefinition::Float => "Float",
             TypeDefinition::String => "String",
             TypeDefinition::Char => "Char",
+            TypeDefinition::Custom(name) => name,
+        })
+    }
+}
+
+/// Canonical spelling of the keyword, used by the formatter
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Keyword::Function(Function::ImportNamespace) => "использовать",
+            Keyword::Function(Function::Alg) => "алг",
+            Keyword::Function(Function::Start) => "нач",
+            Keyword::Function(Function::Stop) => "кон",
+            Keyword::Function(Function::Expects) => "дано",
+            Keyword::Function(Function::FunctionParamType(param_type)) => match param_type {
+                FunctionParamType::ResultParam => "рез",
+                FunctionParamType::ArgumentParam => "арг",
+                FunctionParamType::ArgumentResultParam => "аргрез",
+            },
+            Keyword::TypeDef(type_def) => match type_def {
+                TypeDefinition::Int => "цел",
+                TypeDefinition::Bool => "лог",
+                TypeDefinition::Float => "вещ",
+                TypeDefinition::String => "лит",
+                TypeDefinition::Char => "сим",
+                TypeDefinition::Custom(name) => name,
+            },
+            Keyword::Condition(Condition::If) => "если",
+            Keyword::Condition(Condition::EndCondition) => "все",
+            Keyword::Condition(Condition::Then) => "то",
+            Keyword::Condition(Condition::Else) => "иначе",
+            Keyword::Loop(Loop::Start) => "нц",
+            Keyword::Loop(Loop::End) => "кц",
+            Keyword::Loop(Loop::EndIf) => "кц при",
+            Keyword::Loop(Loop::While) => "пока",
+            Keyword::Loop(Loop::Break) => "выход",
+            Keyword::Loop(Loop::Times) => "раз",
+            Keyword::Range(Range::For) => "для",
+            Keyword::Range(Range::From) => "от",
+            Keyword::Range(Range::To) => "до",
+            Keyword::IO(IO::Input) => "ввод",
+            Keyword::IO(IO::ChangeLine) => "нс",
+            Keyword::IO(IO::Output) => "вывод",
         })
     }
 }
@@ -229,7 +316,7 @@ impl From<&str> for Keyword {
             "сим" => Keyword::TypeDef(TypeDefinition::Char),
             "лит" => Keyword::TypeDef(TypeDefinition::String),
             "если" => Keyword::Condition(Condition::If),
-            "все" => Keyword::Condition(Condition::EndCondition),
+            "все" | "всё" => Keyword::Condition(Condition::EndCondition),
             "то" => Keyword::Condition(Condition::Then),
             "иначе" => Keyword::Condition(Condition::Else),
             "нц" => Keyword::Loop(Loop::Start),
@@ -260,10 +347,97 @@ impl From<&str> for Keyword {
     }
 }
 
+pub const KEYWORDS: [&str; 29] = [
+    "алг",
+    "нач",
+    "кон",
+    "цел",
+    "вещ",
+    "лог",
+    "сим",
+    "лит",
+    "если",
+    "все",
+    "всё",
+    "то",
+    "иначе",
+    "нц",
+    "кц_при",
+    "кц",
+    "пока",
+    "для",
+    "от",
+    "до",
+    "ввод",
+    "вывод",
+    "нс",
+    "раз",
+    "арг",
+    "рез",
+    "аргрез",
+    "дано",
+    "использовать",
+];
+
+/// Line and column in the source, both start from 1
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self { line: 1, column: 1 }
+    }
+}
+
+/// Source range of a token or statement, `end` is exclusive
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    /// Text of the span in `source` by line, without the indentation
+    pub fn lines(&self, source: &str) -> Vec<String> {
+        let lines = source.lines().enumerate();
+        lines
+            .skip(self.start.line.saturating_sub(1))
+            .take((self.end.line + 1).saturating_sub(self.start.line))
+            .map(|(i, line)| {
+                let chars = line.chars();
+                let text: String = match i + 1 == self.end.line {
+                    true => chars.take(self.end.column.saturating_sub(1)).collect(),
+                    false => chars.collect(),
+                };
+                let text = match i + 1 == self.start.line {
+                    true => text
+                        .chars()
+                        .skip(self.start.column.saturating_sub(1))
+                        .collect(),
+                    false => text,
+                };
+                text.trim().to_string()
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.start.line, self.start.column)
+    }
+}
+
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     current_char: Option<char>,
+    keep_trivia: bool,
+    location: Position,
+    token_start: Position,
 }
 
 impl Lexer {
@@ -273,12 +447,30 @@ impl Lexer {
             input: chars,
             position: 0,
             current_char: None,
+            keep_trivia: false,
+            location: Position::default(),
+            token_start: Position::default(),
         };
         lexer.advance();
         lexer
     }
 
+    /// Lexer that emits comments as [`Token::Comment`] instead of discarding them
+    pub fn with_trivia(input: &str) -> Lexer {
+        let mut lexer = Lexer::new(input);
+        lexer.keep_trivia = true;
+        lexer
+    }
+
     pub fn advance(&mut self) {
+        match self.current_char {
+            Some('\n') => {
+                self.location.line += 1;
+                self.location.column = 1;
+            }
+            Some(_) => self.location.column += 1,
+            None => {}
+        }
         self.current_char = if self.position < self.input.len() {
             Some(self.input[self.position])
         } else {
@@ -288,7 +480,10 @@ impl Lexer {
     }
 
     pub fn skip_whitespace(&mut self) {
-        while self.current_char.is_some_and(|c| c.is_whitespace()) {
+        while self
+            .current_char
+            .is_some_and(|c| c.is_whitespace() && c != '\n')
+        {
             self.advance();
         }
     }
@@ -296,21 +491,54 @@ impl Lexer {
     pub fn skip_string(&mut self) {
         while let Some(c) = self.current_char {
             if c == '\n' {
-                // Предполагаем, что комментарий заканчивается на новой строке
-                self.advance();
+                // Предполагаем, что комментарий заканчивается на новой строке,
+                // сам перевод строки остаётся токеном NewLine
                 break;
             }
             self.advance();
         }
     }
 
-    pub fn next_token(&mut self) -> Result<Token, String> {
+    pub fn collect_comment(&mut self) -> String {
+        //Skip "|"
+        self.advance();
+        let mut comment = String::new();
+        while let Some(c) = self.current_char
+            && c != '\n'
+        {
+            comment.push(c);
+            self.advance();
+        }
+        comment.trim_end().to_string()
+    }
+
+    /// Next token together with its position in the source
+    pub fn next_spanned_token(&mut self) -> Result<(Token, Span), Error> {
+        let token = self.next_token()?;
+        Ok((token, self.span()))
+    }
+
+    /// Span of the last token returned by [`Lexer::next_token`]
+    pub fn span(&self) -> Span {
+        Span {
+            start: self.token_start,
+            end: self.location,
+        }
+    }
+
+    pub fn next_token(&mut self) -> Result<Token, Error> {
         self.skip_whitespace();
+        self.token_start = self.location;
 
         match self.current_char {
             None => Ok(Token::Eof),
             Some(c) => {
-                if '|' == c {
+                if '\n' == c {
+                    self.advance();
+                    Ok(Token::NewLine)
+                } else if '|' == c && self.keep_trivia {
+                    Ok(Token::Comment(self.collect_comment()))
+                } else if '|' == c {
                     self.skip_string();
                     self.next_token()
                 } else if c.is_alphabetic() || c == '_' {
@@ -330,7 +558,7 @@ impl Lexer {
                         ',' => Delimiter::Comma,
                         ';' => {
                             self.advance();
-                            return self.next_token();
+                            return Ok(Token::NewLine);
                         }
                         '(' => Delimiter::ParenthesisOpen,
                         ')' => Delimiter::ParenthesisClose,
@@ -348,13 +576,13 @@ impl Lexer {
                     self.advance();
                     char_token
                 } else {
-                    Err(format!("Неизвестный символ: {c}"))
+                    Err(Message::UnknownSymbol(c).into())
                 }
             }
         }
     }
 
-    pub fn collect_operator(&mut self, c: &char) -> Result<Token, String> {
+    pub fn collect_operator(&mut self, c: &char) -> Result<Token, Error> {
         let next = self.input.get(self.position);
         info!(
             "Collecting operator: {:?}, {:?}, {:?}",
@@ -392,17 +620,17 @@ impl Lexer {
             ('-', _) => Ok(Token::Operator(Operator::Minus)),
             ('*', _) => Ok(Token::Operator(Operator::Multiply)),
             ('/', _) => Ok(Token::Operator(Operator::Divide)),
-            (_, _) => Err(format!("Couldn't collect operator from: {c:?}, {next:?}")),
+            (_, _) => Err(Message::UnknownOperator(format!("{c:?}, {next:?}")).into()),
         }
     }
 
-    pub fn collect_char(&mut self) -> Result<Token, String> {
+    pub fn collect_char(&mut self) -> Result<Token, Error> {
         let char = self.current_char.unwrap();
         self.advance();
         if self.current_char.unwrap() == '\'' {
             Ok(Token::Char(char))
         } else {
-            Err("символ не может быть длиннее 1".to_string())
+            Err(Message::CharTooLong.into())
         }
     }
 
@@ -416,7 +644,9 @@ impl Lexer {
         str
     }
 
-    pub fn collect_word(&mut self) -> Result<Token, String> {
+    /// Collects a single word. Words are never glued into multi-word names
+    /// here, that's done by [`crate::resolver::resolve_names`]
+    pub fn collect_word(&mut self) -> Result<Token, Error> {
         let mut word = String::new();
         while self
             .current_char
@@ -424,59 +654,14 @@ impl Lexer {
         {
             word.push(self.current_char.unwrap());
             self.advance();
-            if [
-                "алг",
-                "нач",
-                "кон",
-                "цел",
-                "вещ",
-                "лог",
-                "сим",
-                "лит",
-                "если",
-                "все",
-                "то",
-                "иначе",
-                "нц",
-                "кц_при",
-                "кц",
-                "пока",
-                "для",
-                "от",
-                "до",
-                "ввод",
-                "вывод",
-                "нс",
-                "раз",
-                "арг",
-                "рез",
-                "аргрез",
-                "дано",
-                "использовать",
-            ]
-            .contains(&word.as_str())
-            {
-                return Ok(Token::Keyword(Keyword::from(word.as_str())));
-            } else if ["да", "нет"].contains(&word.as_str()) {
-                match word.as_str() {
-                    "да" => return Ok(Token::Bool(true)),
-                    "нет" => return Ok(Token::Bool(false)),
-                    _ => return Err("Ошибка".to_string()),
-                }
-            } else if word.as_str() == "надо" {
-                return self.next_token();
-            }
-            if let Some(char) = self.current_char
-                && char == ' '
-                    && self.input.get(self.position).is_some_and(|c| {
-                        (c.is_alphanumeric() && !['(', ')'].contains(c)) || *c == '_'
-                    })
-                {
-                    word.push(' ');
-                    self.advance();
-                }
         }
-        Ok(Token::Identifier(word))
+        match word.as_str() {
+            "да" => Ok(Token::Bool(true)),
+            "нет" => Ok(Token::Bool(false)),
+            "надо" => self.next_token(),
+            word if KEYWORDS.contains(&word) => Ok(Token::Keyword(Keyword::from(word))),
+            _ => Ok(Token::Identifier(word)),
+        }
     }
 
     pub fn collect_number(&mut self) -> Number {
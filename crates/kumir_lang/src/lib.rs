@@ -1,5 +1,31 @@
+//Lets `#[kumir_alg]` name this crate from inside it too
+extern crate self as kumir_lang;
+
 pub mod ast;
 pub mod backend;
+pub mod bind;
+pub mod clock;
+pub mod coverage;
+pub mod debugger;
+pub mod encoding;
+pub mod error;
+pub mod formatter;
+pub mod flowchart;
+pub mod fs;
+pub mod inspector;
 pub mod interpreter;
+pub mod io;
 pub mod lexer;
+pub mod limits;
+pub mod messages;
 pub mod parser;
+pub mod profiler;
+pub mod random;
+pub mod resolver;
+pub mod snapshot;
+pub mod stdlib;
+pub mod trace;
+pub mod trace_table;
+pub mod types;
+
+pub use kumir_lang_macros::kumir_alg;
@@ -0,0 +1,233 @@
+use std::{cell::RefCell, time::Duration};
+
+use web_time::Instant;
+
+use crate::{
+    ast::Literal,
+    messages::{Error, Message},
+};
+
+/// Resources a program may use, `None` means unlimited
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ExecutionLimits {
+    /// Executed statements, in Kumir loop passes too
+    pub max_steps: Option<u64>,
+    /// Time of the run, a `ждать` past it fails once the time is up
+    pub max_duration: Option<Duration>,
+    /// Nested algorithm or function calls
+    pub max_call_depth: Option<usize>,
+    /// Characters in a string, elements in a table or a collection
+    pub max_value_len: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Limit {
+    Steps(u64),
+    Duration(Duration),
+    CallDepth(usize),
+    ValueLen(usize),
+}
+
+/// What a running program has used of its [`ExecutionLimits`]
+#[derive(Debug, Clone)]
+pub struct Budget {
+    pub limits: ExecutionLimits,
+    steps: u64,
+    started: Instant,
+    exceeded: Option<Limit>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl Budget {
+    pub fn new(limits: ExecutionLimits) -> Self {
+        Self {
+            limits,
+            steps: 0,
+            started: Instant::now(),
+            exceeded: None,
+        }
+    }
+
+    /// Starts counting from zero
+    pub fn start(&mut self) {
+        *self = Self::new(self.limits);
+    }
+
+    /// First limit the program ran into
+    pub fn exceeded(&self) -> Option<Limit> {
+        self.exceeded
+    }
+
+    /// Counts one step, checks the time too
+    pub fn step(&mut self) -> Result<(), Error> {
+        self.steps += 1;
+        if let Some(max_steps) = self.limits.max_steps
+            && self.steps > max_steps
+        {
+            return self.exceed(Limit::Steps(max_steps));
+        }
+        self.check_time()
+    }
+
+    pub fn check_time(&mut self) -> Result<(), Error> {
+        match self.limits.max_duration {
+            Some(max_duration) if self.started.elapsed() > max_duration => {
+                self.exceed(Limit::Duration(max_duration))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Time the program may still run, `None` when it's unlimited
+    pub fn time_left(&self) -> Option<Duration> {
+        self.limits
+            .max_duration
+            .map(|max_duration| max_duration.saturating_sub(self.started.elapsed()))
+    }
+
+    /// Waits as much of `duration` as the time left allows with `sleep`,
+    /// fails when that's not all of it
+    pub fn wait(
+        budget: &RefCell<Budget>,
+        duration: Duration,
+        sleep: impl FnOnce(Duration),
+    ) -> Result<(), Error> {
+        let left = budget.borrow().time_left();
+        match left {
+            Some(left) if left < duration => {
+                sleep(left);
+                let mut budget = budget.borrow_mut();
+                let max_duration = budget.limits.max_duration.unwrap_or_default();
+                budget.exceed(Limit::Duration(max_duration))
+            }
+            _ => {
+                sleep(duration);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn check_call_depth(&mut self, depth: usize) -> Result<(), Error> {
+        match self.limits.max_call_depth {
+            Some(max_call_depth) if depth > max_call_depth => {
+                self.exceed(Limit::CallDepth(max_call_depth))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_len(&mut self, len: usize) -> Result<(), Error> {
+        match self.limits.max_value_len {
+            Some(max_value_len) if len > max_value_len => {
+                self.exceed(Limit::ValueLen(max_value_len))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_value(&mut self, value: &Literal) -> Result<(), Error> {
+        match value {
+            Literal::String(value) => self.check_len(value.chars().count()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Marks `limit` as exceeded, the program fails with the returned error
+    pub fn exceed(&mut self, limit: Limit) -> Result<(), Error> {
+        let limit = *self.exceeded.get_or_insert(limit);
+        Err(Message::LimitExceeded(limit).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::interpreter;
+
+    fn run(source: &str, input: &[&str], limits: ExecutionLimits) -> Result<(), Error> {
+        let (mut interpreter, _) = interpreter(source, input);
+        interpreter.set_limits(limits);
+        interpreter.run().map_err(|err| err.message)
+    }
+
+    fn value_len(source: &str, input: &[&str]) -> Result<(), Error> {
+        let limits = ExecutionLimits {
+            max_value_len: Some(3),
+            ..Default::default()
+        };
+        run(source, input, limits)
+    }
+
+    fn too_long() -> Result<(), Error> {
+        Err(Message::LimitExceeded(Limit::ValueLen(3)).into())
+    }
+
+    #[test]
+    fn steps_are_limited() {
+        let limits = ExecutionLimits {
+            max_steps: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(
+            run("нц пока да\nкц\n", &[], limits),
+            Err(Message::LimitExceeded(Limit::Steps(100)).into())
+        );
+    }
+
+    #[test]
+    fn waits_are_limited() {
+        let limits = ExecutionLimits {
+            max_duration: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        assert_eq!(
+            run("ждать(100000000)\n", &[], limits),
+            Err(Message::LimitExceeded(Limit::Duration(Duration::from_millis(100))).into())
+        );
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(run("ждать(10)\n", &[], limits), Ok(()));
+    }
+
+    #[test]
+    fn call_depth_is_limited() {
+        let limits = ExecutionLimits {
+            max_call_depth: Some(10),
+            ..Default::default()
+        };
+        let source = "глубже\nалг глубже\nнач\nглубже\nкон\n";
+        assert_eq!(
+            run(source, &[], limits),
+            Err(Message::LimitExceeded(Limit::CallDepth(10)).into())
+        );
+    }
+
+    #[test]
+    fn assigned_values_are_limited() {
+        assert_eq!(value_len("лит с\nс := \"абвг\"\n", &[]), too_long());
+        assert_eq!(value_len("лит с\nс := \"абв\"\n", &[]), Ok(()));
+    }
+
+    #[test]
+    fn initial_values_are_limited() {
+        assert_eq!(value_len("лит с = \"абвг\"\n", &[]), too_long());
+        assert_eq!(value_len("лит с = \"абв\"\n", &[]), Ok(()));
+    }
+
+    #[test]
+    fn input_is_limited() {
+        assert_eq!(value_len("лит с\nввод с\n", &["абвг"]), too_long());
+        assert_eq!(value_len("лит с\nввод с\n", &["абв"]), Ok(()));
+    }
+
+    #[test]
+    fn returned_values_are_limited() {
+        assert_eq!(value_len("вывод цел_в_лит(1234)\n", &[]), too_long());
+        assert_eq!(value_len("вывод цел_в_лит(123)\n", &[]), Ok(()));
+    }
+}
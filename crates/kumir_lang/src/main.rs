@@ -1,14 +1,16 @@
-use std::{cell::RefCell, rc::Rc};
-
-use indexmap::IndexMap;
 use kumir_lang::{
-    ast::{Environment, FunctionParameter, Literal, NativeFunction},
     interpreter::Interpreter,
-    lexer::{self, Lexer, Token, TypeDefinition},
+    kumir_alg,
+    lexer::{Lexer, Token},
     parser::Parser,
 };
 use log::info;
 
+#[kumir_alg("раст")]
+fn stretch(number: f32) -> f32 {
+    number * 40.0
+}
+
 fn main() {
     env_logger::init();
     let code = include_str!("test3.kum");
@@ -37,20 +39,12 @@ fn main() {
         Ok(ast) => {
             info!("AST generated: {ast:#?}");
             let mut interpreter = Interpreter::new(ast, Default::default());
-            interpreter.register_native_function(
-                "раст",
-                NativeFunction {
-                    params: {
-                        let mut params = IndexMap::new();
-                        params.insert("число".to_string(), FunctionParameter { type_definition: TypeDefinition::Float, result_type: lexer::FunctionParamType::ArgumentParam });
-                        params
-                    },
-                    return_type: Some(TypeDefinition::Float),
-                    native_function: Rc::new(RefCell::new(|environment: &Rc<RefCell<Environment>>| -> Result<Option<kumir_lang::ast::Literal>, String> {let num = environment.borrow().get_value("число").unwrap(); if let Literal::Float(num) = num {return Ok(Some(Literal::Float(num*40.0)))} Err("Err".to_string())})),
-                },
-            );
+            let (name, function) = stretch_alg();
+            interpreter.register_native_function(name, function);
 
-            info!("Interpreter result: {:#?}", interpreter.run());
+            if let Err(error) = interpreter.run() {
+                eprintln!("{error}");
+            }
 
             info!("Interpreter environment: {:#?}", interpreter.environment);
         }
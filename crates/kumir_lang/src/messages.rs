@@ -0,0 +1,705 @@
+//! Catalogue of error messages. Every error has a stable code, e.g. `R009`,
+//! and a Russian and an English text:
+//!
+//! ```text
+//! [R009] Деление на ноль
+//! ```
+//!
+//! Codes start with `L` for the lexer, `P` for the parser, `R` for the
+//! interpreter, `F` for the formatter and `A` for actors. Errors are passed
+//! around as [`Error`], which keeps the [`Message`], so whoever shows an
+//! error picks the language with [`Error::text`]: the IDE may show it in
+//! Russian while the log gets it in English.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use crate::{
+    lexer::{Keyword, TypeDefinition},
+    limits::Limit,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Locale {
+    #[default]
+    Ru,
+    En,
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Locale::Ru => write!(f, "Русский"),
+            Locale::En => write!(f, "English"),
+        }
+    }
+}
+
+/// `ru` or `en`
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ru" => Ok(Locale::Ru),
+            "en" => Ok(Locale::En),
+            _ => Err(format!("Unknown locale {s:?}, expected ru or en")),
+        }
+    }
+}
+
+/// Error a program fails with: a [`Message`] of the catalogue or a text of
+/// the host, which has no code
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    Message(Message),
+    Text(String),
+}
+
+impl Error {
+    /// Stable code, `None` for a text of the host
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            Error::Message(message) => Some(message.code()),
+            Error::Text(_) => None,
+        }
+    }
+
+    /// Code and text in `locale`, a text of the host as it is
+    pub fn text(&self, locale: Locale) -> String {
+        match self {
+            Error::Message(message) => format!("[{}] {}", message.code(), message.text(locale)),
+            Error::Text(text) => text.clone(),
+        }
+    }
+}
+
+impl From<Message> for Error {
+    fn from(message: Message) -> Self {
+        Error::Message(message)
+    }
+}
+
+impl From<String> for Error {
+    fn from(text: String) -> Self {
+        Error::Text(text)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(text: &str) -> Self {
+        Error::Text(text.to_string())
+    }
+}
+
+/// In Russian, as Kumir reports errors
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text(Locale::Ru))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Message {
+    UnknownSymbol(char),
+    UnknownOperator(String),
+    CharTooLong,
+
+    UnexpectedToken(String),
+    ExpectedNamespaceName(String),
+    NewLineIsNotStatement,
+    ExpectedInputName(String),
+    ExpectedIdentifier,
+    ParameterTypeRequired,
+    InvalidVarsSequence,
+    UnexpectedTokenInExpression(String),
+    ExpectedToken {
+        expected: String,
+        found: String,
+    },
+
+    NotBoolean(String),
+    NotIntegerInLoop(String),
+    UndefinedFunction(String),
+    ArgsMismatch {
+        args: String,
+        params: String,
+    },
+    ResultNotAssigned(String),
+    UndefinedVariable(String),
+    ValueNotAssigned,
+    UserInterrupt,
+    DivisionByZero,
+    ProcedureAsFunction,
+    NewLineIsNotValue,
+    InvalidOperation(String),
+    TypeMismatch {
+        expected: TypeDefinition,
+        received: TypeDefinition,
+    },
+    NamespaceNotFound(String),
+    InputIsOver,
+    InvalidInput {
+        type_def: TypeDefinition,
+        word: String,
+    },
+    LimitExceeded(Limit),
+    NotRunning,
+    SqrtOfNegative,
+    LogOfNonPositive,
+    OutOfRange(String),
+    IntOverflow,
+    FloatOverflow,
+    ResultArgNotVariable(String),
+    PositionOutOfRange {
+        position: i32,
+        len: usize,
+    },
+    CharCodeOutOfRange(i32),
+    NotInCp1251(char),
+    ActorNotImported {
+        name: String,
+        actor: String,
+    },
+    AmbiguousCommand {
+        name: String,
+        actors: Vec<String>,
+    },
+    InvalidSnapshot(String),
+    SnapshotMismatch,
+    ActorsNotReset,
+
+    FormattingChangesMeaning,
+
+    RobotWallRight,
+    RobotWallLeft,
+    RobotWallAbove,
+    RobotWallBelow,
+
+    CannotOpenFile {
+        name: String,
+        reason: String,
+    },
+    FileAlreadyOpen(String),
+    FileNotOpen,
+    FileNotReadable(String),
+    FileNotWritable(String),
+    FilesLeftOpen(Vec<String>),
+    CloseConsole,
+    UnknownEncoding(String),
+    OutsideSandbox(String),
+}
+
+impl Message {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Message::UnknownSymbol(_) => "L001",
+            Message::UnknownOperator(_) => "L002",
+            Message::CharTooLong => "L003",
+
+            Message::UnexpectedToken(_) => "P001",
+            Message::ExpectedNamespaceName(_) => "P002",
+            Message::NewLineIsNotStatement => "P003",
+            Message::ExpectedInputName(_) => "P004",
+            Message::ExpectedIdentifier => "P005",
+            Message::ParameterTypeRequired => "P006",
+            Message::InvalidVarsSequence => "P007",
+            Message::UnexpectedTokenInExpression(_) => "P008",
+            Message::ExpectedToken { .. } => "P009",
+
+            Message::NotBoolean(_) => "R001",
+            Message::NotIntegerInLoop(_) => "R002",
+            Message::UndefinedFunction(_) => "R003",
+            Message::ArgsMismatch { .. } => "R004",
+            Message::ResultNotAssigned(_) => "R005",
+            Message::UndefinedVariable(_) => "R006",
+            Message::ValueNotAssigned => "R007",
+            Message::UserInterrupt => "R008",
+            Message::DivisionByZero => "R009",
+            Message::ProcedureAsFunction => "R010",
+            Message::NewLineIsNotValue => "R011",
+            Message::InvalidOperation(_) => "R012",
+            Message::TypeMismatch { .. } => "R013",
+            Message::NamespaceNotFound(_) => "R014",
+            Message::InputIsOver => "R015",
+            Message::InvalidInput { .. } => "R016",
+            Message::LimitExceeded(_) => "R017",
+            Message::NotRunning => "R018",
+            Message::SqrtOfNegative => "R019",
+            Message::LogOfNonPositive => "R020",
+            Message::OutOfRange(_) => "R021",
+            Message::IntOverflow => "R022",
+            Message::FloatOverflow => "R023",
+            Message::ResultArgNotVariable(_) => "R024",
+            Message::PositionOutOfRange { .. } => "R025",
+            Message::CharCodeOutOfRange(_) => "R026",
+            Message::NotInCp1251(_) => "R027",
+            Message::ActorNotImported { .. } => "R028",
+            Message::AmbiguousCommand { .. } => "R029",
+            Message::InvalidSnapshot(_) => "R030",
+            Message::SnapshotMismatch => "R031",
+            Message::ActorsNotReset => "R032",
+
+            Message::FormattingChangesMeaning => "F001",
+
+            Message::RobotWallRight => "A001",
+            Message::RobotWallLeft => "A002",
+            Message::RobotWallAbove => "A003",
+            Message::RobotWallBelow => "A004",
+
+            Message::CannotOpenFile { .. } => "A005",
+            Message::FileAlreadyOpen(_) => "A006",
+            Message::FileNotOpen => "A007",
+            Message::FileNotReadable(_) => "A008",
+            Message::FileNotWritable(_) => "A009",
+            Message::FilesLeftOpen(_) => "A010",
+            Message::CloseConsole => "A011",
+            Message::UnknownEncoding(_) => "A012",
+            Message::OutsideSandbox(_) => "A013",
+        }
+    }
+
+    /// Text without the code
+    pub fn text(&self, locale: Locale) -> String {
+        match locale {
+            Locale::Ru => self.ru(),
+            Locale::En => self.en(),
+        }
+    }
+
+    fn ru(&self) -> String {
+        match self {
+            Message::UnknownSymbol(c) => format!("Неизвестный символ: {c}"),
+            Message::UnknownOperator(text) => format!("Неизвестная операция: {text}"),
+            Message::CharTooLong => "символ не может быть длиннее 1".to_string(),
+
+            Message::UnexpectedToken(token) => format!("Неожиданная лексема: {token}"),
+            Message::ExpectedNamespaceName(token) => {
+                format!("После «использовать» ожидалось имя исполнителя, найдено {token}")
+            }
+            Message::NewLineIsNotStatement => "«нс» не может быть командой".to_string(),
+            Message::ExpectedInputName(token) => {
+                format!("Ожидалось имя величины для ввода, найдено {token}")
+            }
+            Message::ExpectedIdentifier => "Ожидалось имя".to_string(),
+            Message::ParameterTypeRequired => "У параметра алгоритма должен быть тип".to_string(),
+            Message::InvalidVarsSequence => "Неверное описание величин".to_string(),
+            Message::UnexpectedTokenInExpression(token) => {
+                format!("Неожиданная лексема в выражении: {token}")
+            }
+            Message::ExpectedToken { expected, found } => {
+                format!("Ожидалось {expected}, найдено {found}")
+            }
+
+            Message::NotBoolean(value) => format!("{value} должно быть логическим значением"),
+            Message::NotIntegerInLoop(value) => {
+                format!("{value} в цикле должно быть целым значением")
+            }
+            Message::UndefinedFunction(name) => format!("Алгоритм «{name}» не определён"),
+            Message::ArgsMismatch { args, params } => {
+                format!("Аргументы не подходят к параметрам: аргументы {args}, параметры {params}")
+            }
+            Message::ResultNotAssigned(name) => {
+                format!("Результату «{name}» не присвоено значение")
+            }
+            Message::UndefinedVariable(name) => format!("Величина «{name}» не описана"),
+            Message::ValueNotAssigned => "Значению функции не присвоено значение".to_string(),
+            Message::UserInterrupt => "Выполнение прервано".to_string(),
+            Message::DivisionByZero => "Деление на ноль".to_string(),
+            Message::ProcedureAsFunction => "Алгоритм не возвращает значения".to_string(),
+            Message::NewLineIsNotValue => "«нс» не может быть значением".to_string(),
+            Message::InvalidOperation(operation) => format!("Недопустимая операция: {operation}"),
+            Message::TypeMismatch { expected, received } => format!(
+                "Несовпадение типов при присваивании: ожидался {}, получен {}",
+                Keyword::TypeDef(*expected),
+                Keyword::TypeDef(*received)
+            ),
+            Message::NamespaceNotFound(name) => format!("Исполнитель «{name}» не найден"),
+            Message::InputIsOver => "Ввод закончился".to_string(),
+            Message::InvalidInput { type_def, word } => format!(
+                "Не удалось прочитать значение типа {} из ввода: {word:?}",
+                Keyword::TypeDef(*type_def)
+            ),
+            Message::LimitExceeded(limit) => format!(
+                "Превышено ограничение: {}",
+                match limit {
+                    Limit::Steps(steps) => format!("больше {steps} шагов"),
+                    Limit::Duration(duration) => format!("дольше {}", seconds(duration)),
+                    Limit::CallDepth(depth) => format!("вложенность вызовов больше {depth}"),
+                    Limit::ValueLen(len) => format!("значение длиннее {len}"),
+                }
+            ),
+            Message::NotRunning => "Программа не выполняется".to_string(),
+            Message::SqrtOfNegative => "Корень из отрицательного числа".to_string(),
+            Message::LogOfNonPositive => "Логарифм неположительного числа".to_string(),
+            Message::OutOfRange(name) => format!("Аргумент {name} должен быть от -1 до 1"),
+            Message::IntOverflow => "Целочисленное переполнение".to_string(),
+            Message::FloatOverflow => "Вещественное переполнение".to_string(),
+            Message::ResultArgNotVariable(name) => {
+                format!("Результат «{name}» можно записать только в величину")
+            }
+            Message::PositionOutOfRange { position, len } => {
+                format!("Позиция {position} вне строки длины {len}")
+            }
+            Message::CharCodeOutOfRange(code) => format!("Нет символа с кодом {code}"),
+            Message::NotInCp1251(char) => format!("Символа «{char}» нет в кодировке CP-1251"),
+            Message::ActorNotImported { name, actor } => format!(
+                "«{name}» — команда исполнителя «{actor}», добавьте в начало программы «использовать {actor}»"
+            ),
+            Message::AmbiguousCommand { name, actors } => format!(
+                "Команда «{name}» есть у нескольких исполнителей: {}",
+                actors.join(", ")
+            ),
+            Message::InvalidSnapshot(line) => format!("Снимок программы повреждён: «{line}»"),
+            Message::SnapshotMismatch => {
+                "Программа разошлась со снимком, восстановить её не удалось".to_string()
+            }
+            Message::ActorsNotReset => {
+                "Исполнители уже выполняли команды, верните их в начальное состояние перед восстановлением".to_string()
+            }
+
+            Message::FormattingChangesMeaning => {
+                "Форматирование изменило бы смысл программы".to_string()
+            }
+
+            Message::RobotWallRight => "Робот разбился: справа стена".to_string(),
+            Message::RobotWallLeft => "Робот разбился: слева стена".to_string(),
+            Message::RobotWallAbove => "Робот разбился: сверху стена".to_string(),
+            Message::RobotWallBelow => "Робот разбился: снизу стена".to_string(),
+
+            Message::CannotOpenFile { name, reason } => {
+                format!("Не удалось открыть файл «{name}»: {reason}")
+            }
+            Message::FileAlreadyOpen(name) => format!("Файл «{name}» уже открыт"),
+            Message::FileNotOpen => "Файл не открыт".to_string(),
+            Message::FileNotReadable(name) => format!("Файл «{name}» открыт не на чтение"),
+            Message::FileNotWritable(name) => format!("Файл «{name}» открыт не на запись"),
+            Message::FilesLeftOpen(names) => format!("Не закрыты файлы: {}", names.join(", ")),
+            Message::CloseConsole => "Консоль нельзя закрыть".to_string(),
+            Message::UnknownEncoding(name) => format!("Неизвестная кодировка «{name}»"),
+            Message::OutsideSandbox(name) => {
+                format!("Путь «{name}» ведёт за пределы корневого каталога")
+            }
+        }
+    }
+
+    fn en(&self) -> String {
+        match self {
+            Message::UnknownSymbol(c) => format!("Unknown symbol: {c}"),
+            Message::UnknownOperator(text) => format!("Couldn't collect operator from: {text}"),
+            Message::CharTooLong => "A character can't be longer than 1".to_string(),
+
+            Message::UnexpectedToken(token) => format!("Unexpected token: {token}"),
+            Message::ExpectedNamespaceName(token) => {
+                format!("Error parsing import namespace expected identifier found {token}")
+            }
+            Message::NewLineIsNotStatement => "Change line couldn't be Statement".to_string(),
+            Message::ExpectedInputName(token) => {
+                format!("Expected variable name to input, found {token}")
+            }
+            Message::ExpectedIdentifier => "Expected identifier".to_string(),
+            Message::ParameterTypeRequired => "Typedef for function parameter required".to_string(),
+            Message::InvalidVarsSequence => "Couldn't construct a vars sequence".to_string(),
+            Message::UnexpectedTokenInExpression(token) => {
+                format!("Unexpected token in expression: {token}")
+            }
+            Message::ExpectedToken { expected, found } => {
+                format!("Expected {expected}, found {found}")
+            }
+
+            Message::NotBoolean(value) => format!("{value} must be a boolean value"),
+            Message::NotIntegerInLoop(value) => format!("{value} must be an integer value in loop"),
+            Message::UndefinedFunction(name) => {
+                format!("Couldn't call undefined function with name {name}")
+            }
+            Message::ArgsMismatch { args, params } => {
+                format!("params and args mismatch args: {args}, params: {params}")
+            }
+            Message::ResultNotAssigned(name) => {
+                format!("Result parameter '{name}' is not assigned")
+            }
+            Message::UndefinedVariable(name) => format!("Undefined variable: {name}"),
+            Message::ValueNotAssigned => "Value of alg func is nothing".to_string(),
+            Message::UserInterrupt => "User interrupt".to_string(),
+            Message::DivisionByZero => "Division by zero".to_string(),
+            Message::ProcedureAsFunction => "This alg is procedure not function".to_string(),
+            Message::NewLineIsNotValue => "New line couldn't be Literal".to_string(),
+            Message::InvalidOperation(operation) => format!("Invalid operation: {operation}"),
+            Message::TypeMismatch { expected, received } => format!(
+                "Type mismatch on assignment, type expected: {expected}, type received: {received}"
+            ),
+            Message::NamespaceNotFound(name) => format!("Namespace with name: {name:?} not found"),
+            Message::InputIsOver => "Input is over".to_string(),
+            Message::InvalidInput { type_def, word } => {
+                format!("Couldn't read value of type {type_def} from input: {word:?}")
+            }
+            Message::LimitExceeded(limit) => format!(
+                "Limit exceeded: {}",
+                match limit {
+                    Limit::Steps(steps) => format!("more than {steps} steps"),
+                    Limit::Duration(duration) => format!("ran longer than {duration:?}"),
+                    Limit::CallDepth(depth) => format!("calls nested deeper than {depth}"),
+                    Limit::ValueLen(len) => format!("value longer than {len}"),
+                }
+            ),
+            Message::NotRunning => "Program is not running".to_string(),
+            Message::SqrtOfNegative => "Square root of a negative number".to_string(),
+            Message::LogOfNonPositive => "Logarithm of a non-positive number".to_string(),
+            Message::OutOfRange(name) => format!("Argument of {name} must be between -1 and 1"),
+            Message::IntOverflow => "Integer overflow".to_string(),
+            Message::FloatOverflow => "Real overflow".to_string(),
+            Message::ResultArgNotVariable(name) => {
+                format!("Result {name:?} can only be written to a variable")
+            }
+            Message::PositionOutOfRange { position, len } => {
+                format!("Position {position} is outside a string of length {len}")
+            }
+            Message::CharCodeOutOfRange(code) => format!("No character with code {code}"),
+            Message::NotInCp1251(char) => format!("Character {char:?} is not in CP-1251"),
+            Message::ActorNotImported { name, actor } => format!(
+                "{name:?} is a command of the actor {actor:?}, add \"использовать {actor}\" at the start of the program"
+            ),
+            Message::AmbiguousCommand { name, actors } => format!(
+                "Command {name:?} belongs to several actors: {}",
+                actors.join(", ")
+            ),
+            Message::InvalidSnapshot(line) => {
+                format!("Snapshot of the program is damaged: {line:?}")
+            }
+            Message::SnapshotMismatch => {
+                "Program diverged from the snapshot and can't be restored".to_string()
+            }
+            Message::ActorsNotReset => {
+                "Actors already ran commands, reset them before restoring".to_string()
+            }
+
+            Message::FormattingChangesMeaning => {
+                "Formatting would change the meaning of the program".to_string()
+            }
+
+            Message::RobotWallRight => {
+                "Robot is destroyed: there is a wall on the right".to_string()
+            }
+            Message::RobotWallLeft => "Robot is destroyed: there is a wall on the left".to_string(),
+            Message::RobotWallAbove => "Robot is destroyed: there is a wall above it".to_string(),
+            Message::RobotWallBelow => "Robot is destroyed: there is a wall below it".to_string(),
+
+            Message::CannotOpenFile { name, reason } => {
+                format!("Couldn't open file {name:?}: {reason}")
+            }
+            Message::FileAlreadyOpen(name) => format!("File {name:?} is already open"),
+            Message::FileNotOpen => "File is not open".to_string(),
+            Message::FileNotReadable(name) => format!("File {name:?} is not open for reading"),
+            Message::FileNotWritable(name) => format!("File {name:?} is not open for writing"),
+            Message::FilesLeftOpen(names) => format!("Files left open: {}", names.join(", ")),
+            Message::CloseConsole => "The console can't be closed".to_string(),
+            Message::UnknownEncoding(name) => format!("Unknown encoding {name:?}"),
+            Message::OutsideSandbox(name) => format!("Path {name:?} leads out of the root"),
+        }
+    }
+}
+
+/// Code and text in Russian
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}", self.code(), self.text(Locale::Ru))
+    }
+}
+
+fn seconds(duration: &Duration) -> String {
+    format!("{} с", duration.as_secs_f64())
+}
+
+/// `Ошибка выполнения в строке 3`
+pub fn runtime_error_at(line: Option<usize>, locale: Locale) -> String {
+    match (locale, line) {
+        (Locale::Ru, Some(line)) => format!("Ошибка выполнения в строке {line}"),
+        (Locale::Ru, None) => "Ошибка выполнения".to_string(),
+        (Locale::En, Some(line)) => format!("Runtime error at line {line}"),
+        (Locale::En, None) => "Runtime error".to_string(),
+    }
+}
+
+/// `в алг делить(5, 0), вызван в строке 8`
+pub fn called_at(frame: &impl fmt::Display, line: usize, locale: Locale) -> String {
+    match locale {
+        Locale::Ru => format!("в алг {frame}, вызван в строке {line}"),
+        Locale::En => format!("in alg {frame}, called at line {line}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    /// One message of every kind, grouped by the series of its code
+    fn catalogue() -> Vec<(char, Vec<Message>)> {
+        let text = || "а".to_string();
+        vec![
+            (
+                'L',
+                vec![
+                    Message::UnknownSymbol('@'),
+                    Message::UnknownOperator(text()),
+                    Message::CharTooLong,
+                ],
+            ),
+            (
+                'P',
+                vec![
+                    Message::UnexpectedToken(text()),
+                    Message::ExpectedNamespaceName(text()),
+                    Message::NewLineIsNotStatement,
+                    Message::ExpectedInputName(text()),
+                    Message::ExpectedIdentifier,
+                    Message::ParameterTypeRequired,
+                    Message::InvalidVarsSequence,
+                    Message::UnexpectedTokenInExpression(text()),
+                    Message::ExpectedToken {
+                        expected: text(),
+                        found: text(),
+                    },
+                ],
+            ),
+            (
+                'R',
+                vec![
+                    Message::NotBoolean(text()),
+                    Message::NotIntegerInLoop(text()),
+                    Message::UndefinedFunction(text()),
+                    Message::ArgsMismatch {
+                        args: text(),
+                        params: text(),
+                    },
+                    Message::ResultNotAssigned(text()),
+                    Message::UndefinedVariable(text()),
+                    Message::ValueNotAssigned,
+                    Message::UserInterrupt,
+                    Message::DivisionByZero,
+                    Message::ProcedureAsFunction,
+                    Message::NewLineIsNotValue,
+                    Message::InvalidOperation(text()),
+                    Message::TypeMismatch {
+                        expected: TypeDefinition::Int,
+                        received: TypeDefinition::String,
+                    },
+                    Message::NamespaceNotFound(text()),
+                    Message::InputIsOver,
+                    Message::InvalidInput {
+                        type_def: TypeDefinition::Int,
+                        word: text(),
+                    },
+                    Message::LimitExceeded(Limit::Steps(10)),
+                    Message::LimitExceeded(Limit::Duration(Duration::from_secs(1))),
+                    Message::LimitExceeded(Limit::CallDepth(10)),
+                    Message::LimitExceeded(Limit::ValueLen(10)),
+                    Message::NotRunning,
+                    Message::SqrtOfNegative,
+                    Message::LogOfNonPositive,
+                    Message::OutOfRange(text()),
+                    Message::IntOverflow,
+                    Message::FloatOverflow,
+                    Message::ResultArgNotVariable(text()),
+                    Message::PositionOutOfRange {
+                        position: 5,
+                        len: 3,
+                    },
+                    Message::CharCodeOutOfRange(-1),
+                    Message::NotInCp1251('😀'),
+                    Message::ActorNotImported {
+                        name: "вверх".to_string(),
+                        actor: "Робот".to_string(),
+                    },
+                    Message::AmbiguousCommand {
+                        name: text(),
+                        actors: vec![text(), text()],
+                    },
+                    Message::InvalidSnapshot(text()),
+                    Message::SnapshotMismatch,
+                    Message::ActorsNotReset,
+                ],
+            ),
+            ('F', vec![Message::FormattingChangesMeaning]),
+            (
+                'A',
+                vec![
+                    Message::RobotWallRight,
+                    Message::RobotWallLeft,
+                    Message::RobotWallAbove,
+                    Message::RobotWallBelow,
+                    Message::CannotOpenFile {
+                        name: text(),
+                        reason: text(),
+                    },
+                    Message::FileAlreadyOpen(text()),
+                    Message::FileNotOpen,
+                    Message::FileNotReadable(text()),
+                    Message::FileNotWritable(text()),
+                    Message::FilesLeftOpen(vec![text()]),
+                    Message::CloseConsole,
+                    Message::UnknownEncoding(text()),
+                    Message::OutsideSandbox(text()),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn codes_are_unique_and_in_their_series() {
+        let mut codes = HashSet::new();
+        for (series, messages) in catalogue() {
+            let mut kinds = HashSet::new();
+            for message in messages {
+                let code = message.code();
+                assert!(
+                    code.starts_with(series),
+                    "{code} is not in the {series} series"
+                );
+                assert_eq!(code.len(), 4, "{code}");
+                //Limits share a code
+                if kinds.insert(std::mem::discriminant(&message)) {
+                    assert!(codes.insert(code), "{code} is used twice");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_message_is_in_both_languages() {
+        for message in catalogue().into_iter().flat_map(|(_, messages)| messages) {
+            let ru = message.text(Locale::Ru);
+            let en = message.text(Locale::En);
+            assert!(!ru.is_empty() && !en.is_empty(), "{message:?}");
+            assert_ne!(ru, en, "{message:?}");
+        }
+    }
+
+    #[test]
+    fn errors_are_rendered_by_their_reader() {
+        let error = Error::from(Message::DivisionByZero);
+        assert_eq!(error.code(), Some("R009"));
+        assert_eq!(error.text(Locale::Ru), "[R009] Деление на ноль");
+        assert_eq!(error.text(Locale::En), "[R009] Division by zero");
+        assert_eq!(error.to_string(), "[R009] Деление на ноль");
+
+        let error = Error::from("NameError: name 'x' is not defined");
+        assert_eq!(error.code(), None);
+        assert_eq!(error.text(Locale::En), error.text(Locale::Ru));
+    }
+
+    #[test]
+    fn lines_and_frames_are_in_the_locale() {
+        assert_eq!(
+            runtime_error_at(Some(3), Locale::Ru),
+            "Ошибка выполнения в строке 3"
+        );
+        assert_eq!(runtime_error_at(None, Locale::En), "Runtime error");
+        assert_eq!(
+            called_at(&"делить(5, 0)", 8, Locale::En),
+            "in alg делить(5, 0), called at line 8"
+        );
+        assert_eq!("en".parse(), Ok(Locale::En));
+        assert!("de".parse::<Locale>().is_err());
+    }
+}
@@ -1,39 +1,116 @@
-use crate::ast::*;
+use crate::{
+    ast::*,
+    messages::{Error, Message},
+    resolver::{KnownTypes, resolve_names},
+};
 
+use hashbrown::HashSet;
 use indexmap::IndexMap;
 use log::info;
 
 use crate::lexer::{
-    self, Delimiter, FunctionParamType, IO, Keyword, Operator, Range, Token, TypeDefinition,
+    self, Delimiter, FunctionParamType, IO, Keyword, Operator, Position, Range, Span, Token,
+    TypeDefinition,
 };
 
+/// Name of the algorithm written without one
+pub(crate) const MAIN: &str = "main";
+
+/// Statements of a loop and the condition of its `кц_при` with the span
+/// of it
+type LoopBody = (Vec<Statement>, Option<(Expr, Span)>);
+
 pub struct Parser {
     pub tokens: Vec<Token>,
+    pub spans: Vec<Span>,
     pub position: usize,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
+        let tokens = tokens
+            .into_iter()
+            .map(|token| (token, Span::default()))
+            .collect();
+        Self::with_names(tokens, &HashSet::new(), &KnownTypes::default())
+    }
+
+    /// Parser over tokens with their source spans that also treats
+    /// `known_names` (native algorithms, namespaces) as names when combining
+    /// words and `known_types` as types, see [`resolve_names`]
+    pub fn with_names(
+        tokens: Vec<(Token, Span)>,
+        known_names: &HashSet<String>,
+        known_types: &KnownTypes,
+    ) -> Self {
+        let (tokens, spans) = resolve_names(tokens, known_names, known_types)
+            .into_iter()
+            .filter(|(token, _)| !token.is_trivia())
+            .unzip();
         Parser {
             tokens,
+            spans,
             position: 0,
         }
     }
 
-    pub fn parse(&mut self) -> Result<AstNode, (Vec<Stmt>, String)> {
+    pub fn parse(&mut self) -> Result<AstNode, (Vec<Statement>, Error)> {
         let mut statements = Vec::new();
         while !self.is_eof() {
-            statements.push(match self.parse_stmt() {
+            statements.push(match self.parse_statement() {
                 Ok(stmt) => stmt,
                 Err(err) => {
                     return Err((statements, err));
                 }
             })
         }
+        //An algorithm without a name can't be called, it's the program itself
+        //and runs after the statements outside of algorithms
+        let main = statements
+            .iter()
+            .find_map(|statement| match &statement.stmt {
+                Stmt::Alg(alg) if alg.name == MAIN && alg.params.is_empty() => Some(statement.span),
+                _ => None,
+            });
+        if let Some(span) = main {
+            statements.push(Statement {
+                span,
+                stmt: Stmt::FunctionCall(FunctionCall {
+                    name: MAIN.to_string(),
+                    args: vec![],
+                }),
+            });
+        }
         Ok(AstNode::Program(statements))
     }
 
-    fn parse_stmt(&mut self) -> Result<Stmt, String> {
+    fn parse_statement(&mut self) -> Result<Statement, Error> {
+        let start = self.current_span().start;
+        let stmt = self.parse_stmt()?;
+        Ok(Statement {
+            span: self.span_from(start),
+            stmt,
+        })
+    }
+
+    /// Expression of a condition with its span
+    fn parse_condition_expr(&mut self) -> Result<(Expr, Span), Error> {
+        let start = self.current_span().start;
+        let condition = self.parse_expr()?;
+        Ok((condition, self.span_from(start)))
+    }
+
+    /// From `start` to the end of the last token parsed
+    fn span_from(&self, start: Position) -> Span {
+        let end = self
+            .position
+            .checked_sub(1)
+            .and_then(|position| self.spans.get(position))
+            .map_or(start, |span| span.end);
+        Span { start, end }
+    }
+
+    fn parse_stmt(&mut self) -> Result<Stmt, Error> {
         match self.current_token().clone() {
             Token::Keyword(Keyword::Function(lexer::Function::Alg)) => self.parse_alg(),
             Token::Keyword(Keyword::Function(lexer::Function::ImportNamespace)) => {
@@ -51,30 +128,33 @@ impl Parser {
                 }
             }
             Token::Keyword(Keyword::IO(io_keyword)) => self.parse_io(io_keyword),
-            _ => Err(format!("Unexpected token: {:?}", self.current_token())),
+            _ => Err(Message::UnexpectedToken(format!("{:?}", self.current_token())).into()),
         }
     }
 
-    fn parse_import_namespace(&mut self) -> Result<Stmt, String> {
+    fn parse_import_namespace(&mut self) -> Result<Stmt, Error> {
         //Skip import namespace token
         self.advance();
-        let name = self.current_token().identifier().ok_or(format!(
-            "Error parsing import namespace expected identifier found {:?}",
-            self.current_token()
-        ))?;
+        let name = self
+            .current_token()
+            .identifier()
+            .ok_or(Message::ExpectedNamespaceName(format!(
+                "{:?}",
+                self.current_token()
+            )))?;
         self.advance();
         Ok(Stmt::ImportNamespace(ImportNamespace { name }))
     }
 
-    fn parse_function_call_stmt(&mut self, name: &str) -> Result<Stmt, String> {
+    fn parse_function_call_stmt(&mut self, name: &str) -> Result<Stmt, Error> {
         Ok(Stmt::FunctionCall(self.parse_function_call(name)?))
     }
 
-    fn parse_function_call_expr(&mut self, name: &str) -> Result<Expr, String> {
+    fn parse_function_call_expr(&mut self, name: &str) -> Result<Expr, Error> {
         Ok(Expr::FunctionCall(self.parse_function_call(name)?))
     }
 
-    fn parse_function_call(&mut self, name: &str) -> Result<FunctionCall, String> {
+    fn parse_function_call(&mut self, name: &str) -> Result<FunctionCall, Error> {
         //Skip name
         self.advance();
 
@@ -106,21 +186,35 @@ impl Parser {
         Ok(FunctionCall { name, args })
     }
 
-    fn parse_io(&mut self, io_keyword: IO) -> Result<Stmt, String> {
+    fn parse_io(&mut self, io_keyword: IO) -> Result<Stmt, Error> {
         match io_keyword {
             IO::Input => self.parse_input(),
             IO::Output => self.parse_output(),
-            IO::ChangeLine => Err("Change line couldn't be Statement".to_string()),
+            IO::ChangeLine => Err(Message::NewLineIsNotStatement.into()),
         }
     }
 
-    fn parse_input(&mut self) -> Result<Stmt, String> {
+    fn parse_input(&mut self) -> Result<Stmt, Error> {
+        //Skip Input keyword
         self.advance();
 
-        todo!()
+        let mut names = Vec::new();
+        loop {
+            match self.current_token() {
+                Token::Identifier(name) => names.push(name.clone()),
+                token => return Err(Message::ExpectedInputName(format!("{token:?}")).into()),
+            }
+            self.advance();
+            if !self.current_token().is_delimiter(Delimiter::Comma) {
+                break;
+            }
+            self.advance();
+        }
+
+        Ok(Stmt::Input { names })
     }
 
-    fn parse_output(&mut self) -> Result<Stmt, String> {
+    fn parse_output(&mut self) -> Result<Stmt, Error> {
         //Skip Output keyword
         self.advance();
 
@@ -131,6 +225,9 @@ impl Parser {
                 Token::Keyword(Keyword::IO(IO::ChangeLine)) => {
                     values.push(Expr::NewLine);
                     self.advance();
+                    if !self.current_token().is_delimiter(Delimiter::Comma) {
+                        break;
+                    }
                 }
                 Token::Delimiter(Delimiter::Comma) => {
                     self.advance();
@@ -150,9 +247,9 @@ impl Parser {
         Ok(Stmt::Output { values })
     }
 
-    fn parse_condition(&mut self) -> Result<Stmt, String> {
+    fn parse_condition(&mut self) -> Result<Stmt, Error> {
         self.advance();
-        let condition = self.parse_expr()?;
+        let (condition, condition_span) = self.parse_condition_expr()?;
         self.expect(Token::Keyword(Keyword::Condition(lexer::Condition::Then)))?;
 
         let mut left = Vec::new();
@@ -161,7 +258,7 @@ impl Parser {
                 lexer::Condition::EndCondition,
             )))
         {
-            left.push(self.parse_stmt()?);
+            left.push(self.parse_statement()?);
         }
 
         let right = if self.check(&Token::Keyword(Keyword::Condition(lexer::Condition::Else))) {
@@ -170,7 +267,7 @@ impl Parser {
             while !self.check(&Token::Keyword(Keyword::Condition(
                 lexer::Condition::EndCondition,
             ))) {
-                else_branch.push(self.parse_stmt()?);
+                else_branch.push(self.parse_statement()?);
             }
             Some(Box::new(AstNode::Program(else_branch)))
         } else {
@@ -183,12 +280,13 @@ impl Parser {
         let left = Box::new(AstNode::Program(left));
         Ok(Stmt::Condition(Condition {
             condition,
+            condition_span,
             left,
             right,
         }))
     }
 
-    fn parse_loop(&mut self) -> Result<Stmt, String> {
+    fn parse_loop(&mut self) -> Result<Stmt, Error> {
         self.advance();
         match self.current_token() {
             Token::Keyword(Keyword::Loop(lexer::Loop::While)) => self.parse_while_loop(),
@@ -199,41 +297,59 @@ impl Parser {
         }
     }
 
-    fn parse_simple_loop(&mut self) -> Result<Stmt, String> {
-        self.advance();
+    fn parse_simple_loop(&mut self) -> Result<Stmt, Error> {
+        let (statements, condition) = self.parse_loop_body()?;
+        let (condition, condition_span) = condition.unzip();
+        let body = Box::new(AstNode::Program(statements));
+        Ok(Stmt::Loop(Loop {
+            condition,
+            condition_span: condition_span.unwrap_or_default(),
+            body,
+        }))
+    }
+
+    /// Parses loop statements up to `кц` or `кц_при <condition>`
+    fn parse_loop_body(&mut self) -> Result<LoopBody, Error> {
         let mut statements = Vec::new();
-        let mut condition = None;
-        while *self.current_token() != Token::Keyword(Keyword::Loop(lexer::Loop::End)) {
-            if *self.current_token() != Token::Keyword(Keyword::Loop(lexer::Loop::EndIf)) {
-                self.advance();
-                condition = Some(self.parse_expr()?);
-            } else {
-                statements.push(self.parse_stmt()?);
-            }
+        while !self.check(&Token::Keyword(Keyword::Loop(lexer::Loop::End)))
+            && !self.check(&Token::Keyword(Keyword::Loop(lexer::Loop::EndIf)))
+        {
+            statements.push(self.parse_statement()?);
+        }
+        if self.check(&Token::Keyword(Keyword::Loop(lexer::Loop::EndIf))) {
+            //Skip Loop::EndIf
+            self.advance();
+            Ok((statements, Some(self.parse_condition_expr()?)))
+        } else {
+            //Skip Loop::End
+            self.advance();
+            Ok((statements, None))
         }
-        let body = Box::new(AstNode::Program(statements));
-        Ok(Stmt::Loop(Loop { condition, body }))
     }
 
-    fn parse_while_loop(&mut self) -> Result<Stmt, String> {
+    fn parse_while_loop(&mut self) -> Result<Stmt, Error> {
         //Skip Loop start token
         self.advance();
-        let condition = Some(self.parse_expr()?);
+        let (condition, condition_span) = self.parse_condition_expr()?;
         let mut statements = Vec::new();
         while *self.current_token() != Token::Keyword(Keyword::Loop(lexer::Loop::End)) {
-            statements.push(self.parse_stmt()?);
+            statements.push(self.parse_statement()?);
         }
         //Skip loop end token
         self.advance();
         let body = Box::new(AstNode::Program(statements));
-        Ok(Stmt::Loop(Loop { condition, body }))
+        Ok(Stmt::Loop(Loop {
+            condition: Some(condition),
+            condition_span,
+            body,
+        }))
     }
 
-    fn parse_for_loop(&mut self) -> Result<Stmt, String> {
+    fn parse_for_loop(&mut self) -> Result<Stmt, Error> {
         self.advance();
         let var = match self.current_token() {
             Token::Identifier(name) => name.clone(),
-            _ => return Err("Expected identifier".to_string()),
+            _ => return Err(Message::ExpectedIdentifier.into()),
         };
         self.advance();
         self.expect(Token::Keyword(Keyword::Range(Range::From)))?;
@@ -242,7 +358,7 @@ impl Parser {
         let end = self.parse_expr()?;
         let mut statements = Vec::new();
         while *self.current_token() != Token::Keyword(Keyword::Loop(lexer::Loop::End)) {
-            statements.push(self.parse_stmt()?);
+            statements.push(self.parse_statement()?);
         }
         self.advance();
         Ok(Stmt::ForLoop(ForLoop {
@@ -253,28 +369,20 @@ impl Parser {
         }))
     }
 
-    fn parse_repeat_loop(&mut self, count: Expr) -> Result<Stmt, String> {
+    fn parse_repeat_loop(&mut self, count: Expr) -> Result<Stmt, Error> {
         self.advance(); // Skip the count
         self.expect(Token::Keyword(Keyword::Loop(lexer::Loop::Times)))?;
-        let mut statements = Vec::new();
-        let mut condition = None;
-        while *self.current_token() != Token::Keyword(Keyword::Loop(lexer::Loop::End)) {
-            if *self.current_token() != Token::Keyword(Keyword::Loop(lexer::Loop::EndIf)) {
-                self.advance();
-                condition = Some(self.parse_expr()?);
-            } else {
-                statements.push(self.parse_stmt()?);
-            }
-        }
-        self.advance(); // Skip Loop::End
+        let (statements, condition) = self.parse_loop_body()?;
+        let (condition, condition_span) = condition.unzip();
         Ok(Stmt::RepeatLoop(RepeatLoop {
             condition,
+            condition_span: condition_span.unwrap_or_default(),
             count,
             body: Box::new(AstNode::Program(statements)),
         }))
     }
 
-    fn parse_alg(&mut self) -> Result<Stmt, String> {
+    fn parse_alg(&mut self) -> Result<Stmt, Error> {
         self.advance(); // Skip Alg
         let return_type =
             if let Token::Keyword(Keyword::TypeDef(return_type)) = self.current_token() {
@@ -290,7 +398,7 @@ impl Parser {
                 self.advance();
                 name.clone()
             }
-            _ => "main".to_string(),
+            _ => MAIN.to_string(),
         };
         info!("Function name: {name}");
         let mut params: IndexMap<String, FunctionParameter> = IndexMap::new();
@@ -332,7 +440,7 @@ impl Parser {
                     if let Token::Keyword(Keyword::TypeDef(type_def)) = self.current_token() {
                         *type_def
                     } else {
-                        return Err("Typedef for function parameter required".to_string());
+                        return Err(Message::ParameterTypeRequired.into());
                     }
                 };
 
@@ -368,14 +476,13 @@ impl Parser {
                     )
                 }));
             }
+            self.expect(Token::Delimiter(Delimiter::ParenthesisClose))?;
         }
         info!(
             "Function params parsed: {:#?}, token stoped: {}",
             params, self.position
         );
 
-        self.expect(Token::Delimiter(Delimiter::ParenthesisClose))?;
-
         if self.check(&Token::Keyword(Keyword::Function(lexer::Function::Expects))) {
             //skip Expects token
             self.advance();
@@ -388,7 +495,7 @@ impl Parser {
         while !self.check(&Token::Keyword(Keyword::Function(lexer::Function::Stop)))
             && !self.is_eof()
         {
-            body.push(self.parse_stmt()?);
+            body.push(self.parse_statement()?);
         }
         let body = Box::new(AstNode::Program(body));
         self.expect(Token::Keyword(Keyword::Function(lexer::Function::Stop)))?;
@@ -400,17 +507,18 @@ impl Parser {
         }))
     }
 
-    fn parse_var_decl(&mut self, type_def: &TypeDefinition) -> Result<Stmt, String> {
+    fn parse_var_decl(&mut self, type_def: &TypeDefinition) -> Result<Stmt, Error> {
         self.advance(); // Skip type
         let name = match self.current_token() {
             Token::Identifier(name) => name.clone(),
-            _ => return Err("Expected identifier".to_string()),
+            _ => return Err(Message::ExpectedIdentifier.into()),
         };
         info!("Creating var with name: {}", &name);
         self.advance();
 
         match self.current_token() {
-            Token::Operator(lexer::Operator::Assignment) => {
+            //`компл а = 1i + 1` initializes like `:=`
+            Token::Operator(lexer::Operator::Assignment | lexer::Operator::Equal) => {
                 self.advance();
                 let value = Some(self.parse_expr()?);
                 Ok(Stmt::VarDecl(VarDecl {
@@ -435,7 +543,7 @@ impl Parser {
                                 value: None,
                             });
                         }
-                        _ => return Err("Couldn't construct a vars sequence".to_string()),
+                        _ => return Err(Message::InvalidVarsSequence.into()),
                     };
                     self.advance();
                 }
@@ -449,10 +557,10 @@ impl Parser {
         }
     }
 
-    fn parse_assign(&mut self) -> Result<Stmt, String> {
+    fn parse_assign(&mut self) -> Result<Stmt, Error> {
         let name = match self.current_token() {
             Token::Identifier(name) => name.clone(),
-            _ => return Err("Expected identifier".to_string()),
+            _ => return Err(Message::ExpectedIdentifier.into()),
         };
         self.advance();
         self.expect(Token::Operator(lexer::Operator::Assignment))?;
@@ -460,11 +568,11 @@ impl Parser {
         Ok(Stmt::Assign { name, value })
     }
 
-    fn parse_expr(&mut self) -> Result<Expr, String> {
+    fn parse_expr(&mut self) -> Result<Expr, Error> {
         self.parse_binary_expr(0)
     }
 
-    fn parse_binary_expr(&mut self, precedence: i32) -> Result<Expr, String> {
+    fn parse_binary_expr(&mut self, precedence: i32) -> Result<Expr, Error> {
         let mut left = self.parse_primary()?;
         while let Some(op) = self.current_token().as_operator() {
             let op_precedence = op.precedence();
@@ -482,15 +590,15 @@ impl Parser {
         Ok(left)
     }
 
-    fn parse_primary(&mut self) -> Result<Expr, String> {
+    fn parse_primary(&mut self) -> Result<Expr, Error> {
         match self.current_token().clone() {
             Token::Int(i) => {
                 self.advance();
-                Ok(Expr::Literal(Literal::Int(i)))
+                self.parse_implicit_product(Expr::Literal(Literal::Int(i)))
             }
             Token::Float(f) => {
                 self.advance();
-                Ok(Expr::Literal(Literal::Float(f)))
+                self.parse_implicit_product(Expr::Literal(Literal::Float(f)))
             }
             Token::String(s) => {
                 self.advance();
@@ -518,17 +626,39 @@ impl Parser {
                 self.expect(Token::Delimiter(Delimiter::ParenthesisClose))?;
                 Ok(expr)
             }
-            _ => Err(format!(
-                "Unexpected token in expression: {:?}",
-                self.current_token()
-            )),
+            _ => Err(
+                Message::UnexpectedTokenInExpression(format!("{:?}", self.current_token())).into(),
+            ),
         }
     }
 
+    /// A name written right after a number multiplies it, `2i` is `2 * i`
+    fn parse_implicit_product(&mut self, number: Expr) -> Result<Expr, Error> {
+        let number_span = self.spans[self.position - 1];
+        let adjacent =
+            number_span.start != number_span.end && number_span.end == self.current_span().start;
+        if !adjacent || !self.current_token().is_identifier() {
+            return Ok(number);
+        }
+        Ok(Expr::BinaryOp(BinaryOp {
+            left: Box::new(number),
+            op: Operator::Multiply,
+            right: Box::new(self.parse_primary()?),
+        }))
+    }
+
     fn current_token(&self) -> &Token {
         self.tokens.get(self.position).unwrap_or(&Token::Eof)
     }
 
+    fn current_span(&self) -> Span {
+        self.spans
+            .get(self.position)
+            .or(self.spans.last())
+            .copied()
+            .unwrap_or_default()
+    }
+
     fn advance(&mut self) {
         self.position += 1;
     }
@@ -541,17 +671,16 @@ impl Parser {
         self.current_token() == token
     }
 
-    fn expect(&mut self, token: Token) -> Result<(), String> {
+    fn expect(&mut self, token: Token) -> Result<(), Error> {
         if self.check(&token) {
             self.advance();
             Ok(())
         } else {
-            Err(format!(
-                "Expected {:?}, found {:?}, current token: {}",
-                token,
-                self.current_token(),
-                self.position
-            ))
+            Err(Message::ExpectedToken {
+                expected: format!("{token:?}"),
+                found: format!("{:?}", self.current_token()),
+            }
+            .into())
         }
     }
 
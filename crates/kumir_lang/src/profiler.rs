@@ -0,0 +1,385 @@
+//! Opt-in measurements of a run: how often each line runs, how long each
+//! algorithm takes and how many actor commands each line calls. Enabled with
+//! [`crate::interpreter::Interpreter::enable_profiler`], read with
+//! [`crate::interpreter::Interpreter::profile`].
+
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use indexmap::IndexMap;
+use web_time::Instant;
+
+/// What was measured on one line
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct LineProfile {
+    pub line: usize,
+    /// Statements entered on the line
+    pub count: u64,
+    /// Commands of actors called from the line, see [`crate::stdlib::BUILT_IN`]
+    pub actor_calls: u64,
+}
+
+/// Calls and time of one algorithm of the program
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AlgorithmProfile {
+    pub name: String,
+    pub calls: u64,
+    /// From the call to the return, recursive calls counted once
+    pub total: Duration,
+    /// Like `total`, without the algorithms it called
+    pub own: Duration,
+}
+
+/// Hint the IDE shows next to a line, `heat` is its count relative to the
+/// most frequent line, from 0 to 1
+#[derive(Debug, PartialEq, Clone)]
+pub struct LineOverlay {
+    pub line: usize,
+    pub text: String,
+    pub heat: f32,
+}
+
+/// Results of a profiled run, as JSON by [`ProfileReport::to_json`] and as
+/// a text table by `Display`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ProfileReport {
+    pub total: Duration,
+    /// By line number, lines that never ran are missing
+    pub lines: Vec<LineProfile>,
+    /// In the order of their first call
+    pub algorithms: Vec<AlgorithmProfile>,
+}
+
+impl ProfileReport {
+    pub fn line(&self, line: usize) -> Option<&LineProfile> {
+        self.lines.iter().find(|profile| profile.line == line)
+    }
+
+    /// Hints for every line that ran
+    pub fn overlay(&self) -> Vec<LineOverlay> {
+        let max = self
+            .lines
+            .iter()
+            .map(|profile| profile.count)
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        self.lines
+            .iter()
+            .map(|profile| {
+                let mut text = format!("×{}", profile.count);
+                if profile.actor_calls > 0 {
+                    text += &format!(", команд: {}", profile.actor_calls);
+                }
+                LineOverlay {
+                    line: profile.line,
+                    text,
+                    heat: profile.count as f32 / max as f32,
+                }
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        let lines: Vec<String> = self
+            .lines
+            .iter()
+            .map(|profile| {
+                format!(
+                    "{{\"line\":{},\"count\":{},\"actor_calls\":{}}}",
+                    profile.line, profile.count, profile.actor_calls
+                )
+            })
+            .collect();
+        let algorithms: Vec<String> = self
+            .algorithms
+            .iter()
+            .map(|algorithm| {
+                format!(
+                    "{{\"name\":{},\"calls\":{},\"total_ms\":{:.3},\"own_ms\":{:.3}}}",
+                    json_string(&algorithm.name),
+                    algorithm.calls,
+                    milliseconds(algorithm.total),
+                    milliseconds(algorithm.own)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"total_ms\":{:.3},\"lines\":[{}],\"algorithms\":[{}]}}",
+            milliseconds(self.total),
+            lines.join(","),
+            algorithms.join(",")
+        )
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>6} {:>12} {:>8}", "Строка", "Выполнений", "Команд")?;
+        for profile in &self.lines {
+            writeln!(
+                f,
+                "{:>6} {:>12} {:>8}",
+                profile.line, profile.count, profile.actor_calls
+            )?;
+        }
+        if !self.algorithms.is_empty() {
+            let width = self
+                .algorithms
+                .iter()
+                .map(|algorithm| algorithm.name.chars().count())
+                .max()
+                .unwrap_or_default()
+                .max("Алгоритм".chars().count());
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{:<width$} {:>8} {:>12} {:>12}",
+                "Алгоритм", "Вызовов", "Всего, мс", "Своё, мс"
+            )?;
+            for algorithm in &self.algorithms {
+                writeln!(
+                    f,
+                    "{:<width$} {:>8} {:>12.3} {:>12.3}",
+                    algorithm.name,
+                    algorithm.calls,
+                    milliseconds(algorithm.total),
+                    milliseconds(algorithm.own)
+                )?;
+            }
+        }
+        writeln!(f)?;
+        write!(f, "Всего: {:.3} мс", milliseconds(self.total))
+    }
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn json_string(text: &str) -> String {
+    let mut result = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            '\n' => result += "\\n",
+            c if c.is_control() => result += &format!("\\u{:04x}", c as u32),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Algorithm that is running
+#[derive(Debug)]
+struct Call {
+    name: String,
+    /// Line of the caller, current again after the return
+    line: usize,
+    started: Instant,
+    /// Time spent in the algorithms it called
+    children: Duration,
+}
+
+/// Counters of a run, shared by all scopes through
+/// [`crate::ast::Environment::profiler`]
+#[derive(Debug, Default)]
+pub struct Profiler {
+    lines: BTreeMap<usize, LineProfile>,
+    algorithms: IndexMap<String, AlgorithmProfile>,
+    calls: Vec<Call>,
+    /// Line of the statement that runs
+    line: usize,
+    started: Option<Instant>,
+    total: Duration,
+}
+
+impl Profiler {
+    /// Forgets the previous run
+    pub(crate) fn start(&mut self) {
+        *self = Self {
+            started: Some(Instant::now()),
+            ..Default::default()
+        };
+    }
+
+    pub(crate) fn finish(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.total = started.elapsed();
+        }
+        self.calls.clear();
+    }
+
+    pub(crate) fn statement_entered(&mut self, line: usize) {
+        self.line = line;
+        self.line_profile(line).count += 1;
+    }
+
+    pub(crate) fn actor_called(&mut self) {
+        let line = self.line;
+        self.line_profile(line).actor_calls += 1;
+    }
+
+    pub(crate) fn enter_call(&mut self, name: &str) {
+        self.algorithms
+            .entry(name.to_string())
+            .or_insert_with(|| AlgorithmProfile {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .calls += 1;
+        self.calls.push(Call {
+            name: name.to_string(),
+            line: self.line,
+            started: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    pub(crate) fn exit_call(&mut self) {
+        let Some(call) = self.calls.pop() else {
+            return;
+        };
+        let elapsed = call.started.elapsed();
+        let recursive = self.calls.iter().any(|outer| outer.name == call.name);
+        if let Some(algorithm) = self.algorithms.get_mut(&call.name) {
+            if !recursive {
+                algorithm.total += elapsed;
+            }
+            algorithm.own += elapsed.saturating_sub(call.children);
+        }
+        if let Some(caller) = self.calls.last_mut() {
+            caller.children += elapsed;
+        }
+        self.line = call.line;
+    }
+
+    pub fn report(&self) -> ProfileReport {
+        ProfileReport {
+            total: match self.started {
+                Some(started) => started.elapsed(),
+                None => self.total,
+            },
+            lines: self.lines.values().copied().collect(),
+            algorithms: self.algorithms.values().cloned().collect(),
+        }
+    }
+
+    fn line_profile(&mut self, line: usize) -> &mut LineProfile {
+        self.lines.entry(line).or_insert(LineProfile {
+            line,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Namespace, io::testing::interpreter, stdlib::native};
+
+    #[test]
+    fn definitions_are_not_counted() {
+        let source = "алг квадрат(арг цел х)\nнач\nвывод х * х\nкон\nквадрат(2)\nквадрат(3)";
+        let (mut interpreter, output) = interpreter(source, &[]);
+        interpreter.enable_profiler();
+        interpreter.run().unwrap();
+        assert_eq!(output.take(), "49");
+        let report = interpreter.profile().unwrap();
+        assert_eq!(report.line(1), None);
+        assert_eq!(report.line(3).map(|line| line.count), Some(2));
+        assert_eq!(report.line(5).map(|line| line.count), Some(1));
+        assert_eq!(report.algorithms[0].calls, 2);
+    }
+
+    #[test]
+    fn actor_commands_per_line() {
+        let source = "использовать Альфа\nалг\nнач\nнц для и от 0 до 3\nшаг\nкц\nшаг; прыжок; вывод длин(\"аб\")\nкон";
+        let (mut interpreter, _) = interpreter(source, &[]);
+        let mut namespace = Namespace::default();
+        for command in ["шаг", "прыжок"] {
+            namespace.register_native_function(command, native(&[], None, |_| Ok(None)));
+        }
+        interpreter.register_namespace("Альфа", namespace);
+        interpreter.enable_profiler();
+        interpreter.run().unwrap();
+        let report = interpreter.profile().unwrap();
+        let lines: Vec<_> = report
+            .lines
+            .iter()
+            .map(|line| (line.line, line.count, line.actor_calls))
+            .collect();
+        //`длин` is built in, not a command of an actor
+        assert_eq!(lines, [(1, 1, 0), (4, 1, 0), (5, 3, 3), (7, 3, 2)]);
+
+        let overlay: Vec<_> = report
+            .overlay()
+            .into_iter()
+            .map(|line| (line.line, line.text, line.heat))
+            .collect();
+        assert_eq!(
+            overlay,
+            [
+                (1, "×1".to_string(), 1.0 / 3.0),
+                (4, "×1".to_string(), 1.0 / 3.0),
+                (5, "×3, команд: 3".to_string(), 1.0),
+                (7, "×3, команд: 2".to_string(), 1.0)
+            ]
+        );
+        assert_eq!(ProfileReport::default().overlay(), []);
+    }
+
+    #[test]
+    fn recursive_calls_count_their_time_once() {
+        let wait = || std::thread::sleep(Duration::from_millis(10));
+        let mut profiler = Profiler::default();
+        profiler.start();
+        profiler.enter_call("ф");
+        wait();
+        profiler.enter_call("ф");
+        wait();
+        profiler.exit_call();
+        profiler.enter_call("г");
+        wait();
+        profiler.exit_call();
+        profiler.exit_call();
+        profiler.finish();
+
+        let report = profiler.report();
+        let [f, g] = report.algorithms.as_slice() else {
+            panic!("{:?}", report.algorithms);
+        };
+        assert_eq!((f.name.as_str(), f.calls), ("ф", 2));
+        assert_eq!((g.name.as_str(), g.calls), ("г", 1));
+        //Only the outer call of `ф`, which includes the inner one and `г`
+        assert!(f.total >= Duration::from_millis(30));
+        assert!(f.total <= report.total);
+        assert_eq!(f.own, f.total - g.total);
+        assert_eq!(g.own, g.total);
+    }
+
+    #[test]
+    fn json_of_a_report() {
+        let report = ProfileReport {
+            total: Duration::from_micros(2500),
+            lines: vec![LineProfile {
+                line: 3,
+                count: 2,
+                actor_calls: 1,
+            }],
+            algorithms: vec![AlgorithmProfile {
+                name: "а \"б\"\\в\nг\u{1}".to_string(),
+                calls: 1,
+                total: Duration::from_millis(2),
+                own: Duration::from_micros(1500),
+            }],
+        };
+        assert_eq!(
+            report.to_json(),
+            "{\"total_ms\":2.500,\"lines\":[{\"line\":3,\"count\":2,\"actor_calls\":1}],\
+             \"algorithms\":[{\"name\":\"а \\\"б\\\"\\\\в\\nг\\u0001\",\"calls\":1,\
+             \"total_ms\":2.000,\"own_ms\":1.500}]}"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Random numbers of `rnd`, `rand`, `irnd` and `irand`, see
+//! `userdocs/md_output/math.md`. The numbers depend only on the seed, so a
+//! run with the same seed can be replayed exactly
+
+/// Generator of a program, by default seeded from the current time
+#[derive(Debug, Clone)]
+pub struct Random {
+    seed: u64,
+    rng: fastrand::Rng,
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        let seed = web_time::SystemTime::now()
+            .duration_since(web_time::UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+        Self::new(seed)
+    }
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Uniformly distributed between `from` and `to`, in either order
+    pub fn float(&mut self, from: f32, to: f32) -> f32 {
+        let (from, to) = (from as f64, to as f64);
+        (from + self.rng.f64() * (to - from)) as f32
+    }
+
+    /// Uniformly distributed between `from` and `to` inclusive, in either
+    /// order
+    pub fn int(&mut self, from: i32, to: i32) -> i32 {
+        self.rng.i32(from.min(to)..=from.max(to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::interpreter;
+
+    const SOURCE: &str = "нц для i от 1 до 5\nвывод rnd(1), \" \", irnd(100), \" \", rand(2, 3), \" \", irand(5, 9), нс\nкц";
+
+    #[test]
+    fn runs_with_one_seed_draw_the_same_numbers() {
+        let (mut first_run, output) = interpreter(SOURCE, &[]);
+        first_run.set_seed(42);
+        first_run.run().unwrap();
+        let first = output.take();
+        first_run.run().unwrap();
+        assert_eq!(output.take(), first);
+        assert_eq!(first_run.seed(), 42);
+
+        let (mut other, output) = interpreter(SOURCE, &[]);
+        other.set_seed(42);
+        other.run().unwrap();
+        assert_eq!(output.take(), first);
+    }
+
+    #[test]
+    fn numbers_stay_in_range() {
+        let mut random = Random::new(7);
+        for _ in 0..1000 {
+            let float = random.float(2.0, 3.0);
+            assert!((2.0..=3.0).contains(&float));
+            assert!((-5..=5).contains(&random.int(5, -5)));
+        }
+    }
+}
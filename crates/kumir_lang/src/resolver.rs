@@ -0,0 +1,345 @@
+//! Combines word tokens into Kumir names.
+//!
+//! A Kumir name is a sequence of words separated by spaces, none of which is
+//! a keyword (`слева свободно`, `клетка закрашена`). The lexer emits every
+//! word as a separate [`Token::Identifier`], and names are resolved here:
+//!
+//! 1. A run is a maximal sequence of [`Token::Identifier`] tokens on one
+//!    line. Keywords, literals, operators, delimiters, comments, line breaks
+//!    and `;` all end a run, so a keyword is never merged into a name.
+//! 2. Names are declared by the program itself: the run after `алг` (and
+//!    its return type), after a type keyword (`цел`, `вещ`, `лог`, `сим`,
+//!    `лит`), after every `,` that continues such a declaration, and after
+//!    `для`. The whole run is the declared name.
+//! 3. Known names are the declared names plus the names the host provides,
+//!    e.g. native algorithms and namespaces registered on the interpreter.
+//! 4. Every run is split left to right into the longest known names. When
+//!    no known name starts at the current word, the rest of the run becomes
+//!    one name, so undeclared names still reach the parser as written.
+//! 5. `кц при` is the two-word spelling of `кц_при`.
+//! 6. A word naming a type of an imported namespace is a type keyword, like
+//!    `файл` of `Файлы`. Types of the namespaces imported by the host are
+//!    known from the start, the others after `использовать` of their
+//!    namespace, see [`crate::types`]. This happens before names are
+//!    declared, so `файл ф` declares `ф`.
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::lexer::{Delimiter, Function, Keyword, Loop, Range, Span, Token, TypeDefinition};
+
+/// Names of the types namespaces add to the language
+#[derive(Debug, Clone, Default)]
+pub struct KnownTypes {
+    /// Types of the namespaces already imported
+    pub imported: HashSet<&'static str>,
+    /// Types of every namespace by its name
+    pub namespaces: HashMap<String, Vec<&'static str>>,
+}
+
+pub fn resolve_names(
+    tokens: Vec<(Token, Span)>,
+    known_names: &HashSet<String>,
+    known_types: &KnownTypes,
+) -> Vec<(Token, Span)> {
+    let tokens = resolve_keyword_phrases(tokens);
+    let tokens = resolve_types(tokens, known_types);
+
+    let mut names = declared_names(&tokens);
+    names.extend(known_names.iter().cloned());
+
+    let mut resolved = Vec::with_capacity(tokens.len());
+    let mut run: Vec<(String, Span)> = vec![];
+    for (token, span) in tokens {
+        match token {
+            Token::Identifier(word) => run.push((word, span)),
+            token => {
+                split_run(&run, &names, &mut resolved);
+                run.clear();
+                resolved.push((token, span));
+            }
+        }
+    }
+    split_run(&run, &names, &mut resolved);
+    resolved
+}
+
+fn resolve_keyword_phrases(tokens: Vec<(Token, Span)>) -> Vec<(Token, Span)> {
+    let mut resolved: Vec<(Token, Span)> = Vec::with_capacity(tokens.len());
+    for (token, span) in tokens {
+        match resolved.last_mut() {
+            Some((last, last_span))
+                if token == Token::Identifier("при".to_string())
+                    && *last == Token::Keyword(Keyword::Loop(Loop::End)) =>
+            {
+                *last = Token::Keyword(Keyword::Loop(Loop::EndIf));
+                last_span.end = span.end;
+            }
+            _ => resolved.push((token, span)),
+        }
+    }
+    resolved
+}
+
+fn resolve_types(tokens: Vec<(Token, Span)>, known_types: &KnownTypes) -> Vec<(Token, Span)> {
+    let mut types = known_types.imported.clone();
+    let mut importing: Option<Vec<String>> = None;
+    let mut resolved = Vec::with_capacity(tokens.len());
+    for (token, span) in tokens {
+        if let (Some(words), Token::Identifier(word)) = (importing.as_mut(), &token) {
+            words.push(word.clone());
+            resolved.push((token, span));
+            continue;
+        }
+        if let Some(words) = importing.take()
+            && let Some(namespace_types) = known_types.namespaces.get(&words.join(" "))
+        {
+            types.extend(namespace_types);
+        }
+        match token {
+            Token::Keyword(Keyword::Function(Function::ImportNamespace)) => {
+                importing = Some(vec![]);
+                resolved.push((token, span));
+            }
+            Token::Identifier(word) if types.contains(word.as_str()) => {
+                let name = types.get(word.as_str()).copied().unwrap_or_default();
+                let token = Token::Keyword(Keyword::TypeDef(TypeDefinition::Custom(name)));
+                resolved.push((token, span));
+            }
+            token => resolved.push((token, span)),
+        }
+    }
+    resolved
+}
+
+fn declared_names(tokens: &[(Token, Span)]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut declaring = false;
+    let mut run: Vec<&str> = vec![];
+    for (token, _) in tokens {
+        if let Token::Identifier(word) = token {
+            if declaring {
+                run.push(word);
+            }
+            continue;
+        }
+        if !run.is_empty() {
+            names.insert(run.join(" "));
+            run.clear();
+            declaring = token.is_delimiter(Delimiter::Comma);
+            continue;
+        }
+        declaring = matches!(
+            token,
+            Token::Keyword(
+                Keyword::Function(Function::Alg) | Keyword::TypeDef(_) | Keyword::Range(Range::For)
+            )
+        ) || (declaring && token.is_delimiter(Delimiter::Comma));
+    }
+    if !run.is_empty() {
+        names.insert(run.join(" "));
+    }
+    names
+}
+
+fn split_run(run: &[(String, Span)], names: &HashSet<String>, resolved: &mut Vec<(Token, Span)>) {
+    let name = |start: usize, end: usize| {
+        run[start..end]
+            .iter()
+            .map(|(word, _)| word.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut start = 0;
+    while start < run.len() {
+        let end = (start + 1..=run.len())
+            .rev()
+            .find(|end| names.contains(&name(start, *end)))
+            .unwrap_or(run.len());
+        let span = Span {
+            start: run[start].1.start,
+            end: run[end - 1].1.end,
+        };
+        resolved.push((Token::Identifier(name(start, end)), span));
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn resolve(source: &str, known: &[&str], known_types: &KnownTypes) -> Vec<Token> {
+        let mut lexer = Lexer::new(source);
+        let mut tokens = vec![];
+        loop {
+            match lexer.next_spanned_token().unwrap() {
+                (Token::Eof, _) => break,
+                token => tokens.push(token),
+            }
+        }
+        let known = known.iter().map(|name| name.to_string()).collect();
+        resolve_names(tokens, &known, known_types)
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    fn identifiers(source: &str, known: &[&str]) -> Vec<String> {
+        resolve(source, known, &KnownTypes::default())
+            .iter()
+            .filter_map(Token::identifier)
+            .collect()
+    }
+
+    #[test]
+    fn declared_names_are_joined() {
+        let source = "алг цел сумма двух(цел перв число, втор число)\nнач\nзнач := перв число + втор число\nкон";
+        assert_eq!(
+            identifiers(source, &[]),
+            [
+                "сумма двух",
+                "перв число",
+                "втор число",
+                "знач",
+                "перв число",
+                "втор число"
+            ]
+        );
+    }
+
+    #[test]
+    fn names_after_commas_and_для_are_declared() {
+        let source = "цел первый ряд, второй ряд\nнц для номер строки от 1 до 3\nпервый ряд := номер строки\nкц";
+        assert_eq!(
+            identifiers(source, &[]),
+            [
+                "первый ряд",
+                "второй ряд",
+                "номер строки",
+                "первый ряд",
+                "номер строки"
+            ]
+        );
+    }
+
+    #[test]
+    fn known_names_split_runs_longest_first() {
+        assert_eq!(
+            identifiers(
+                "если слева свободно то вправо все",
+                &["слева", "слева свободно", "вправо"]
+            ),
+            ["слева свободно", "вправо"]
+        );
+        assert_eq!(
+            identifiers("вправо вправо", &["вправо"]),
+            ["вправо", "вправо"]
+        );
+    }
+
+    #[test]
+    fn unknown_words_keep_the_rest_of_the_run() {
+        assert_eq!(
+            identifiers("вправо клетка закрашена", &["вправо"]),
+            ["вправо", "клетка закрашена"]
+        );
+    }
+
+    #[test]
+    fn keywords_and_operators_end_runs() {
+        assert_eq!(
+            identifiers("цел а б\nа б := а б + 1; вывод а б", &[]),
+            ["а б", "а б", "а б", "а б"]
+        );
+        assert_eq!(
+            identifiers("если край то стена все", &[]),
+            ["край", "стена"]
+        );
+    }
+
+    #[test]
+    fn кц_при_is_one_keyword() {
+        let tokens = resolve("нц\nкц при да", &[], &KnownTypes::default());
+        assert_eq!(tokens[2], Token::Keyword(Keyword::Loop(Loop::EndIf)));
+        assert!(!tokens.iter().any(Token::is_identifier));
+    }
+
+    #[test]
+    fn namespace_types_become_keywords_after_import() {
+        let known_types = KnownTypes {
+            imported: HashSet::new(),
+            namespaces: HashMap::from([("Файлы".to_string(), vec!["файл"])]),
+        };
+        let file = Token::Keyword(Keyword::TypeDef(TypeDefinition::Custom("файл")));
+
+        let tokens = resolve("файл ф", &[], &known_types);
+        assert!(!tokens.contains(&file));
+
+        let tokens = resolve("использовать Файлы\nфайл ф\nф := ф", &[], &known_types);
+        assert!(tokens.contains(&file));
+        assert_eq!(
+            tokens
+                .iter()
+                .filter_map(Token::identifier)
+                .collect::<Vec<_>>(),
+            ["Файлы", "ф", "ф", "ф"]
+        );
+    }
+
+    /// Commands of `Робот`
+    const ROBOT: [&str; 15] = [
+        "вверх",
+        "вниз",
+        "влево",
+        "вправо",
+        "закрасить",
+        "слева свободно",
+        "справа свободно",
+        "сверху свободно",
+        "снизу свободно",
+        "слева стена",
+        "справа стена",
+        "стена сверху",
+        "стена снизу",
+        "клетка закрашена",
+        "клетка чистая",
+    ];
+
+    #[test]
+    fn robot_commands_next_to_keywords() {
+        for command in ROBOT.iter().filter(|command| command.contains(' ')) {
+            let cases = [
+                (
+                    format!("если {command} то вправо все"),
+                    vec![command, "вправо"],
+                ),
+                (
+                    format!("если {command}\nто закрасить\nиначе вниз\nвсе"),
+                    vec![command, "закрасить", "вниз"],
+                ),
+                (
+                    format!("нц пока {command}\nвлево\nкц"),
+                    vec![command, "влево"],
+                ),
+                (
+                    format!("нц\nвверх\nкц при {command}"),
+                    vec!["вверх", command],
+                ),
+                (format!("{command}\nзакрасить"), vec![command, "закрасить"]),
+                (
+                    format!("вправо {command} вниз"),
+                    vec!["вправо", command, "вниз"],
+                ),
+                (format!("{command} {command}"), vec![command, command]),
+                (
+                    format!("лог л := {command}; вывод {command}, нс"),
+                    vec!["л", command, command],
+                ),
+            ];
+            for (source, expected) in cases {
+                assert_eq!(identifiers(&source, &ROBOT), expected, "{source}");
+            }
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! Saving a paused program and restoring it later, in another process or in
+//! the browser.
+//!
+//! A running program lives in the recursion of the interpreter, which can't
+//! be saved as it is. A [`Snapshot`] holds what makes the run reproducible
+//! instead: the source, the seed of the random numbers, the lines `ввод`
+//! read, the times `время` returned and how many statements were entered.
+//! [`crate::interpreter::Interpreter::restore`] runs the program again with
+//! them, without output, waits or stops, up to the same statement. There the
+//! call stack, the variables and the position must be the saved ones, and
+//! the program stops with [`crate::debugger::StopReason::Restored`].
+//!
+//! Only the program is replayed, the world around it is not saved. Actors
+//! and files see the replayed commands again, so restoring is right only
+//! when they are in the state they were in at the start of the saved run:
+//! the host puts them back, e.g. the robot on its starting field and the
+//! files as they were, and tells the interpreter with
+//! [`crate::interpreter::Interpreter::actors_reset`]. Until then an
+//! interpreter that ran actor commands refuses to restore. The program must
+//! also be deterministic apart from the input, `время` and the random
+//! numbers, which the snapshot holds.
+
+use std::{cell::RefCell, collections::VecDeque, fmt, rc::Rc, str::FromStr, time::Duration};
+
+use crate::{
+    ast::Environment,
+    clock::Clock,
+    inspector::{self, ProgramState, VariableView},
+    messages::{Error, Message},
+};
+
+const HEADER: &str = "kumir-snapshot 1";
+
+/// State of a paused program, written as text by `Display` and read back by
+/// `FromStr`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Snapshot {
+    pub source: String,
+    pub seed: u64,
+    /// Statements entered up to the one the program is paused at
+    pub steps: usize,
+    /// Lines read from the keyboard
+    pub input: Vec<String>,
+    /// Times of day `время` returned
+    pub times: Vec<Duration>,
+    /// Position, call stack and variables, see [`state_lines`]
+    pub state: Vec<String>,
+}
+
+impl Snapshot {
+    /// Snapshot of the program `environment` belongs to, meant to be called
+    /// from a [`crate::debugger::StopHandler`] with the environment it was
+    /// given
+    pub fn take(environment: &Rc<RefCell<Environment>>) -> Snapshot {
+        let journal = environment.borrow().io.journal();
+        let journal = journal.borrow();
+        let steps = environment.borrow().debugger.borrow().steps();
+        let seed = environment.borrow().random.borrow().seed();
+        Snapshot {
+            source: journal.source.clone(),
+            seed,
+            steps,
+            input: journal.input.clone(),
+            times: journal.times.clone(),
+            state: state_lines(&inspector::inspect(environment)),
+        }
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "steps {}", self.steps)?;
+        for line in &self.input {
+            writeln!(f, "input {}", escape(line))?;
+        }
+        for time in &self.times {
+            writeln!(f, "time {}", time.as_nanos())?;
+        }
+        for line in &self.state {
+            writeln!(f, "state {line}")?;
+        }
+        writeln!(f, "source")?;
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = |line: &str| Error::from(Message::InvalidSnapshot(line.to_string()));
+        let mut lines = text.split_inclusive('\n');
+        match lines.next() {
+            Some(line) if line.trim_end() == HEADER => {}
+            line => return Err(invalid(line.unwrap_or_default())),
+        }
+        let mut snapshot = Snapshot::default();
+        for line in lines.by_ref() {
+            let line = line.strip_suffix('\n').unwrap_or(line);
+            if line == "source" {
+                break;
+            }
+            let (key, value) = line.split_once(' ').ok_or_else(|| invalid(line))?;
+            match key {
+                "seed" => snapshot.seed = value.parse().map_err(|_| invalid(line))?,
+                "steps" => snapshot.steps = value.parse().map_err(|_| invalid(line))?,
+                "input" => snapshot.input.push(unescape(value)),
+                "time" => {
+                    let nanos: u64 = value.parse().map_err(|_| invalid(line))?;
+                    snapshot.times.push(Duration::from_nanos(nanos));
+                }
+                "state" => snapshot.state.push(value.to_string()),
+                _ => return Err(invalid(line)),
+            }
+        }
+        snapshot.source = lines.collect();
+        Ok(snapshot)
+    }
+}
+
+/// Position, call stack and variables of `state`, one per line, as stored
+/// in a [`Snapshot`]
+pub fn state_lines(state: &ProgramState) -> Vec<String> {
+    let variable = |prefix: &str, variable: &VariableView| {
+        format!(
+            "{prefix}{} {} = {}",
+            variable.type_name(),
+            variable.name,
+            escape(&variable.value_text())
+        )
+    };
+    let mut lines = vec![format!("at {}", state.span)];
+    lines.extend(
+        state
+            .globals
+            .iter()
+            .map(|global| variable("global ", global)),
+    );
+    for frame in &state.call_stack {
+        lines.push(format!("call {} at {}", frame.name, frame.call_site));
+        lines.extend(
+            frame
+                .parameters
+                .iter()
+                .map(|parameter| variable("param ", &parameter.variable)),
+        );
+        lines.extend(frame.locals.iter().map(|local| variable("local ", local)));
+    }
+    lines
+}
+
+/// Line breaks of texts, so a value stays on its line
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// What a run got from outside the program, shared through
+/// [`crate::io::Io`]. While a snapshot is replayed its input and times are
+/// used up first and the screen stays untouched
+#[derive(Debug, Default)]
+pub struct Journal {
+    /// Source of the loaded program
+    pub source: String,
+    input: Vec<String>,
+    times: Vec<Duration>,
+    replay: Option<(VecDeque<String>, VecDeque<Duration>)>,
+    /// Actor commands run since the host last reset the actors
+    actor_commands: usize,
+}
+
+impl Journal {
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Forgets the input and times of the previous run
+    pub(crate) fn clear(&mut self) {
+        self.input.clear();
+        self.times.clear();
+    }
+
+    pub(crate) fn actor_called(&mut self) {
+        self.actor_commands += 1;
+    }
+
+    pub(crate) fn actors_reset(&mut self) {
+        self.actor_commands = 0;
+    }
+
+    /// Whether actors are still as the host set them up
+    pub fn actors_untouched(&self) -> bool {
+        self.actor_commands == 0
+    }
+
+    pub(crate) fn start_replay(&mut self, snapshot: &Snapshot) {
+        self.replay = Some((
+            snapshot.input.iter().cloned().collect(),
+            snapshot.times.iter().copied().collect(),
+        ));
+    }
+
+    pub(crate) fn finish_replay(&mut self) {
+        self.replay = None;
+    }
+
+    /// Next line of the replay or the one `read` gives
+    pub(crate) fn read_line(
+        &mut self,
+        read: impl FnOnce() -> Result<String, Error>,
+    ) -> Result<String, Error> {
+        let replayed = self
+            .replay
+            .as_mut()
+            .and_then(|(input, _)| input.pop_front());
+        let line = match replayed {
+            Some(line) => line,
+            None => read()?,
+        };
+        self.input.push(line.clone());
+        Ok(line)
+    }
+
+    /// Next time of the replay or the one of `clock`
+    pub(crate) fn time_of_day(&mut self, clock: &dyn Clock) -> Duration {
+        let replayed = self
+            .replay
+            .as_mut()
+            .and_then(|(_, times)| times.pop_front());
+        let time = replayed.unwrap_or_else(|| clock.time_of_day());
+        self.times.push(time);
+        time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::Namespace,
+        clock::VirtualClock,
+        debugger::{StepMode, Stop, StopReason},
+        interpreter::Interpreter,
+        io::testing::interpreter,
+        stdlib,
+    };
+
+    const LAMP: &str = "использовать Лампа\nвключить\nвывод \"да\"";
+
+    fn with_lamp(source: &str) -> Interpreter {
+        let (mut interpreter, _) = interpreter(source, &[]);
+        let mut lamp = Namespace::default();
+        lamp.register_native_function("включить", stdlib::native(&[], None, |_| Ok(None)));
+        interpreter.register_namespace("Лампа", lamp);
+        interpreter
+    }
+
+    fn snapshot(source: &str) -> Snapshot {
+        Snapshot {
+            source: source.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn restore_refuses_until_actors_are_reset() {
+        let mut interpreter = with_lamp(LAMP);
+        interpreter.run().unwrap();
+        assert_eq!(
+            interpreter.restore(&snapshot(LAMP)),
+            Err(Message::ActorsNotReset.into())
+        );
+        interpreter.actors_reset();
+        assert_eq!(interpreter.restore(&snapshot(LAMP)), Ok(()));
+    }
+
+    #[test]
+    fn restore_ignores_stateless_actors() {
+        let source = "использовать Строки\nлит с := \"ав\"\nвставить(\"б\", с, 2)";
+        let (mut interpreter, _) = interpreter(source, &[]);
+        interpreter.run().unwrap();
+        assert_eq!(interpreter.restore(&snapshot(source)), Ok(()));
+    }
+
+    #[test]
+    fn snapshot_reads_back_what_it_wrote() {
+        let snapshot = Snapshot {
+            seed: 7,
+            steps: 3,
+            input: vec!["5".to_string()],
+            times: vec![Duration::from_millis(1500)],
+            ..snapshot(LAMP)
+        };
+        assert_eq!(snapshot.to_string().parse::<Snapshot>(), Ok(snapshot));
+    }
+
+    const GREETING: &str = "алг
+нач
+  лит имя
+  цел т
+  ввод имя
+  т := время
+  показать(имя, т)
+кон
+
+алг показать(лит с, цел м)
+нач
+  цел к
+  к := м + 1
+  вывод с, к
+кон
+";
+
+    /// Snapshot of `source` paused at `line`, run on `input` at `time`
+    fn take_at(source: &str, input: &[&str], time: Duration, line: usize) -> Snapshot {
+        let (mut interpreter, _) = interpreter(source, input);
+        interpreter.set_clock(Rc::new(VirtualClock::new(time)));
+        interpreter.set_breakpoint(line);
+        let taken = Rc::new(RefCell::new(None));
+        let snapshot = taken.clone();
+        interpreter.attach_debugger(move |_: &Stop, environment: &Rc<RefCell<Environment>>| {
+            snapshot.replace(Some(Snapshot::take(environment)));
+            StepMode::Continue
+        });
+        interpreter.run().unwrap();
+        taken.take().unwrap()
+    }
+
+    /// Where a program stopped, with its state there
+    type Stops = Vec<(StopReason, Vec<String>)>;
+
+    /// Restores `snapshot` read back from its text in an interpreter without
+    /// input and at another time. Gives the stops with their state, and the
+    /// output after the restored statement
+    fn restore(snapshot: &Snapshot) -> Result<(Stops, String), Error> {
+        let snapshot: Snapshot = snapshot.to_string().parse()?;
+        let (mut interpreter, output) = interpreter("", &[]);
+        interpreter.set_clock(Rc::new(VirtualClock::new(Duration::ZERO)));
+        interpreter.restore(&snapshot)?;
+        let stops = Rc::new(RefCell::new(vec![]));
+        let recorded = stops.clone();
+        interpreter.attach_debugger(move |stop: &Stop, environment: &Rc<RefCell<Environment>>| {
+            let state = state_lines(&inspector::inspect(environment));
+            recorded.borrow_mut().push((stop.reason, state));
+            StepMode::Continue
+        });
+        interpreter.run().map_err(|err| err.message)?;
+        Ok((stops.take(), output.take()))
+    }
+
+    #[test]
+    fn restored_program_stops_where_it_was_taken() {
+        let snapshot = take_at(GREETING, &["Аня"], Duration::from_millis(1234), 14);
+        assert_eq!(snapshot.steps, 11);
+        assert_eq!(snapshot.input, ["Аня"]);
+        assert_eq!(
+            snapshot.state,
+            [
+                "at 14:3",
+                "call main at 1:1",
+                "local лит имя = \"Аня\"",
+                "local цел т = 1234",
+                "call показать at 7:3",
+                "param лит с = \"Аня\"",
+                "param цел м = 1234",
+                "local цел к = 1235",
+            ]
+        );
+
+        let (stops, output) = restore(&snapshot).unwrap();
+        assert_eq!(stops, [(StopReason::Restored, snapshot.state.clone())]);
+        assert_eq!(output, "Аня1235");
+    }
+
+    #[test]
+    fn restore_fails_in_another_state() {
+        let snapshot = take_at(GREETING, &["Аня"], Duration::from_millis(1234), 14);
+        let mismatch = Err(Message::SnapshotMismatch.into());
+
+        let other_input = Snapshot {
+            input: vec!["Боб".to_string()],
+            ..snapshot.clone()
+        };
+        assert_eq!(restore(&other_input), mismatch);
+
+        let mut other_state = snapshot.clone();
+        other_state.state.pop();
+        assert_eq!(restore(&other_state), mismatch);
+
+        let past_the_end = Snapshot {
+            steps: 100,
+            ..snapshot
+        };
+        assert_eq!(restore(&past_the_end), mismatch);
+    }
+
+    #[test]
+    fn input_with_line_breaks_and_backslashes_is_replayed() {
+        let line = "C:\\новая\\папка\nи \\n вторая строка\\";
+        let snapshot = take_at(GREETING, &[line], Duration::ZERO, 14);
+        let text = snapshot.to_string();
+        assert_eq!(
+            text.lines()
+                .filter(|line| line.starts_with("input "))
+                .count(),
+            1
+        );
+        assert!(text.contains("input C:\\\\новая\\\\папка\\nи \\\\n вторая строка\\\\\n"));
+
+        let (stops, output) = restore(&snapshot).unwrap();
+        assert_eq!(stops, [(StopReason::Restored, snapshot.state)]);
+        assert!(output.ends_with("вторая строка\\1"));
+    }
+
+    #[test]
+    fn time_is_replayed() {
+        let snapshot = take_at(GREETING, &["Аня"], Duration::from_millis(86_000), 14);
+        assert_eq!(snapshot.times, [Duration::from_millis(86_000)]);
+        assert!(snapshot.to_string().contains("time 86000000000\n"));
+
+        let (stops, output) = restore(&snapshot).unwrap();
+        assert_eq!(stops, [(StopReason::Restored, snapshot.state)]);
+        assert_eq!(output, "Аня86001");
+    }
+}
@@ -0,0 +1,262 @@
+//! Actor `Комплексные числа`, see `userdocs/md_output/ActorComplexnumbers.md`.
+//! Adds the type `компл`, written `a*i + b` or `ai + b`, with `+ - * /`
+//! between complex numbers and `цел` or `вещ` values, which are complex
+//! numbers too. Complex numbers are only compared with `==` and `<>`
+
+use std::fmt;
+
+use crate::{
+    ast::{Literal, Namespace},
+    lexer::{
+        Operator,
+        TypeDefinition::{self, Float},
+    },
+    messages::{Error, Message},
+    types::{CustomType, CustomValue},
+};
+
+use super::{float, native};
+
+pub const NAME: &str = "Комплексные числа";
+
+const COMPLEX: TypeDefinition = TypeDefinition::Custom(Complex::TYPE);
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub const TYPE: &'static str = "компл";
+
+    /// `цел` and `вещ` values are complex numbers with no imaginary part
+    pub fn from_literal(value: &Literal) -> Option<Complex> {
+        match value {
+            Literal::Int(_) | Literal::Float(_) => Some(Complex {
+                re: float(value),
+                im: 0.0,
+            }),
+            value => value.as_custom().copied(),
+        }
+    }
+
+    /// Parses `a*i + b`, `ai+b`, `b - i`, `2.5` or `3i`, spaces are ignored
+    pub fn parse(text: &str) -> Option<Complex> {
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut complex = Complex { re: 0.0, im: 0.0 };
+        let mut term_start = 0;
+        for (i, c) in text.char_indices().chain([(text.len(), '+')]) {
+            //A sign starts the next term unless it's the one of an exponent
+            if i == term_start || !matches!(c, '+' | '-') || text[..i].ends_with(['e', 'E']) {
+                continue;
+            }
+            let term = &text[term_start..i];
+            term_start = i;
+            let (sign, term) = match term.strip_prefix('-') {
+                Some(term) => (-1.0, term),
+                None => (1.0, term.strip_prefix('+').unwrap_or(term)),
+            };
+            let imaginary = ["*i", "i"]
+                .into_iter()
+                .find_map(|suffix| term.strip_suffix(suffix))
+                .or_else(|| term.strip_prefix("i*"));
+            match imaginary {
+                Some("") => complex.im += sign,
+                Some(number) => complex.im += sign * number.parse::<f32>().ok()?,
+                None => complex.re += sign * term.parse::<f32>().ok()?,
+            }
+        }
+        (term_start == text.len() && !text.is_empty()).then_some(complex)
+    }
+
+    fn checked(self) -> Result<Literal, Error> {
+        if self.re.is_infinite() || self.im.is_infinite() {
+            return Err(Message::FloatOverflow.into());
+        }
+        Ok(Literal::custom(self))
+    }
+}
+
+/// Real part first as in mathematics: `2+8i`, `0.5-i`
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Complex { re, im } = *self;
+        match im {
+            1.0 => write!(f, "{re}+i"),
+            -1.0 => write!(f, "{re}-i"),
+            im if im.is_sign_negative() => write!(f, "{re}-{}i", -im),
+            im => write!(f, "{re}+{im}i"),
+        }
+    }
+}
+
+impl CustomValue for Complex {
+    fn type_name(&self) -> &'static str {
+        Self::TYPE
+    }
+
+    fn equals(&self, other: &dyn CustomValue) -> bool {
+        other.downcast_ref() == Some(self)
+    }
+
+    fn to_output_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+pub fn namespace() -> Namespace {
+    let mut namespace = Namespace::default();
+
+    namespace.register_type(
+        CustomType::new(Complex::TYPE)
+            .with_operators(|left, op, right| {
+                let (left, right) = (Complex::from_literal(left)?, Complex::from_literal(right)?);
+                Some(operation(left, op, right)?.and_then(Complex::checked))
+            })
+            .with_conversion(|value| Complex::from_literal(value).map(Literal::custom))
+            .with_parser(|text| Complex::parse(text).map(Literal::custom)),
+    );
+
+    namespace.register_native_function(
+        "i",
+        native(&[], Some(COMPLEX), |_| {
+            Ok(Some(Literal::custom(Complex { re: 0.0, im: 1.0 })))
+        }),
+    );
+    namespace.register_native_function(
+        "Компл",
+        native(&[("re", Float), ("im", Float)], Some(COMPLEX), |args| {
+            let (re, im) = (float(&args[0]), float(&args[1]));
+            Ok(Some(Literal::custom(Complex { re, im })))
+        }),
+    );
+
+    let part = |namespace: &mut Namespace, name: &str, function: fn(Complex) -> f32| {
+        namespace.register_native_function(
+            name,
+            native(&[("x", COMPLEX)], Some(Float), move |args| {
+                let x = Complex::from_literal(&args[0]).unwrap_or(Complex { re: 0.0, im: 0.0 });
+                Ok(Some(Literal::Float(function(x))))
+            }),
+        );
+    };
+    part(&mut namespace, "Re", |x| x.re);
+    part(&mut namespace, "Im", |x| x.im);
+    //Capitalized like `Re` and `Im`, `abs` of `Математика` stays real
+    part(&mut namespace, "Abs", |x| x.re.hypot(x.im));
+    part(&mut namespace, "Arg", |x| x.im.atan2(x.re));
+
+    namespace
+}
+
+fn operation(left: Complex, op: Operator, right: Complex) -> Option<Result<Complex, Error>> {
+    let Complex { re: a, im: b } = left;
+    let Complex { re: c, im: d } = right;
+    Some(Ok(match op {
+        Operator::Plus => Complex {
+            re: a + c,
+            im: b + d,
+        },
+        Operator::Minus => Complex {
+            re: a - c,
+            im: b - d,
+        },
+        Operator::Multiply => Complex {
+            re: a * c - b * d,
+            im: a * d + b * c,
+        },
+        Operator::Divide => {
+            let denominator = c * c + d * d;
+            if denominator == 0.0 {
+                return Some(Err(Message::DivisionByZero.into()));
+            }
+            Complex {
+                re: (a * c + b * d) / denominator,
+                im: (b * c - a * d) / denominator,
+            }
+        }
+        _ => return None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::io::testing::run;
+
+    fn complex(body: &str, input: &[&str]) -> Result<String, Error> {
+        run(&format!("использовать {NAME}\n{body}"), input)
+    }
+
+    #[test]
+    fn documented_example() {
+        let source = "алг\nнач\n  компл а = 1i + 1\n  компл б = 2*i + 4\n  вывод а * (а + б)\nкон";
+        assert_eq!(complex(source, &[]), Ok("2+8i".to_string()));
+    }
+
+    #[test]
+    fn parses_the_documented_notation() {
+        let cases = [
+            ("1i + 1", Some((1.0, 1.0))),
+            ("2*i + 4", Some((4.0, 2.0))),
+            ("0.5-i", Some((0.5, -1.0))),
+            ("-3i", Some((0.0, -3.0))),
+            ("2.5", Some((2.5, 0.0))),
+            ("1e-3+i*2", Some((0.001, 2.0))),
+            ("", None),
+            ("2+", None),
+            ("один", None),
+        ];
+        for (text, expected) in cases {
+            let expected = expected.map(|(re, im)| Complex { re, im });
+            assert_eq!(Complex::parse(text), expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn reads_like_it_is_written() {
+        assert_eq!(
+            complex("компл а\nввод а\nвывод а * 2", &["1i + 1"]),
+            Ok("2+2i".to_string())
+        );
+    }
+
+    #[test]
+    fn parts() {
+        assert_eq!(
+            complex("компл а = 2*i + 4\nвывод Re(а), \" \", Im(а)", &[]),
+            Ok("4 2".to_string())
+        );
+        assert_eq!(
+            complex("вывод Abs(3 + 4*i), \" \", Arg(i)", &[]),
+            Ok(format!("5 {FRAC_PI_2}"))
+        );
+    }
+
+    #[test]
+    fn arithmetic_with_real_operands() {
+        let source =
+            "компл а = 1 + i\nвывод а + 1, \" \", а - 0.5, \" \", а * 2, \" \", а / (1 - i)";
+        assert_eq!(complex(source, &[]), Ok("2+i 0.5+i 2+2i 0+i".to_string()));
+        assert_eq!(
+            complex("компл а = 1 + i\nвывод а / 0", &[]),
+            Err(Message::DivisionByZero.into())
+        );
+    }
+
+    #[test]
+    fn real_values_are_complex() {
+        assert_eq!(
+            complex("компл а\nа := 3\nвывод а, \" \"\nа := 2.5\nвывод а", &[]),
+            Ok("3+0i 2.5+0i".to_string())
+        );
+    }
+
+    #[test]
+    fn needs_import() {
+        assert!(run("вывод Re(i)", &[]).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! Actor `Файлы`, see `userdocs/md_output/files.md`. Works on the program's
+//! [`Files`], so the host decides which filesystem it sees
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    ast::{Literal, Namespace},
+    fs::{FileHandle, Files, OpenMode},
+    lexer::TypeDefinition::{self, Bool, String},
+    messages::Error,
+    types::CustomType,
+};
+
+use super::{native, string};
+
+pub const NAME: &str = "Файлы";
+
+const FILE: TypeDefinition = TypeDefinition::Custom(FileHandle::TYPE);
+
+type Command = fn(&mut Files, &[Literal]) -> Result<Option<Literal>, Error>;
+
+pub fn namespace(files: Rc<RefCell<Files>>) -> Namespace {
+    let mut namespace = Namespace::default();
+    namespace.register_type(CustomType::new(FileHandle::TYPE));
+
+    for (name, mode) in [
+        ("открыть на чтение", OpenMode::Read),
+        ("открыть на запись", OpenMode::Write),
+        ("открыть на добавление", OpenMode::Append),
+    ] {
+        let files = files.clone();
+        namespace.register_native_function(
+            name,
+            native(&[("имя файла", String)], Some(FILE), move |args| {
+                let handle = files.borrow_mut().open(string(&args[0]), mode)?;
+                Ok(Some(Literal::custom(handle)))
+            }),
+        );
+    }
+
+    let mut command = |name: &str,
+                       params: &[(&str, TypeDefinition)],
+                       return_type: Option<TypeDefinition>,
+                       command: Command| {
+        let files = files.clone();
+        namespace.register_native_function(
+            name,
+            native(params, return_type, move |args| {
+                command(&mut files.borrow_mut(), args)
+            }),
+        );
+    };
+
+    command("закрыть", &[("ф", FILE)], None, |files, args| {
+        files.close(handle(&args[0]))?;
+        Ok(None)
+    });
+    command(
+        "начать чтение",
+        &[("ф", FILE)],
+        None,
+        |files, args| {
+            files.rewind(handle(&args[0]))?;
+            Ok(None)
+        },
+    );
+    command(
+        "конец файла",
+        &[("ф", FILE)],
+        Some(Bool),
+        |files, args| Ok(Some(Literal::Bool(files.at_end(handle(&args[0]))?))),
+    );
+    command(
+        "есть данные",
+        &[("ф", FILE)],
+        Some(Bool),
+        |files, args| Ok(Some(Literal::Bool(files.has_data(handle(&args[0]))?))),
+    );
+    command("консоль", &[], Some(FILE), |_, _| {
+        Ok(Some(Literal::custom(FileHandle::CONSOLE)))
+    });
+    command(
+        "установить кодировку",
+        &[("имя кодировки", String)],
+        None,
+        |files, args| {
+            files.set_encoding(string(&args[0]))?;
+            Ok(None)
+        },
+    );
+
+    command(
+        "можно открыть на чтение",
+        &[("имя файла", String)],
+        Some(Bool),
+        |files, args| Ok(Some(Literal::Bool(files.can_read(string(&args[0]))))),
+    );
+    command(
+        "можно открыть на запись",
+        &[("имя файла", String)],
+        Some(Bool),
+        |files, args| Ok(Some(Literal::Bool(files.can_write(string(&args[0]))))),
+    );
+    command(
+        "существует",
+        &[("имя", String)],
+        Some(Bool),
+        |files, args| {
+            let metadata = files.metadata(string(&args[0]));
+            Ok(Some(Literal::Bool(metadata.is_some())))
+        },
+    );
+    command(
+        "является каталогом",
+        &[("имя", String)],
+        Some(Bool),
+        |files, args| {
+            let metadata = files.metadata(string(&args[0]));
+            Ok(Some(Literal::Bool(
+                metadata.is_some_and(|metadata| metadata.is_dir),
+            )))
+        },
+    );
+    command(
+        "создать каталог",
+        &[("имя каталога", String)],
+        Some(Bool),
+        |files, args| Ok(Some(Literal::Bool(files.create_dir(string(&args[0]))))),
+    );
+    command(
+        "удалить_файл",
+        &[("имя файла", String)],
+        Some(Bool),
+        |files, args| Ok(Some(Literal::Bool(files.remove_file(string(&args[0]))))),
+    );
+    command(
+        "удалить_каталог",
+        &[("имя каталога", String)],
+        Some(Bool),
+        |files, args| Ok(Some(Literal::Bool(files.remove_dir(string(&args[0]))))),
+    );
+    command(
+        "полный путь",
+        &[("имя", String)],
+        Some(String),
+        |files, args| Ok(Some(Literal::String(files.full_path(string(&args[0]))?))),
+    );
+    command(
+        "РАБОЧИЙ КАТАЛОГ",
+        &[],
+        Some(String),
+        |files, _| Ok(Some(Literal::String(files.working_dir().to_string()))),
+    );
+    //Programs aren't saved into the virtual filesystem
+    command(
+        "КАТАЛОГ ПРОГРАММЫ",
+        &[],
+        Some(String),
+        |_, _| Ok(Some(Literal::String("./".to_string()))),
+    );
+    command(
+        "НАЗНАЧИТЬ ВВОД",
+        &[("имя файла", String)],
+        None,
+        |files, args| {
+            files.redirect_input(string(&args[0]))?;
+            Ok(None)
+        },
+    );
+    command(
+        "НАЗНАЧИТЬ ВЫВОД",
+        &[("имя файла", String)],
+        None,
+        |files, args| {
+            files.redirect_output(string(&args[0]))?;
+            Ok(None)
+        },
+    );
+
+    namespace
+}
+
+fn handle(value: &Literal) -> FileHandle {
+    value.as_custom().copied().unwrap_or(FileHandle::CONSOLE)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fs::MemoryFs, io::testing::interpreter};
+
+    /// Output of `source` run on `fs`
+    fn run(source: &str, fs: &MemoryFs) -> String {
+        let (mut interpreter, output) = interpreter(source, &[]);
+        interpreter.set_fs(fs.clone());
+        interpreter.run().unwrap();
+        output.take()
+    }
+
+    #[test]
+    fn input_and_output_are_redirected() {
+        let fs = MemoryFs::default();
+        fs.insert("/in.txt", "2 3\n");
+        let output = run(
+            "использовать Файлы\n\
+             цел а, б\n\
+             НАЗНАЧИТЬ ВВОД(\"in.txt\")\n\
+             ввод а, б\n\
+             НАЗНАЧИТЬ ВЫВОД(\"out.txt\")\n\
+             вывод а + б, нс\n\
+             НАЗНАЧИТЬ ВЫВОД(\"\")\n\
+             НАЗНАЧИТЬ ВВОД(\"\")\n\
+             вывод \"готово\"\n",
+            &fs,
+        );
+        assert_eq!(output, "готово");
+        assert_eq!(fs.get("/out.txt").unwrap(), b"5\n");
+    }
+
+    #[test]
+    fn files_are_written_and_read_in_their_encoding() {
+        for (name, bytes) in [
+            ("CP1251", [0xCF, 0xA8, 0xE6]),
+            ("CP866", [0x8F, 0xF0, 0xA6]),
+            ("KOI8-R", [0xF0, 0xB3, 0xD6]),
+        ] {
+            let fs = MemoryFs::default();
+            let source = format!(
+                "использовать Файлы\n\
+                 установить кодировку(\"{name}\")\n\
+                 файл ф\n\
+                 ф := открыть на запись(\"текст.txt\")\n\
+                 вывод ф, \"ПЁж\"\n\
+                 закрыть(ф)\n\
+                 лит с\n\
+                 ф := открыть на чтение(\"текст.txt\")\n\
+                 ввод ф, с\n\
+                 закрыть(ф)\n\
+                 вывод с\n"
+            );
+            assert_eq!(run(&source, &fs), "ПЁж", "{name}");
+            assert_eq!(fs.get("/текст.txt").unwrap(), bytes, "{name}");
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Mathematical functions, see `userdocs/md_output/math.md`
+
+use std::f32::consts::FRAC_PI_2;
+
+use crate::{
+    ast::{Literal, Namespace},
+    lexer::TypeDefinition::{Float, Int},
+    messages::{Error, Message},
+};
+
+use super::{float, int, native, native_with_env};
+
+pub const NAME: &str = "Математика";
+
+pub fn namespace() -> Namespace {
+    let mut namespace = Namespace::default();
+
+    let real =
+        |namespace: &mut Namespace, name: &'static str, function: fn(f32) -> Result<f32, Error>| {
+            namespace.register_native_function(
+                name,
+                native(&[("x", Float)], Some(Float), move |args| {
+                    real_result(function(float(&args[0]))?)
+                }),
+            );
+        };
+    real(&mut namespace, "sqrt", |x| {
+        if x < 0.0 {
+            return Err(Message::SqrtOfNegative.into());
+        }
+        Ok(x.sqrt())
+    });
+    real(&mut namespace, "abs", |x| Ok(x.abs()));
+    real(&mut namespace, "sin", |x| Ok(x.sin()));
+    real(&mut namespace, "cos", |x| Ok(x.cos()));
+    real(&mut namespace, "tg", |x| Ok(x.tan()));
+    real(&mut namespace, "ctg", |x| {
+        let tan = x.tan();
+        if tan == 0.0 {
+            return Err(Message::DivisionByZero.into());
+        }
+        Ok(1.0 / tan)
+    });
+    real(&mut namespace, "arcsin", |x| {
+        if !(-1.0..=1.0).contains(&x) {
+            return Err(Message::OutOfRange("arcsin".to_string()).into());
+        }
+        Ok(x.asin())
+    });
+    real(&mut namespace, "arccos", |x| {
+        if !(-1.0..=1.0).contains(&x) {
+            return Err(Message::OutOfRange("arccos".to_string()).into());
+        }
+        Ok(x.acos())
+    });
+    real(&mut namespace, "arctg", |x| Ok(x.atan()));
+    real(&mut namespace, "arcctg", |x| Ok(FRAC_PI_2 - x.atan()));
+    real(&mut namespace, "ln", |x| {
+        if x <= 0.0 {
+            return Err(Message::LogOfNonPositive.into());
+        }
+        Ok(x.ln())
+    });
+    real(&mut namespace, "lg", |x| {
+        if x <= 0.0 {
+            return Err(Message::LogOfNonPositive.into());
+        }
+        Ok(x.log10())
+    });
+    real(&mut namespace, "exp", |x| Ok(x.exp()));
+
+    namespace.register_native_function(
+        "iabs",
+        native(&[("x", Int)], Some(Int), |args| {
+            int_result(int(&args[0]).checked_abs())
+        }),
+    );
+    namespace.register_native_function(
+        "sign",
+        native(&[("x", Float)], Some(Int), |args| {
+            let x = float(&args[0]);
+            let sign = if x > 0.0 {
+                1
+            } else if x < 0.0 {
+                -1
+            } else {
+                0
+            };
+            Ok(Some(Literal::Int(sign)))
+        }),
+    );
+    namespace.register_native_function(
+        "int",
+        native(&[("x", Float)], Some(Int), |args| {
+            let x = float(&args[0]).floor();
+            if !(i32::MIN as f32..=i32::MAX as f32).contains(&x) {
+                return Err(Message::IntOverflow.into());
+            }
+            Ok(Some(Literal::Int(x as i32)))
+        }),
+    );
+
+    let real_pair =
+        |namespace: &mut Namespace, name: &'static str, function: fn(f32, f32) -> f32| {
+            namespace.register_native_function(
+                name,
+                native(&[("x", Float), ("y", Float)], Some(Float), move |args| {
+                    real_result(function(float(&args[0]), float(&args[1])))
+                }),
+            );
+        };
+    real_pair(&mut namespace, "min", f32::min);
+    real_pair(&mut namespace, "max", f32::max);
+
+    let int_pair =
+        |namespace: &mut Namespace, name: &'static str, function: fn(i32, i32) -> Option<i32>| {
+            namespace.register_native_function(
+                name,
+                native(&[("x", Int), ("y", Int)], Some(Int), move |args| {
+                    let y = int(&args[1]);
+                    if y == 0 && matches!(name, "div" | "mod") {
+                        return Err(Message::DivisionByZero.into());
+                    }
+                    int_result(function(int(&args[0]), y))
+                }),
+            );
+        };
+    int_pair(&mut namespace, "imin", |x, y| Some(x.min(y)));
+    int_pair(&mut namespace, "imax", |x, y| Some(x.max(y)));
+    //The remainder is never negative, so x = div(x, y) * y + mod(x, y)
+    int_pair(&mut namespace, "div", i32::checked_div_euclid);
+    int_pair(&mut namespace, "mod", i32::checked_rem_euclid);
+
+    namespace.register_native_function(
+        "МАКСЦЕЛ",
+        native(&[], Some(Int), |_| Ok(Some(Literal::Int(i32::MAX)))),
+    );
+    //`вещ` values are single precision
+    namespace.register_native_function(
+        "МАКСВЕЩ",
+        native(&[], Some(Float), |_| Ok(Some(Literal::Float(f32::MAX)))),
+    );
+
+    namespace.register_native_function(
+        "rnd",
+        native_with_env(&[("x", Float)], Some(Float), |environment, args| {
+            let value = environment.random.borrow_mut().float(0.0, float(&args[0]));
+            Ok(Some(Literal::Float(value)))
+        }),
+    );
+    namespace.register_native_function(
+        "rand",
+        native_with_env(
+            &[("x", Float), ("y", Float)],
+            Some(Float),
+            |environment, args| {
+                let value = environment
+                    .random
+                    .borrow_mut()
+                    .float(float(&args[0]), float(&args[1]));
+                Ok(Some(Literal::Float(value)))
+            },
+        ),
+    );
+    namespace.register_native_function(
+        "irnd",
+        native_with_env(&[("x", Int)], Some(Int), |environment, args| {
+            let value = environment.random.borrow_mut().int(0, int(&args[0]));
+            Ok(Some(Literal::Int(value)))
+        }),
+    );
+    namespace.register_native_function(
+        "irand",
+        native_with_env(&[("x", Int), ("y", Int)], Some(Int), |environment, args| {
+            let value = environment
+                .random
+                .borrow_mut()
+                .int(int(&args[0]), int(&args[1]));
+            Ok(Some(Literal::Int(value)))
+        }),
+    );
+
+    namespace
+}
+
+fn real_result(value: f32) -> Result<Option<Literal>, Error> {
+    if value.is_infinite() {
+        return Err(Message::FloatOverflow.into());
+    }
+    Ok(Some(Literal::Float(value)))
+}
+
+fn int_result(value: Option<i32>) -> Result<Option<Literal>, Error> {
+    value
+        .map(|value| Some(Literal::Int(value)))
+        .ok_or(Message::IntOverflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use super::*;
+    use crate::io::testing::run;
+
+    /// Output of a documented example with its variables declared inside
+    /// the algorithm, it is run as `алг` without a name
+    fn example(declarations: &str, body: &str, input: &[&str]) -> Result<String, Error> {
+        run(&format!("алг\nнач\n{declarations}\n{body}\nкон\n"), input)
+    }
+
+    /// Output of the one-argument example of `function`, where the result
+    /// is printed after `text`
+    fn real_example(function: &str, text: &str, input: &str) -> Result<String, Error> {
+        let body = format!("ввод х\nх := {function} (х)\nвывод \"{text} \", х");
+        example("вещ х", &body, &[input])
+    }
+
+    #[test]
+    fn sqrt() {
+        let text = "корень квадратный из числа х равен";
+        assert_eq!(real_example("sqrt", text, "9"), Ok(format!("{text} 3")));
+        assert_eq!(
+            real_example("sqrt", text, "-1"),
+            Err(Message::SqrtOfNegative.into())
+        );
+    }
+
+    #[test]
+    fn abs() {
+        let body = "ввод а,б\nа := а+б\nа := abs(а)\nвывод \"Модуль суммы чисел равен \", а";
+        assert_eq!(
+            example("вещ а, б", body, &["2 -5.5"]),
+            Ok("Модуль суммы чисел равен 3.5".to_string())
+        );
+    }
+
+    #[test]
+    fn iabs() {
+        let body = "ввод а,б\nа := iabs(а)\nб := iabs(б)\nвывод а+б";
+        assert_eq!(example("цел а, б", body, &["-3 4"]), Ok("7".to_string()));
+    }
+
+    #[test]
+    fn sign() {
+        //`=` and the unary minus of the example are spelled `==` and `0 - 1`
+        let body = "ввод а\nб := sign(а)\nесли б == 0 - 1\nто вывод а, \"<=0\"\nиначе\nесли б == 0\nто вывод а, \"=0\"\nиначе вывод а, \">=0\"\nвсе\nвсе";
+        for (input, output) in [("-7", "-7<=0"), ("0", "0=0"), ("7", "7>=0")] {
+            assert_eq!(example("цел а, б", body, &[input]), Ok(output.to_string()));
+        }
+    }
+
+    #[test]
+    fn trigonometry() {
+        let cases = [
+            ("sin", "синус угла х равен", 0.5f32.sin()),
+            ("cos", "косинус угла х равен", 0.5f32.cos()),
+            ("tg", "тангенс угла х равен", 0.5f32.tan()),
+            ("ctg", "котангенс угла х равен", 1.0 / 0.5f32.tan()),
+        ];
+        for (function, text, expected) in cases {
+            assert_eq!(
+                real_example(function, text, "0.5"),
+                Ok(format!("{text} {expected}"))
+            );
+        }
+        assert_eq!(
+            real_example("ctg", "", "0"),
+            Err(Message::DivisionByZero.into())
+        );
+
+        let body = "вывод \"угол x=\"\nввод x\ny := 2*sin(x)*cos(x)\nвывод \"sin2x = \", y";
+        assert_eq!(
+            example("вещ x, y", body, &["0.5"]),
+            Ok(format!(
+                "угол x=sin2x = {}",
+                2.0 * 0.5f32.sin() * 0.5f32.cos()
+            ))
+        );
+    }
+
+    #[test]
+    fn inverse_trigonometry() {
+        let cases = [
+            ("arcsin", "арксинус числа х равен", FRAC_PI_2),
+            ("arccos", "арккосинус числа х равен", 0.0),
+            ("arctg", "арктангенс числа х равен", FRAC_PI_4),
+            ("arcctg", "арккотангенс числа х равен", FRAC_PI_4),
+        ];
+        for (function, text, expected) in cases {
+            assert_eq!(
+                real_example(function, text, "1"),
+                Ok(format!("{text} {expected}"))
+            );
+        }
+        for function in ["arcsin", "arccos"] {
+            assert_eq!(
+                real_example(function, "", "2"),
+                Err(Message::OutOfRange(function.to_string()).into())
+            );
+        }
+    }
+
+    #[test]
+    fn logarithms() {
+        let body = |function: &str, text: &str| {
+            format!(
+                "ввод а, б\nс := а+б\nс := {function}(с)\nвывод \"{text} от суммы чисел \",а,\" и \",б,\" равен \",с"
+            )
+        };
+        assert_eq!(
+            example("вещ а,б,с", &body("ln", "Натуральный логарифм"), &["1 1"]),
+            Ok(format!(
+                "Натуральный логарифм от суммы чисел 1 и 1 равен {}",
+                2f32.ln()
+            ))
+        );
+        assert_eq!(
+            example("вещ а,б,с", &body("lg", "Десятичный логарифм"), &["40 60"]),
+            Ok("Десятичный логарифм от суммы чисел 40 и 60 равен 2".to_string())
+        );
+        for function in ["ln", "lg"] {
+            assert_eq!(
+                example("вещ а,б,с", &body(function, ""), &["1 -1"]),
+                Err(Message::LogOfNonPositive.into())
+            );
+        }
+    }
+
+    #[test]
+    fn exp() {
+        let body = "ввод а\nx := exp(а)\nвывод \"число е в степени \", а, \" равно \", x";
+        assert_eq!(
+            example("вещ x\nцел а", body, &["1"]),
+            Ok(format!("число е в степени 1 равно {}", 1f32.exp()))
+        );
+    }
+
+    #[test]
+    fn min_and_max() {
+        let body = "ввод а, б\nс1 := max(а,б)\nс2 := min(а,б)\nвывод с1, нс\nвывод с2, нс";
+        assert_eq!(
+            example("вещ а,б,с1, с2", body, &["2.5 1.5"]),
+            Ok("2.5\n1.5\n".to_string())
+        );
+        let body = "ввод а, б\nс1 := imax(а,б)\nс2 := imin(а,б)\nвывод с1, нс\nвывод с2, нс";
+        assert_eq!(
+            example("цел а,б,с1, с2", body, &["3 7"]),
+            Ok("7\n3\n".to_string())
+        );
+    }
+
+    #[test]
+    fn div_and_mod() {
+        //The remainder is never negative
+        let body = "ввод а, б\nвывод div(а, б), \" \", mod(а, б)";
+        for (input, output) in [("7 2", "3 1"), ("-7 2", "-4 1"), ("7 -2", "-3 1")] {
+            assert_eq!(example("цел а, б", body, &[input]), Ok(output.to_string()));
+        }
+        assert_eq!(
+            example("цел а, б", body, &["7 0"]),
+            Err(Message::DivisionByZero.into())
+        );
+    }
+
+    #[test]
+    fn int() {
+        let body = "ввод а\nб := int(а)\nвывод \"Целая часть \", а, \" равна \", б";
+        assert_eq!(
+            example("вещ а\nцел б", body, &["2.7"]),
+            Ok("Целая часть 2.7 равна 2".to_string())
+        );
+        assert_eq!(
+            example("вещ а\nцел б", body, &["-2.7"]),
+            Ok("Целая часть -2.7 равна -3".to_string())
+        );
+    }
+
+    #[test]
+    fn max_values() {
+        assert_eq!(
+            run("вывод МАКСЦЕЛ, \" \", МАКСВЕЩ", &[]),
+            Ok(format!("{} {}", i32::MAX, f32::MAX))
+        );
+    }
+}
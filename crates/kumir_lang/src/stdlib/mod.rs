@@ -0,0 +1,172 @@
+//! Built-in algorithms of Kumir, registered as native namespaces on every
+//! [`crate::interpreter::Interpreter`]. Most are imported without
+//! `использовать`, actors outside the language need it.
+
+use std::{cell::RefCell, rc::Rc};
+
+use indexmap::IndexMap;
+
+use crate::{
+    ast::{Environment, FunctionParameter, Literal, NativeFunction},
+    lexer::{FunctionParamType, TypeDefinition},
+    messages::{Error, Message},
+};
+
+pub mod complex;
+pub mod files;
+pub mod math;
+pub mod strings;
+pub mod system;
+pub mod text;
+
+/// Namespaces imported without `использовать`
+pub const BUILT_IN: [&str; 3] = [math::NAME, system::NAME, text::NAME];
+
+/// Actors that only compute values, there's nothing of them to reset
+pub const STATELESS: [&str; 2] = [strings::NAME, complex::NAME];
+
+/// Namespaces of every program, all but the actors are imported
+pub fn register(environment: &mut Environment) {
+    for (name, namespace) in [
+        (math::NAME, math::namespace()),
+        (system::NAME, system::namespace()),
+        (text::NAME, text::namespace()),
+    ] {
+        environment.register_namespace(name, namespace);
+        let _ = environment.import_namespace(name);
+    }
+    let files = files::namespace(environment.io.files());
+    for (name, namespace) in [
+        (strings::NAME, strings::namespace()),
+        (files::NAME, files),
+        (complex::NAME, complex::namespace()),
+    ] {
+        environment.register_namespace(name, namespace);
+    }
+}
+
+/// Native algorithm with `арг` parameters, `body` gets their values in
+/// declaration order
+pub fn native(
+    params: &[(&str, TypeDefinition)],
+    return_type: Option<TypeDefinition>,
+    body: impl Fn(&[Literal]) -> Result<Option<Literal>, Error> + 'static,
+) -> NativeFunction {
+    native_with_env(params, return_type, move |_, args| body(args))
+}
+
+/// Like [`native`], `body` also gets the scope of the call, e.g. for its
+/// clock
+pub fn native_with_env(
+    params: &[(&str, TypeDefinition)],
+    return_type: Option<TypeDefinition>,
+    body: impl Fn(&Environment, &[Literal]) -> Result<Option<Literal>, Error> + 'static,
+) -> NativeFunction {
+    let params = params
+        .iter()
+        .map(|(name, type_definition)| (*name, FunctionParamType::ArgumentParam, *type_definition))
+        .collect::<Vec<_>>();
+    native_function(&params, return_type, move |environment, values| {
+        body(
+            environment,
+            &values.iter().flatten().cloned().collect::<Vec<_>>(),
+        )
+    })
+}
+
+/// Native algorithm with `рез` and `аргрез` parameters too. `body` gets the
+/// values of all parameters in declaration order, `None` for `рез` ones, and
+/// sets the results in place
+pub fn native_with_results(
+    params: &[(&str, FunctionParamType, TypeDefinition)],
+    return_type: Option<TypeDefinition>,
+    body: impl Fn(&mut [Option<Literal>]) -> Result<Option<Literal>, Error> + 'static,
+) -> NativeFunction {
+    native_function(params, return_type, move |_, values| body(values))
+}
+
+pub(crate) fn native_function(
+    params: &[(&str, FunctionParamType, TypeDefinition)],
+    return_type: Option<TypeDefinition>,
+    mut body: impl FnMut(&Environment, &mut [Option<Literal>]) -> Result<Option<Literal>, Error>
+    + 'static,
+) -> NativeFunction {
+    let names = params
+        .iter()
+        .map(|(name, result_type, _)| (name.to_string(), *result_type))
+        .collect::<Vec<_>>();
+    NativeFunction {
+        params: params
+            .iter()
+            .map(|(name, result_type, type_definition)| {
+                (
+                    name.to_string(),
+                    FunctionParameter {
+                        type_definition: *type_definition,
+                        result_type: *result_type,
+                    },
+                )
+            })
+            .collect::<IndexMap<_, _>>(),
+        return_type,
+        native_function: Rc::new(RefCell::new(
+            move |environment: &Rc<RefCell<Environment>>| {
+                let mut values = names
+                    .iter()
+                    .map(|(name, result_type)| match result_type {
+                        FunctionParamType::ResultParam => Ok(None),
+                        _ => environment
+                            .borrow()
+                            .get_value(name)
+                            .map(Some)
+                            .ok_or(Message::UndefinedVariable(name.clone())),
+                    })
+                    .collect::<Result<Vec<_>, Message>>()?;
+                let value = body(&environment.borrow(), &mut values)?;
+                for ((name, result_type), value) in names.iter().zip(values) {
+                    if let (
+                        FunctionParamType::ResultParam | FunctionParamType::ArgumentResultParam,
+                        Some(value),
+                    ) = (result_type, value)
+                    {
+                        environment.borrow_mut().assign_var(name, value)?;
+                    }
+                }
+                Ok(value)
+            },
+        )),
+    }
+}
+
+pub fn float(value: &Literal) -> f32 {
+    match value {
+        Literal::Float(value) => *value,
+        Literal::Int(value) => *value as f32,
+        _ => f32::NAN,
+    }
+}
+
+pub fn int(value: &Literal) -> i32 {
+    match value {
+        Literal::Int(value) => *value,
+        _ => 0,
+    }
+}
+
+pub fn string(value: &Literal) -> &str {
+    match value {
+        Literal::String(value) => value,
+        _ => "",
+    }
+}
+
+pub fn char(value: &Literal) -> char {
+    match value {
+        Literal::Char(value) => *value,
+        _ => '\0',
+    }
+}
+
+pub fn bool(value: &Literal) -> bool {
+    matches!(value, Literal::Bool(true))
+}
@@ -0,0 +1,244 @@
+//! Actor `Строки`, see `userdocs/md_output/stringutils.md`. Positions count
+//! characters from 1
+
+use crate::{
+    ast::{Literal, Namespace},
+    lexer::{
+        FunctionParamType::{ArgumentParam, ArgumentResultParam},
+        TypeDefinition::{Bool, Int, String},
+    },
+    messages::{Error, Message},
+};
+
+use super::{bool, int, native, native_with_results, string};
+
+pub const NAME: &str = "Строки";
+
+pub fn namespace() -> Namespace {
+    let mut namespace = Namespace::default();
+
+    namespace.register_native_function(
+        "верхний регистр",
+        native(&[("строка", String)], Some(String), |args| {
+            Ok(Some(Literal::String(string(&args[0]).to_uppercase())))
+        }),
+    );
+    namespace.register_native_function(
+        "нижний регистр",
+        native(&[("строка", String)], Some(String), |args| {
+            Ok(Some(Literal::String(string(&args[0]).to_lowercase())))
+        }),
+    );
+
+    for name in ["позиция", "поз"] {
+        namespace.register_native_function(
+            name,
+            native(
+                &[("строка", String), ("фрагмент", String)],
+                Some(Int),
+                |args| {
+                    Ok(Some(Literal::Int(position(
+                        string(&args[0]),
+                        string(&args[1]),
+                        1,
+                    ))))
+                },
+            ),
+        );
+    }
+    for name in ["позиция после", "поз после"] {
+        namespace.register_native_function(
+            name,
+            native(
+                &[("начало", Int), ("строка", String), ("фрагмент", String)],
+                Some(Int),
+                |args| {
+                    Ok(Some(Literal::Int(position(
+                        string(&args[1]),
+                        string(&args[2]),
+                        int(&args[0]),
+                    ))))
+                },
+            ),
+        );
+    }
+
+    namespace.register_native_function(
+        "вставить",
+        native_with_results(
+            &[
+                ("фрагмент", ArgumentParam, String),
+                ("строка", ArgumentResultParam, String),
+                ("начало", ArgumentParam, Int),
+            ],
+            None,
+            |values| {
+                let [Some(fragment), Some(text), Some(start)] = values else {
+                    return Ok(None);
+                };
+                let mut chars = string(text).chars().collect::<Vec<_>>();
+                let start = char_index(int(start), chars.len())?;
+                chars.splice(start..start, string(fragment).chars());
+                *text = Literal::String(chars.into_iter().collect());
+                Ok(None)
+            },
+        ),
+    );
+    namespace.register_native_function(
+        "заменить",
+        native_with_results(
+            &[
+                ("строка", ArgumentResultParam, String),
+                ("старый фрагмент", ArgumentParam, String),
+                ("новый фрагмент", ArgumentParam, String),
+                ("каждый", ArgumentParam, Bool),
+            ],
+            None,
+            |values| {
+                let [Some(text), Some(old), Some(new), Some(every)] = values else {
+                    return Ok(None);
+                };
+                let (old, new) = (string(old), string(new));
+                let replaced = if old.is_empty() {
+                    string(text).to_string()
+                } else if bool(every) {
+                    string(text).replace(old, new)
+                } else {
+                    string(text).replacen(old, new, 1)
+                };
+                *text = Literal::String(replaced);
+                Ok(None)
+            },
+        ),
+    );
+    namespace.register_native_function(
+        "удалить",
+        native_with_results(
+            &[
+                ("строка", ArgumentResultParam, String),
+                ("начало", ArgumentParam, Int),
+                ("количество", ArgumentParam, Int),
+            ],
+            None,
+            |values| {
+                let [Some(text), Some(start), Some(count)] = values else {
+                    return Ok(None);
+                };
+                let mut chars = string(text).chars().collect::<Vec<_>>();
+                let start = char_index(int(start), chars.len())?;
+                let end = chars.len().min(start + int(count).max(0) as usize);
+                chars.drain(start..end);
+                *text = Literal::String(chars.into_iter().collect());
+                Ok(None)
+            },
+        ),
+    );
+
+    namespace
+}
+
+/// Position of the first `fragment` in `text` at or after `start`, 0 if
+/// there is none
+fn position(text: &str, fragment: &str, start: i32) -> i32 {
+    let skip = start.max(1) as usize - 1;
+    let Some((offset, _)) = text.char_indices().nth(skip) else {
+        return 0;
+    };
+    text[offset..].find(fragment).map_or(0, |found| {
+        (skip + text[offset..][..found].chars().count() + 1) as i32
+    })
+}
+
+/// Index of the character at `position`, which can also be right after the
+/// last one
+fn char_index(position: i32, len: usize) -> Result<usize, Error> {
+    if position < 1 || position as usize > len + 1 {
+        return Err(Message::PositionOutOfRange { position, len }.into());
+    }
+    Ok(position as usize - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::run;
+
+    /// Output of `body` with `Строки` imported
+    fn strings(body: &str) -> Result<std::string::String, Error> {
+        run(&format!("использовать Строки\nлит с\n{body}"), &[])
+    }
+
+    fn out_of_range(position: i32, len: usize) -> Result<std::string::String, Error> {
+        Err(Message::PositionOutOfRange { position, len }.into())
+    }
+
+    #[test]
+    fn position_counts_characters() {
+        let cases = [
+            ("позиция(\"привет, мир\", \"мир\")", "9"),
+            ("поз(\"привет\", \"при\")", "1"),
+            ("позиция(\"привет\", \"мир\")", "0"),
+            ("позиция после(2, \"абвабв\", \"аб\")", "4"),
+            ("поз после(4, \"абвабв\", \"аб\")", "4"),
+            ("позиция после(0, \"абв\", \"а\")", "1"),
+            ("позиция после(4, \"абв\", \"а\")", "0"),
+            ("позиция после(100, \"абв\", \"\")", "0"),
+        ];
+        for (call, position) in cases {
+            assert_eq!(
+                strings(&format!("вывод {call}")),
+                Ok(position.to_string()),
+                "{call}"
+            );
+        }
+    }
+
+    #[test]
+    fn insert_at_the_edges() {
+        let insert = |start: i32| {
+            strings(&format!(
+                "с := \"мир\"\nвставить(\"ё\", с, {start})\nвывод с"
+            ))
+        };
+        assert_eq!(insert(1), Ok("ёмир".to_string()));
+        assert_eq!(insert(3), Ok("миёр".to_string()));
+        assert_eq!(insert(4), Ok("мирё".to_string()));
+        assert_eq!(insert(5), out_of_range(5, 3));
+        assert_eq!(insert(0), out_of_range(0, 3));
+        assert_eq!(
+            strings("с := \"\"\nвставить(\"ё\", с, 1)\nвывод с"),
+            Ok("ё".to_string())
+        );
+    }
+
+    #[test]
+    fn delete_at_the_edges() {
+        let delete = |start: i32, count: i32| {
+            strings(&format!(
+                "с := \"привет\"\nудалить(с, {start}, {count})\nвывод с"
+            ))
+        };
+        assert_eq!(delete(1, 2), Ok("ивет".to_string()));
+        assert_eq!(delete(6, 1), Ok("приве".to_string()));
+        assert_eq!(delete(5, 100), Ok("прив".to_string()));
+        assert_eq!(delete(7, 1), Ok("привет".to_string()));
+        assert_eq!(delete(2, 0), Ok("привет".to_string()));
+        assert_eq!(delete(8, 1), out_of_range(8, 6));
+        assert_eq!(delete(0, 1), out_of_range(0, 6));
+    }
+
+    #[test]
+    fn replace_first_or_every_fragment() {
+        let replace = |old: &str, new: &str, every: &str| {
+            strings(&format!(
+                "с := \"абаба\"\nзаменить(с, \"{old}\", \"{new}\", {every})\nвывод с"
+            ))
+        };
+        assert_eq!(replace("а", "я", "да"), Ok("ябябя".to_string()));
+        assert_eq!(replace("а", "я", "нет"), Ok("ябаба".to_string()));
+        assert_eq!(replace("ба", "", "да"), Ok("а".to_string()));
+        assert_eq!(replace("абаба", "ё", "нет"), Ok("ё".to_string()));
+        assert_eq!(replace("", "ё", "да"), Ok("абаба".to_string()));
+        assert_eq!(replace("в", "ё", "да"), Ok("абаба".to_string()));
+    }
+}
@@ -0,0 +1,57 @@
+//! System functions `ждать` and `время`, see
+//! `userdocs/md_output/sys_func.md`. Both use the clock of the program, see
+//! [`crate::clock`]
+
+use std::{sync::atomic::Ordering, time::Duration};
+
+use crate::{
+    ast::{Literal, Namespace},
+    lexer::TypeDefinition::Int,
+    limits::Budget,
+    messages::Message,
+};
+
+use super::{int, native_with_env};
+
+pub const NAME: &str = "Система";
+
+pub fn namespace() -> Namespace {
+    let mut namespace = Namespace::default();
+
+    namespace.register_native_function(
+        "ждать",
+        native_with_env(&[("x", Int)], None, |environment, args| {
+            let millis = int(&args[0]).max(0) as u64;
+            //A replayed snapshot doesn't wait, its times are recorded
+            let replaying = environment.io.journal().borrow().is_replaying();
+            //Waiting past the time limit would hang batch runs
+            let waited = Budget::wait(
+                &environment.budget,
+                Duration::from_millis(millis),
+                |duration| {
+                    if !replaying {
+                        environment.clock.sleep(duration, &environment.kill_flag);
+                    }
+                },
+            );
+            if environment.kill_flag.load(Ordering::Relaxed) {
+                return Err(Message::UserInterrupt.into());
+            }
+            waited.map(|_| None)
+        }),
+    );
+    namespace.register_native_function(
+        "время",
+        native_with_env(&[], Some(Int), |environment, _| {
+            let time = environment
+                .io
+                .journal()
+                .borrow_mut()
+                .time_of_day(environment.clock.as_ref());
+            let millis = time.as_millis() as i32;
+            Ok(Some(Literal::Int(millis)))
+        }),
+    );
+
+    namespace
+}
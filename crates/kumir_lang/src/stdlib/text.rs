@@ -0,0 +1,225 @@
+//! Built-in text algorithms and conversions between numbers and text, see
+//! `userdocs/md_output/simple_string.md` and `lexical_cast.md`. Positions
+//! and lengths count characters, not bytes
+
+use crate::{
+    ast::{Literal, Namespace},
+    encoding::Encoding,
+    lexer::{
+        FunctionParamType::{ArgumentParam, ResultParam},
+        TypeDefinition::{Bool, Char, Float, Int, String},
+    },
+    messages::Message,
+};
+
+use super::{bool, char, float, int, native, native_with_results, string};
+
+pub const NAME: &str = "Текст";
+
+pub fn namespace() -> Namespace {
+    let mut namespace = Namespace::default();
+
+    namespace.register_native_function(
+        "длин",
+        native(&[("строка", String)], Some(Int), |args| {
+            Ok(Some(Literal::Int(string(&args[0]).chars().count() as i32)))
+        }),
+    );
+    namespace.register_native_function(
+        "код",
+        native(&[("c", Char)], Some(Int), |args| {
+            let char = char(&args[0]);
+            let code = Encoding::Cp1251
+                .encode_char(char)
+                .ok_or(Message::NotInCp1251(char))?;
+            Ok(Some(Literal::Int(code as i32)))
+        }),
+    );
+    namespace.register_native_function(
+        "символ",
+        native(&[("n", Int)], Some(Char), |args| {
+            let code = int(&args[0]);
+            let char = u8::try_from(code)
+                .ok()
+                .and_then(|code| Encoding::Cp1251.decode_byte(code))
+                .ok_or(Message::CharCodeOutOfRange(code))?;
+            Ok(Some(Literal::Char(char)))
+        }),
+    );
+    namespace.register_native_function(
+        "юникод",
+        native(&[("c", Char)], Some(Int), |args| {
+            Ok(Some(Literal::Int(char(&args[0]) as i32)))
+        }),
+    );
+    namespace.register_native_function(
+        "юнисимвол",
+        native(&[("n", Int)], Some(Char), |args| {
+            let code = int(&args[0]);
+            let char = u32::try_from(code)
+                .ok()
+                .and_then(std::char::from_u32)
+                .ok_or(Message::CharCodeOutOfRange(code))?;
+            Ok(Some(Literal::Char(char)))
+        }),
+    );
+
+    namespace.register_native_function(
+        "цел_в_лит",
+        native(&[("число", Int)], Some(String), |args| {
+            Ok(Some(Literal::String(args[0].to_string())))
+        }),
+    );
+    namespace.register_native_function(
+        "вещ_в_лит",
+        native(&[("число", Float)], Some(String), |args| {
+            Ok(Some(Literal::String(args[0].to_string())))
+        }),
+    );
+    namespace.register_native_function(
+        "лит_в_цел",
+        native_with_results(
+            &[
+                ("строка", ArgumentParam, String),
+                ("успех", ResultParam, Bool),
+            ],
+            Some(Int),
+            |values| {
+                let value = values[0]
+                    .as_ref()
+                    .and_then(|value| parse_int(string(value)));
+                values[1] = Some(Literal::Bool(value.is_some()));
+                Ok(Some(Literal::Int(value.unwrap_or(0))))
+            },
+        ),
+    );
+    namespace.register_native_function(
+        "лит_в_вещ",
+        native_with_results(
+            &[
+                ("строка", ArgumentParam, String),
+                ("успех", ResultParam, Bool),
+            ],
+            Some(Float),
+            |values| {
+                let value = values[0]
+                    .as_ref()
+                    .and_then(|value| parse_float(string(value)));
+                values[1] = Some(Literal::Bool(value.is_some()));
+                Ok(Some(Literal::Float(value.unwrap_or(0.0))))
+            },
+        ),
+    );
+
+    namespace.register_native_function(
+        "Цел",
+        native(
+            &[("строка", String), ("по умолчанию", Int)],
+            Some(Int),
+            |args| {
+                let value = parse_int(string(&args[0])).unwrap_or(int(&args[1]));
+                Ok(Some(Literal::Int(value)))
+            },
+        ),
+    );
+    namespace.register_native_function(
+        "Вещ",
+        native(
+            &[("строка", String), ("по умолчанию", Float)],
+            Some(Float),
+            |args| {
+                let value = parse_float(string(&args[0])).unwrap_or(float(&args[1]));
+                Ok(Some(Literal::Float(value)))
+            },
+        ),
+    );
+    namespace.register_native_function(
+        "Лог",
+        native(
+            &[("строка", String), ("по умолчанию", Bool)],
+            Some(Bool),
+            |args| {
+                let value = match string(&args[0]).trim() {
+                    "да" | "1" | "истина" => true,
+                    "нет" | "0" | "ложь" => false,
+                    _ => bool(&args[1]),
+                };
+                Ok(Some(Literal::Bool(value)))
+            },
+        ),
+    );
+
+    namespace
+}
+
+fn parse_int(text: &str) -> Option<i32> {
+    text.trim().parse().ok()
+}
+
+/// Only the decimal point is accepted, `1,4` is not a number
+fn parse_float(text: &str) -> Option<f32> {
+    text.trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{io::testing::run, messages::Error};
+
+    fn output(body: &str) -> Result<std::string::String, Error> {
+        run(&format!("лог ус\n{body}"), &[])
+    }
+
+    #[test]
+    fn length_counts_characters() {
+        assert_eq!(output("вывод длин(\"привет\")"), Ok("6".to_string()));
+        assert_eq!(output("вывод длин(\"\")"), Ok("0".to_string()));
+        assert_eq!(output("вывод длин(\"ёж, 1\")"), Ok("5".to_string()));
+    }
+
+    #[test]
+    fn unicode_and_cp1251_codes() {
+        assert_eq!(output("вывод юникод('я')"), Ok("1103".to_string()));
+        assert_eq!(output("вывод юнисимвол(1103)"), Ok("я".to_string()));
+        assert_eq!(
+            output("вывод юникод(юнисимвол(8364))"),
+            Ok("8364".to_string())
+        );
+        assert_eq!(
+            output("вывод юнисимвол(1114112)"),
+            Err(Message::CharCodeOutOfRange(1_114_112).into())
+        );
+        assert_eq!(output("вывод код('я')"), Ok("255".to_string()));
+        assert_eq!(output("вывод символ(255)"), Ok("я".to_string()));
+        assert_eq!(output("вывод символ(код('Ё'))"), Ok("Ё".to_string()));
+        assert_eq!(
+            output("вывод символ(256)"),
+            Err(Message::CharCodeOutOfRange(256).into())
+        );
+        assert_eq!(
+            output("вывод код('中')"),
+            Err(Message::NotInCp1251('中').into())
+        );
+    }
+
+    #[test]
+    fn parsing_reports_success() {
+        let parse = |function: &str, text: &str| {
+            output(&format!(
+                "вывод {function}(\"{text}\", ус)\nесли ус то вывод \" да\" иначе вывод \" нет\" все"
+            ))
+        };
+        assert_eq!(parse("лит_в_цел", "12"), Ok("12 да".to_string()));
+        assert_eq!(parse("лит_в_цел", " 7 "), Ok("7 да".to_string()));
+        assert_eq!(parse("лит_в_цел", "12а"), Ok("0 нет".to_string()));
+        assert_eq!(parse("лит_в_цел", "1.5"), Ok("0 нет".to_string()));
+        assert_eq!(parse("лит_в_цел", ""), Ok("0 нет".to_string()));
+        assert_eq!(parse("лит_в_вещ", "1.5"), Ok("1.5 да".to_string()));
+        assert_eq!(parse("лит_в_вещ", "1,5"), Ok("0 нет".to_string()));
+        assert_eq!(parse("лит_в_вещ", "1e100"), Ok("0 нет".to_string()));
+        assert_eq!(parse("лит_в_вещ", "абв"), Ok("0 нет".to_string()));
+    }
+}
@@ -0,0 +1,357 @@
+//! Record of a run for stepping backwards. Every statement entered becomes a
+//! [`TraceEntry`] with the calls entered and returned since the previous
+//! one, the variables it changed, the conditions it checked and the actor
+//! actions it ran, so the state at any recorded statement can be rebuilt
+//! with [`Trace::state_at`].
+//!
+//! Enabled with [`crate::interpreter::Interpreter::enable_trace`]. The
+//! program itself only runs forwards: [`crate::debugger::DebugHandle`] shows
+//! the recorded states while it stays paused, and the host undoes the actor
+//! actions counted by [`Trace::actions_before`].
+
+use crate::{
+    inspector::{FrameView, ProgramState, VariableView},
+    lexer::Span,
+};
+
+/// Variable set, declared or gone out of scope
+#[derive(Debug, PartialEq, Clone)]
+pub struct VariableWrite {
+    /// Index in the call stack, `None` for the main program
+    pub frame: Option<usize>,
+    pub name: String,
+    /// `None` when the variable no longer exists
+    pub variable: Option<VariableView>,
+}
+
+/// Condition of `если`, `нц пока` or `кц при` and what it was
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Check {
+    /// Statement the condition belongs to
+    pub span: Span,
+    pub result: bool,
+}
+
+/// Actor command without a result, which changes the world of the actor
+#[derive(Debug, PartialEq, Clone)]
+pub struct ActorEffect {
+    pub actor: String,
+    pub command: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TraceEntry {
+    pub span: Span,
+    /// Calls that returned since the previous statement
+    pub returned: usize,
+    /// Calls entered since the previous statement, with their variables at
+    /// this one
+    pub called: Vec<FrameView>,
+    /// Variables the statement changed, known once the next statement is
+    /// entered or the program ends
+    pub writes: Vec<VariableWrite>,
+    /// Conditions checked after the statement was entered, before the
+    /// next one: of the statement itself or of a loop it ends an iteration
+    /// of
+    pub checks: Vec<Check>,
+    /// Actor actions the statement ran, in order
+    pub effects: Vec<ActorEffect>,
+}
+
+/// Statements a run entered, shared by all scopes through
+/// [`crate::ast::Environment::trace`]
+#[derive(Debug, Default)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+    /// State at the last entry, the next one is compared with it
+    last: ProgramState,
+    /// Statements with a condition that are running, innermost last
+    conditions: Vec<Span>,
+    /// State when the first scope was left after the last entry, with the
+    /// variables of the scope still there
+    left: Option<ProgramState>,
+}
+
+impl Trace {
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forgets the previous run
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Adds the statement at `span`, `state` is the state before it runs
+    pub(crate) fn statement_entered(&mut self, span: Span, state: ProgramState) {
+        let (returned, called) = self.record_changes(&state);
+        self.entries.push(TraceEntry {
+            span,
+            returned,
+            called,
+            ..Default::default()
+        });
+        self.last = state;
+    }
+
+    /// A scope is left, `state` gives the state before its variables go
+    /// away
+    pub(crate) fn scope_left(&mut self, state: impl FnOnce() -> ProgramState) {
+        if self.left.is_none() && !self.entries.is_empty() {
+            self.left = Some(state());
+        }
+    }
+
+    /// Records what the last statement changed, `state` is the final one.
+    /// When the program ended in a scope that's gone by now, the final
+    /// state is the one of that scope
+    pub(crate) fn finish(&mut self, state: ProgramState) {
+        let left = self.left.clone();
+        self.record_changes(&state);
+        self.last = left.unwrap_or(state);
+    }
+
+    pub(crate) fn enter_condition(&mut self, span: Span) {
+        self.conditions.push(span);
+    }
+
+    pub(crate) fn exit_condition(&mut self) {
+        self.conditions.pop();
+    }
+
+    /// Condition of the innermost statement with one was `result`
+    pub(crate) fn checked(&mut self, result: bool) {
+        if let (Some(entry), Some(&span)) = (self.entries.last_mut(), self.conditions.last()) {
+            entry.checks.push(Check { span, result });
+        }
+    }
+
+    pub(crate) fn actor_called(&mut self, actor: &str, command: &str) {
+        if let Some(entry) = self.entries.last_mut() {
+            entry.effects.push(ActorEffect {
+                actor: actor.to_string(),
+                command: command.to_string(),
+            });
+        }
+    }
+
+    /// Actions of `actor` run before the statement at `index` was entered
+    pub fn actions_before(&self, index: usize, actor: &str) -> usize {
+        self.entries[..index.min(self.entries.len())]
+            .iter()
+            .flat_map(|entry| &entry.effects)
+            .filter(|effect| effect.actor == actor)
+            .count()
+    }
+
+    /// State before the statement at `index` ran, `None` past the end
+    pub fn state_at(&self, index: usize) -> Option<ProgramState> {
+        self.states().nth(index)
+    }
+
+    /// State before every statement, in order
+    pub fn states(&self) -> impl Iterator<Item = ProgramState> + '_ {
+        let mut state = ProgramState::default();
+        self.entries.iter().enumerate().map(move |(i, entry)| {
+            if i > 0 {
+                for write in &self.entries[i - 1].writes {
+                    apply(&mut state, write);
+                }
+            }
+            let frames = state.call_stack.len().saturating_sub(entry.returned);
+            state.call_stack.truncate(frames);
+            state.call_stack.extend(entry.called.iter().cloned());
+            state.span = entry.span;
+            state.clone()
+        })
+    }
+
+    /// State after the last statement ran, once the program ended
+    pub fn final_state(&self) -> &ProgramState {
+        &self.last
+    }
+
+    /// Writes of the last entry and the calls returned and entered since
+    fn record_changes(&mut self, state: &ProgramState) -> (usize, Vec<FrameView>) {
+        //Variables of a scope left since are only in the state of then
+        let writes = match self.left.take() {
+            Some(left) => {
+                let mut writes = writes_between(&self.last, &left);
+                writes.extend(writes_between(&left, state));
+                writes
+            }
+            None => writes_between(&self.last, state),
+        };
+        if let Some(entry) = self.entries.last_mut() {
+            entry.writes = writes;
+        }
+        let common = common_frames(&self.last, state);
+        (
+            self.last.call_stack.len() - common,
+            state.call_stack[common..].to_vec(),
+        )
+    }
+}
+
+/// Number of calls at the bottom of the stack both states are in
+fn common_frames(last: &ProgramState, current: &ProgramState) -> usize {
+    last.call_stack
+        .iter()
+        .zip(&current.call_stack)
+        .take_while(|(last, current)| {
+            last.name == current.name && last.call_site == current.call_site
+        })
+        .count()
+}
+
+/// Variables set, declared or gone from `last` to `current`, in the calls
+/// both are in
+fn writes_between(last: &ProgramState, current: &ProgramState) -> Vec<VariableWrite> {
+    let common = common_frames(last, current);
+    let mut writes = changes(None, &last.globals, &current.globals);
+    let frames = last.call_stack.iter().zip(&current.call_stack).take(common);
+    for (frame, (last, current)) in frames.enumerate() {
+        writes.extend(changes(
+            Some(frame),
+            &frame_variables(last),
+            &frame_variables(current),
+        ));
+    }
+    writes
+}
+
+fn frame_variables(frame: &FrameView) -> Vec<VariableView> {
+    frame
+        .parameters
+        .iter()
+        .map(|parameter| parameter.variable.clone())
+        .chain(frame.locals.iter().cloned())
+        .collect()
+}
+
+fn changes(
+    frame: Option<usize>,
+    last: &[VariableView],
+    current: &[VariableView],
+) -> Vec<VariableWrite> {
+    let gone = last
+        .iter()
+        .filter(|variable| !current.iter().any(|current| current.name == variable.name))
+        .map(|variable| VariableWrite {
+            frame,
+            name: variable.name.clone(),
+            variable: None,
+        });
+    let set = current
+        .iter()
+        .filter(|variable| !last.contains(variable))
+        .map(|variable| VariableWrite {
+            frame,
+            name: variable.name.clone(),
+            variable: Some(variable.clone()),
+        });
+    gone.chain(set).collect()
+}
+
+fn apply(state: &mut ProgramState, write: &VariableWrite) {
+    let (parameters, variables) = match write.frame {
+        None => (None, &mut state.globals),
+        Some(frame) => match state.call_stack.get_mut(frame) {
+            Some(frame) => (Some(&mut frame.parameters), &mut frame.locals),
+            None => return,
+        },
+    };
+    if let Some(parameter) = parameters.and_then(|parameters| {
+        parameters
+            .iter_mut()
+            .find(|parameter| parameter.variable.name == write.name)
+    }) {
+        if let Some(variable) = &write.variable {
+            parameter.variable = variable.clone();
+        }
+        return;
+    }
+    let position = variables
+        .iter()
+        .position(|variable| variable.name == write.name);
+    match (position, &write.variable) {
+        (Some(position), Some(variable)) => variables[position] = variable.clone(),
+        (Some(position), None) => {
+            variables.remove(position);
+        }
+        (None, Some(variable)) => variables.push(variable.clone()),
+        (None, None) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inspector::VariableView, io::testing::interpreter};
+
+    fn trace(source: &str) -> Trace {
+        let (mut interpreter, _) = interpreter(source, &[]);
+        interpreter.enable_trace();
+        interpreter.run().unwrap();
+        interpreter.trace().unwrap().take()
+    }
+
+    /// Variables the last statement set, with their values
+    fn last_writes(trace: &Trace) -> Vec<(String, Option<String>)> {
+        let entry = trace.entries().last().unwrap();
+        entry
+            .writes
+            .iter()
+            .map(|write| {
+                let value = write.variable.as_ref().map(VariableView::value_text);
+                (write.name.clone(), value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn last_statement_of_the_program_is_recorded() {
+        for source in [
+            "лит s\ns := \"a|b,c\"",
+            "алг\nнач\nлит s\ns := \"a|b,c\"\nкон",
+        ] {
+            let trace = trace(source);
+            let written = Some("\"a|b,c\"".to_string());
+            assert_eq!(
+                last_writes(&trace),
+                [("s".to_string(), written)],
+                "{source}"
+            );
+            let state = trace.final_state();
+            let variables = state
+                .call_stack
+                .last()
+                .map_or(&state.globals, |frame| &frame.locals);
+            assert_eq!(variables[0].value_text(), "\"a|b,c\"", "{source}");
+        }
+    }
+
+    #[test]
+    fn last_statement_of_a_call_is_recorded() {
+        let trace = trace("алг f\nнач\nцел x\nx := 1\nкон\nf\nцел y");
+        let entry = trace
+            .entries()
+            .iter()
+            .find(|entry| entry.span.start.line == 4)
+            .unwrap();
+        let names: Vec<_> = entry
+            .writes
+            .iter()
+            .map(|write| write.name.as_str())
+            .collect();
+        assert_eq!(names, ["x"]);
+    }
+}
@@ -0,0 +1,377 @@
+//! Trace table (таблица трассировки) of a run: a row for every assignment
+//! and every checked condition, with the values of the chosen variables after
+//! it. Built from a [`Trace`] by [`TraceTable::new`] or in one go by
+//! [`crate::interpreter::Interpreter::trace_table`], written as Markdown, CSV
+//! or HTML for answer keys.
+
+use std::fmt::Write;
+
+use crate::{
+    ast::{AstNode, Stmt},
+    inspector::{ProgramState, VariableView},
+    lexer::Span,
+    trace::Trace,
+};
+
+/// One step of the table
+#[derive(Debug, PartialEq, Clone)]
+pub struct TraceRow {
+    /// Number of the row, from 1
+    pub step: usize,
+    pub line: usize,
+    /// First line of the source of the statement
+    pub statement: String,
+    /// Values of [`TraceTable::variables`] after the step, `None` where the
+    /// variable doesn't exist
+    pub values: Vec<Option<String>>,
+    /// What the condition was, `None` for an assignment
+    pub condition: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TraceTable {
+    pub variables: Vec<String>,
+    pub rows: Vec<TraceRow>,
+    /// Error that stopped the run, the rows end at it
+    pub error: Option<String>,
+}
+
+impl TraceTable {
+    /// Table of the run recorded in `trace` of `program` loaded from
+    /// `source`. Columns are `variables`, or every variable the run set when
+    /// it is empty
+    pub fn new(trace: &Trace, program: &AstNode, source: &str, variables: &[String]) -> Self {
+        let statements = program.statements();
+        let variables = match variables {
+            [] => written_variables(trace),
+            variables => variables.to_vec(),
+        };
+        let mut table = TraceTable {
+            variables,
+            ..Default::default()
+        };
+        let mut states = trace.states().peekable();
+        //Assignments wait for the calls in them to return
+        let mut pending: Vec<Pending> = vec![];
+        for entry in trace.entries() {
+            let Some(before) = states.next() else {
+                break;
+            };
+            let after = states.peek().unwrap_or(trace.final_state());
+            let depth = before.call_stack.len();
+            table.finish(&mut pending, depth, &before, source);
+            let assigned = statements
+                .iter()
+                .find(|statement| statement.span == entry.span)
+                .map_or(vec![], |statement| assigned(&statement.stmt));
+            if !assigned.is_empty() {
+                //Other variables as before, e.g. not yet the next value of `нц для`
+                let values = table
+                    .variables
+                    .iter()
+                    .map(|name| match assigned.contains(&name.as_str()) {
+                        true => None,
+                        false => value(&before, name),
+                    })
+                    .collect();
+                pending.push(Pending {
+                    span: entry.span,
+                    depth,
+                    assigned,
+                    values,
+                });
+            }
+            if !entry.checks.is_empty() {
+                table.finish(&mut pending, depth, after, source);
+            }
+            for check in &entry.checks {
+                let values = table
+                    .variables
+                    .iter()
+                    .map(|name| value(after, name))
+                    .collect();
+                table.push(check.span, source, values, Some(check.result));
+            }
+        }
+        table.finish(&mut pending, 0, trace.final_state(), source);
+        table
+    }
+
+    /// Adds the rows of the pending assignments at `depth` and deeper, which
+    /// are done in `state`
+    fn finish(
+        &mut self,
+        pending: &mut Vec<Pending>,
+        depth: usize,
+        state: &ProgramState,
+        source: &str,
+    ) {
+        let done = pending.partition_point(|pending| pending.depth < depth);
+        for Pending {
+            span,
+            assigned,
+            mut values,
+            ..
+        } in pending.drain(done..).rev()
+        {
+            for (name, value) in self.variables.iter().zip(&mut values) {
+                if assigned.contains(&name.as_str()) {
+                    *value = self::value(state, name);
+                }
+            }
+            self.push(span, source, values, None);
+        }
+    }
+
+    fn push(
+        &mut self,
+        span: Span,
+        source: &str,
+        values: Vec<Option<String>>,
+        condition: Option<bool>,
+    ) {
+        self.rows.push(TraceRow {
+            step: self.rows.len() + 1,
+            line: span.start.line,
+            statement: span.lines(source).into_iter().next().unwrap_or_default(),
+            values,
+            condition,
+        });
+    }
+
+    /// Cells of the header and of every row, the same in every format
+    fn cells(&self) -> (Vec<String>, Vec<Vec<String>>) {
+        let mut header = vec![
+            "Шаг".to_string(),
+            "Строка".to_string(),
+            "Оператор".to_string(),
+        ];
+        header.extend(self.variables.iter().cloned());
+        header.push("Условие".to_string());
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                let mut cells = vec![
+                    row.step.to_string(),
+                    row.line.to_string(),
+                    row.statement.clone(),
+                ];
+                cells.extend(
+                    row.values
+                        .iter()
+                        .map(|value| value.clone().unwrap_or_default()),
+                );
+                cells.push(match row.condition {
+                    Some(true) => "да".to_string(),
+                    Some(false) => "нет".to_string(),
+                    None => String::new(),
+                });
+                cells
+            })
+            .collect();
+        (header, rows)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let (header, rows) = self.cells();
+        let line = |cells: &[String]| {
+            let cells: Vec<_> = cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+        let mut markdown = line(&header);
+        markdown += &line(&vec!["---".to_string(); header.len()]);
+        for row in &rows {
+            markdown += &line(row);
+        }
+        if let Some(error) = &self.error {
+            let _ = writeln!(markdown, "\n{error}");
+        }
+        markdown
+    }
+
+    /// Separated by commas, with the error as the last line
+    pub fn to_csv(&self) -> String {
+        let (header, rows) = self.cells();
+        let mut csv = String::new();
+        for cells in std::iter::once(&header).chain(&rows) {
+            let cells: Vec<_> = cells.iter().map(|cell| csv_cell(cell)).collect();
+            let _ = writeln!(csv, "{}", cells.join(","));
+        }
+        if let Some(error) = &self.error {
+            let _ = writeln!(csv, "{}", csv_cell(error));
+        }
+        csv
+    }
+
+    /// `<table>` element, with the error in a `<p>` after it
+    pub fn to_html(&self) -> String {
+        let (header, rows) = self.cells();
+        let mut html = String::from("<table>\n<tr>");
+        for cell in &header {
+            let _ = write!(html, "<th>{}</th>", html_text(cell));
+        }
+        html += "</tr>\n";
+        for row in &rows {
+            html += "<tr>";
+            for cell in row {
+                let _ = write!(html, "<td>{}</td>", html_text(cell));
+            }
+            html += "</tr>\n";
+        }
+        html += "</table>\n";
+        if let Some(error) = &self.error {
+            let _ = writeln!(html, "<p>{}</p>", html_text(error));
+        }
+        html
+    }
+}
+
+/// Assignment that hasn't finished yet
+struct Pending<'a> {
+    span: Span,
+    /// Calls running when it started
+    depth: usize,
+    assigned: Vec<&'a str>,
+    /// Values of the other variables, before it started
+    values: Vec<Option<String>>,
+}
+
+/// Variables a statement sets
+fn assigned(stmt: &Stmt) -> Vec<&str> {
+    match stmt {
+        Stmt::Assign { name, .. } => vec![name],
+        Stmt::Input { names } => names.iter().map(String::as_str).collect(),
+        Stmt::VarDecl(var_decl) if var_decl.value.is_some() => vec![&var_decl.name],
+        Stmt::VarsDecl(var_decls) => var_decls
+            .iter()
+            .filter(|var_decl| var_decl.value.is_some())
+            .map(|var_decl| var_decl.name.as_str())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Names of the variables given a value, in the order of the first one
+fn written_variables(trace: &Trace) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    let writes = trace.entries().iter().flat_map(|entry| &entry.writes);
+    for write in writes {
+        let set = matches!(&write.variable, Some(variable) if variable.value.is_some());
+        if set && !names.contains(&write.name) {
+            names.push(write.name.clone());
+        }
+    }
+    names
+}
+
+/// Value of `name` in the innermost call, then in the main program
+fn value(state: &ProgramState, name: &str) -> Option<String> {
+    let find = |variables: &[VariableView]| {
+        variables
+            .iter()
+            .find(|variable| variable.name == name)
+            .map(VariableView::value_text)
+    };
+    state
+        .call_stack
+        .last()
+        .and_then(|frame| {
+            frame
+                .parameters
+                .iter()
+                .find(|parameter| parameter.variable.name == name)
+                .map(|parameter| parameter.variable.value_text())
+                .or_else(|| find(&frame.locals))
+        })
+        .or_else(|| find(&state.globals))
+}
+
+fn csv_cell(text: &str) -> String {
+    match text.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
+
+fn html_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::interpreter;
+
+    const SOURCE: &str =
+        "алг\nнач\nцел а := 1\nнц пока а < 3\nа := а + 1\nкц\nлит s\ns := \"a|b,c\"\nкон";
+
+    #[test]
+    fn program_ending_in_an_assignment() {
+        let (mut interpreter, _) = interpreter(SOURCE, &[]);
+        let table = interpreter.trace_table(&[]);
+        assert_eq!(
+            table.to_markdown(),
+            "| Шаг | Строка | Оператор | а | s | Условие |\n\
+             | --- | --- | --- | --- | --- | --- |\n\
+             | 1 | 3 | цел а := 1 | 1 |  |  |\n\
+             | 2 | 4 | нц пока а < 3 | 1 |  | да |\n\
+             | 3 | 5 | а := а + 1 | 2 |  |  |\n\
+             | 4 | 4 | нц пока а < 3 | 2 |  | да |\n\
+             | 5 | 5 | а := а + 1 | 3 |  |  |\n\
+             | 6 | 4 | нц пока а < 3 | 3 |  | нет |\n\
+             | 7 | 8 | s := \"a\\|b,c\" | 3 | \"a\\|b,c\" |  |\n"
+        );
+        let csv = table.to_csv();
+        assert_eq!(
+            csv.lines().last(),
+            Some("7,8,\"s := \"\"a|b,c\"\"\",3,\"\"\"a|b,c\"\"\",")
+        );
+    }
+
+    fn one_row(statement: &str, value: &str, error: &str) -> TraceTable {
+        TraceTable {
+            variables: vec!["s".to_string()],
+            rows: vec![TraceRow {
+                step: 1,
+                line: 3,
+                statement: statement.to_string(),
+                values: vec![Some(value.to_string())],
+                condition: None,
+            }],
+            error: Some(error.to_string()),
+        }
+    }
+
+    #[test]
+    fn csv_quotes_commas_quotes_and_line_breaks() {
+        let table = one_row("s := \"a,b\"", "x\ny", "Ошибка: \"нет\"");
+        assert_eq!(
+            table.to_csv(),
+            "Шаг,Строка,Оператор,s,Условие\n\
+             1,3,\"s := \"\"a,b\"\"\",\"x\ny\",\n\
+             \"Ошибка: \"\"нет\"\"\"\n"
+        );
+        let plain = one_row("s := 1", "1", "Ошибка");
+        assert_eq!(
+            plain.to_csv(),
+            "Шаг,Строка,Оператор,s,Условие\n1,3,s := 1,1,\nОшибка\n"
+        );
+    }
+
+    #[test]
+    fn html_escapes_markup() {
+        let table = one_row("s := \"<b>&\"", "<b>&", "a > b");
+        assert_eq!(
+            table.to_html(),
+            "<table>\n\
+             <tr><th>Шаг</th><th>Строка</th><th>Оператор</th><th>s</th><th>Условие</th></tr>\n\
+             <tr><td>1</td><td>3</td><td>s := &quot;&lt;b&gt;&amp;&quot;</td><td>&lt;b&gt;&amp;</td><td></td></tr>\n\
+             </table>\n\
+             <p>a &gt; b</p>\n"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Types that namespaces add to the language, like `файл` of `Файлы`.
+//!
+//! A namespace registers a [`CustomType`] with
+//! [`crate::ast::Namespace::register_type`]. Once the namespace is imported,
+//! at the start or with `использовать`, the name of the type declares
+//! variables, parameters and results like `цел` does, see
+//! [`crate::resolver`]. Values of the type are [`Literal::Custom`] holding a
+//! [`CustomValue`], which knows how to compare and print itself. Operators,
+//! values of other types it accepts and how `ввод` reads it are up to the
+//! type.
+
+use std::{any::Any, fmt, rc::Rc};
+
+use crate::{ast::Literal, lexer::Operator, messages::Error};
+
+/// Value of a [`CustomType`]. Values travel with runtime events, hence
+/// `Send + Sync`
+pub trait CustomValue: Any + fmt::Debug + Send + Sync {
+    /// Name of the type of the value, as its [`CustomType`] is registered
+    fn type_name(&self) -> &'static str;
+
+    /// `==` and `<>` of two values of the type
+    fn equals(&self, other: &dyn CustomValue) -> bool;
+
+    /// Value as `вывод` prints it
+    fn to_output_string(&self) -> String;
+}
+
+impl dyn CustomValue {
+    pub fn downcast_ref<T: CustomValue>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
+}
+
+impl PartialEq for dyn CustomValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other)
+    }
+}
+
+/// `left op right` where at least one operand has the type. `None` when the
+/// type doesn't define the operation
+pub type Operators = Rc<dyn Fn(&Literal, Operator, &Literal) -> Option<Result<Literal, Error>>>;
+
+/// Value of the type made from a value of another type, `None` if the type
+/// doesn't accept it
+pub type Conversion = Rc<dyn Fn(&Literal) -> Option<Literal>>;
+
+/// Value of the type written as text, `None` if the text isn't one
+pub type TextParser = Rc<dyn Fn(&str) -> Option<Literal>>;
+
+#[derive(Clone)]
+pub struct CustomType {
+    /// Name that declares values of the type, a single word
+    pub name: &'static str,
+    pub operators: Option<Operators>,
+    /// Applied where a value is assigned, initializes a variable or is
+    /// passed as an argument
+    pub conversion: Option<Conversion>,
+    /// Used by `ввод`, which reads the rest of the line like for `лит`
+    pub parser: Option<TextParser>,
+}
+
+impl CustomType {
+    /// Type whose values can only be compared
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            operators: None,
+            conversion: None,
+            parser: None,
+        }
+    }
+
+    pub fn with_operators(
+        mut self,
+        operators: impl Fn(&Literal, Operator, &Literal) -> Option<Result<Literal, Error>> + 'static,
+    ) -> Self {
+        self.operators = Some(Rc::new(operators));
+        self
+    }
+
+    pub fn with_conversion(
+        mut self,
+        conversion: impl Fn(&Literal) -> Option<Literal> + 'static,
+    ) -> Self {
+        self.conversion = Some(Rc::new(conversion));
+        self
+    }
+
+    pub fn with_parser(mut self, parser: impl Fn(&str) -> Option<Literal> + 'static) -> Self {
+        self.parser = Some(Rc::new(parser));
+        self
+    }
+
+    /// Result of `left op right` if the type defines it
+    pub fn apply(
+        &self,
+        left: &Literal,
+        op: Operator,
+        right: &Literal,
+    ) -> Option<Result<Literal, Error>> {
+        self.operators.as_ref()?(left, op, right)
+    }
+
+    /// `value` as a value of the type if the type accepts it
+    pub fn convert(&self, value: &Literal) -> Option<Literal> {
+        self.conversion.as_ref()?(value)
+    }
+
+    pub fn parse(&self, text: &str) -> Option<Literal> {
+        self.parser.as_ref()?(text)
+    }
+}
+
+impl fmt::Debug for CustomType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomType")
+            .field("name", &self.name)
+            .finish()
+    }
+}
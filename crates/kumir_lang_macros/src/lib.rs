@@ -0,0 +1,243 @@
+//! `#[kumir_alg]`, which binds a Rust function as a Kumir native algorithm.
+//! Re-exported by `kumir_lang`, see `kumir_lang::bind` for the types it
+//! accepts.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    FnArg, Ident, ItemFn, LitStr, Pat, ReturnType, Type, TypeReference, ext::IdentExt,
+    parse_macro_input, spanned::Spanned,
+};
+
+/// Marks a `&mut` parameter as `аргрез` instead of `рез`
+const ARGUMENT_RESULT: &str = "argres";
+
+/// Keeps the function and adds `<name>_alg`, which returns the Kumir name
+/// and the `NativeFunction` calling the function:
+///
+/// ```ignore
+/// #[kumir_alg("слева свободно")]
+/// fn free_left(&self) -> Result<bool, String> { … }
+///
+/// let (name, function) = robot.free_left_alg();
+/// namespace.register_native_function(name, function);
+/// ```
+///
+/// Parameters are `арг` of their type, `&mut` ones are `рез` starting from
+/// the default value, or `аргрез` with `#[argres]`. A `&Environment`
+/// parameter gets the scope of the call. The function returns nothing, a
+/// value, or a `Result` of those with the error message. Methods are called
+/// on a clone of `self` made when the algorithm is created.
+#[proc_macro_attribute]
+pub fn kumir_alg(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let name = parse_macro_input!(attr as LitStr);
+    let mut function = parse_macro_input!(item as ItemFn);
+    match expand(&name, &mut function) {
+        Ok(alg) => quote! {
+            #function
+            #alg
+        }
+        .into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+enum Param {
+    Environment,
+    Argument { name: String, ty: Type },
+    Result { name: String, ty: Type },
+    ArgumentResult { name: String, ty: Type },
+}
+
+fn expand(name: &LitStr, function: &mut ItemFn) -> syn::Result<TokenStream2> {
+    let signature = &function.sig;
+    if !signature.generics.params.is_empty() || signature.asyncness.is_some() {
+        return Err(syn::Error::new(
+            signature.span(),
+            "a Kumir algorithm can't be generic or async",
+        ));
+    }
+
+    let mut receiver = None;
+    let mut params = vec![];
+    for input in function.sig.inputs.iter_mut() {
+        match input {
+            FnArg::Receiver(this) => receiver = Some(this.clone()),
+            FnArg::Typed(typed) => {
+                let argument_result = typed
+                    .attrs
+                    .iter()
+                    .any(|attr| attr.path().is_ident(ARGUMENT_RESULT));
+                typed
+                    .attrs
+                    .retain(|attr| !attr.path().is_ident(ARGUMENT_RESULT));
+                let Pat::Ident(ident) = typed.pat.as_ref() else {
+                    return Err(syn::Error::new(
+                        typed.pat.span(),
+                        "a parameter of a Kumir algorithm needs a name",
+                    ));
+                };
+                let name = ident.ident.unraw().to_string();
+                params.push(match typed.ty.as_ref() {
+                    Type::Reference(reference) if is_environment(reference) => Param::Environment,
+                    Type::Reference(TypeReference {
+                        mutability: Some(_),
+                        elem,
+                        ..
+                    }) => {
+                        let ty = elem.as_ref().clone();
+                        if argument_result {
+                            Param::ArgumentResult { name, ty }
+                        } else {
+                            Param::Result { name, ty }
+                        }
+                    }
+                    Type::Reference(reference) => {
+                        return Err(syn::Error::new(
+                            reference.span(),
+                            "only `&Environment` and `&mut` parameters can be references",
+                        ));
+                    }
+                    ty => Param::Argument {
+                        name,
+                        ty: ty.clone(),
+                    },
+                });
+            }
+        }
+    }
+
+    let kumir = quote!(::kumir_lang);
+    let values = Ident::new("kumir_values", Span::call_site());
+    let environment = Ident::new("kumir_environment", Span::call_site());
+    let mut declarations = vec![];
+    let mut reads = vec![];
+    let mut args = vec![];
+    let mut writes = vec![];
+    //Index of the value of a parameter, `&Environment` has none
+    let mut position = 0usize;
+    for (index, param) in params.iter().enumerate() {
+        let arg = format_ident!("kumir_arg{index}");
+        let value = position;
+        if !matches!(param, Param::Environment) {
+            position += 1;
+        }
+        let declaration = |result_type: TokenStream2, param_name: &String, ty: &Type| {
+            quote! {
+                (
+                    #param_name,
+                    #kumir::lexer::FunctionParamType::#result_type,
+                    <#ty as #kumir::bind::KumirType>::TYPE,
+                )
+            }
+        };
+        match param {
+            Param::Environment => args.push(quote!(#environment)),
+            Param::Argument { name, ty } => {
+                declarations.push(declaration(quote!(ArgumentParam), name, ty));
+                reads.push(quote! {
+                    let #arg: #ty = #kumir::bind::argument(#values, #value)?;
+                });
+                args.push(quote!(#arg));
+            }
+            Param::Result { name, ty } | Param::ArgumentResult { name, ty } => {
+                if let Param::Result { .. } = param {
+                    declarations.push(declaration(quote!(ResultParam), name, ty));
+                    reads.push(quote! {
+                        let mut #arg: #ty = ::std::default::Default::default();
+                    });
+                } else {
+                    declarations.push(declaration(quote!(ArgumentResultParam), name, ty));
+                    reads.push(quote! {
+                        let mut #arg: #ty = #kumir::bind::argument(#values, #value)?;
+                    });
+                }
+                args.push(quote!(&mut #arg));
+                writes.push(quote! {
+                    #values[#value] = ::std::option::Option::Some(
+                        #kumir::bind::KumirType::into_literal(#arg),
+                    );
+                });
+            }
+        }
+    }
+
+    let function_name = &function.sig.ident;
+    let alg_name = format_ident!("{}_alg", function_name.unraw());
+    let return_type = match &function.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let (this, call, alg_receiver) = match &receiver {
+        None => (quote!(), quote!(#function_name), quote!()),
+        Some(receiver) => {
+            let this = Ident::new("kumir_this", Span::call_site());
+            let setup = match (&receiver.reference, &receiver.mutability) {
+                (Some(_), Some(_)) => quote!(let mut #this = ::std::clone::Clone::clone(self);),
+                _ => quote!(let #this = ::std::clone::Clone::clone(self);),
+            };
+            let call = match receiver.reference {
+                Some(_) => quote!(#this.#function_name),
+                None => quote!(::std::clone::Clone::clone(&#this).#function_name),
+            };
+            (setup, call, quote!(&self))
+        }
+    };
+    let values_param = match params
+        .iter()
+        .all(|param| matches!(param, Param::Environment))
+    {
+        true => format_ident!("_{values}"),
+        false => values.clone(),
+    };
+    let environment_param = match params
+        .iter()
+        .any(|param| matches!(param, Param::Environment))
+    {
+        true => environment.clone(),
+        false => format_ident!("_{environment}"),
+    };
+    let visibility = &function.vis;
+    let doc = format!(
+        "`{}` as a native algorithm calling [`Self::{function_name}`]",
+        name.value()
+    );
+    let doc = match receiver {
+        Some(_) => doc,
+        None => doc.replace("Self::", ""),
+    };
+
+    Ok(quote! {
+        #[doc = #doc]
+        #visibility fn #alg_name(#alg_receiver) -> (&'static str, #kumir::ast::NativeFunction) {
+            #this
+            (
+                #name,
+                #kumir::bind::alg(
+                    &[#(#declarations),*],
+                    <#return_type as #kumir::bind::AlgResult>::TYPE,
+                    move |
+                        #environment_param: &#kumir::ast::Environment,
+                        #values_param: &mut [::std::option::Option<#kumir::ast::Literal>],
+                    | {
+                        #(#reads)*
+                        let kumir_result = #call(#(#args),*);
+                        #(#writes)*
+                        #kumir::bind::AlgResult::into_result(kumir_result)
+                    },
+                ),
+            )
+        }
+    })
+}
+
+fn is_environment(reference: &TypeReference) -> bool {
+    reference.mutability.is_none()
+        && matches!(
+            reference.elem.as_ref(),
+            Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == "Environment")
+        )
+}
@@ -1,6 +1,8 @@
-use log::info;
+use log::{error, info};
 
-use crate::{FuncResult, RobotRequirements, RuntimeRequirementsTrait};
+use crate::{
+    FuncResult, MemoryFs, NativeFs, RobotRequirements, RuntimeRequirementsTrait, VirtualFs,
+};
 
 pub struct ConsoleRuntimeRequirements;
 
@@ -8,6 +10,17 @@ impl RuntimeRequirementsTrait for ConsoleRuntimeRequirements {
     fn println(&self, message: &str) {
         info!("{}", message)
     }
+
+    /// The directory the runtime was started in
+    fn file_system(&self) -> Box<dyn VirtualFs> {
+        match NativeFs::new(".") {
+            Ok(fs) => Box::new(fs),
+            Err(err) => {
+                error!("Working directory is unavailable, files are kept in memory: {err}");
+                Box::new(MemoryFs::default())
+            }
+        }
+    }
 }
 
 impl RobotRequirements for ConsoleRuntimeRequirements {
@@ -85,4 +98,12 @@ impl RobotRequirements for ConsoleRuntimeRequirements {
         info!("Not colored");
         Ok(Some(true))
     }
+
+    fn undo(&self, actions: usize) {
+        info!("Undo {actions} actions");
+    }
+
+    fn redo(&self, actions: usize) {
+        info!("Redo {actions} actions");
+    }
 }
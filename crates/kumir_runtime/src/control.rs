@@ -0,0 +1,415 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    time::Duration,
+};
+
+use kumir_lang::messages::{Error, Message};
+use web_time::Instant;
+
+use crate::events::RuntimeEvent;
+
+/// Handle for controlling a running program from another thread.
+///
+/// Runtimes call [`RuntimeControl::checkpoint`] before every statement of
+/// the program, in Kumir and in Python. While the program is paused the
+/// checkpoint blocks, [`RuntimeControl::step`] lets exactly one checkpoint
+/// pass and [`RuntimeControl::step_back`] shows the statement before. Actor
+/// delays wait on the same handle, so stopping or changing the delay takes
+/// effect immediately.
+///
+/// The handle also carries the program's [`RuntimeEvent`]s to subscribers
+/// and the lines typed in for `ввод`/`input`.
+#[derive(Clone, Default)]
+pub struct RuntimeControl {
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    kill_flag: Arc<AtomicBool>,
+    subscribers: Mutex<Vec<Sender<RuntimeEvent>>>,
+}
+
+#[derive(Default)]
+struct State {
+    paused: bool,
+    /// Checkpoints allowed to pass while paused
+    steps: usize,
+    /// Steps back asked for while paused
+    steps_back: usize,
+    stopped: bool,
+    delay: Duration,
+    input: VecDeque<String>,
+}
+
+impl RuntimeControl {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Flag the interpreters poll, set by [`RuntimeControl::stop`]
+    pub fn kill_flag(&self) -> Arc<AtomicBool> {
+        self.shared.kill_flag.clone()
+    }
+
+    pub fn pause(&self) {
+        self.update(|state| state.paused = true);
+    }
+
+    pub fn resume(&self) {
+        self.update(|state| {
+            state.paused = false;
+            state.steps = 0;
+            state.steps_back = 0;
+        });
+    }
+
+    /// Shows the statement before the one a paused program is at, and
+    /// restores the actors to how they were there. Only Kumir programs
+    /// recording a trace step back, see
+    /// [`crate::RuntimeRequirementsTrait::record_trace`]
+    pub fn step_back(&self) {
+        self.update(|state| {
+            if state.paused {
+                state.steps_back += 1;
+            }
+        });
+    }
+
+    /// Runs one step of a paused program, pauses a running one
+    pub fn step(&self) {
+        self.update(|state| {
+            if state.paused {
+                state.steps += 1;
+            } else {
+                state.paused = true;
+            }
+        });
+    }
+
+    pub fn stop(&self) {
+        self.update(|state| {
+            state.stopped = true;
+            state.paused = false;
+        });
+        self.shared.kill_flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Delay after every actor action, applies to a delay that is already
+    /// running too
+    pub fn set_delay(&self, delay: Duration) {
+        self.update(|state| state.delay = delay);
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.state().delay
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state().paused
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.state().stopped
+    }
+
+    /// Whether both handles control the same program
+    pub fn ptr_eq(&self, other: &RuntimeControl) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /// Events of the program from now on, each subscriber gets all of them
+    pub fn subscribe(&self) -> Receiver<RuntimeEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.shared.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub(crate) fn publish(&self, event: RuntimeEvent) {
+        self.shared
+            .subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// Line of input for the program, without the line break
+    pub fn send_input(&self, line: &str) {
+        self.update(|state| state.input.push_back(line.to_string()));
+    }
+
+    /// Waits for [`RuntimeControl::send_input`], fails when the program is
+    /// stopped
+    pub fn read_line(&self) -> Result<String, Error> {
+        let mut state = self.state();
+        if state.input.is_empty() {
+            drop(state);
+            self.publish(RuntimeEvent::InputRequested);
+            state = self.state();
+        }
+        loop {
+            if state.stopped {
+                return Err(Message::UserInterrupt.into());
+            }
+            if let Some(line) = state.input.pop_front() {
+                return Ok(line);
+            }
+            state = self.shared.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Blocks while the program is paused, steps back are ignored
+    pub fn checkpoint(&self) {
+        while self.checkpoint_or_back() {}
+    }
+
+    /// Blocks while the program is paused, `true` when it is woken by a
+    /// [`RuntimeControl::step_back`] instead of being let through
+    pub(crate) fn checkpoint_or_back(&self) -> bool {
+        let waiting = |state: &State| {
+            state.paused && state.steps == 0 && state.steps_back == 0 && !state.stopped
+        };
+        let mut state = self.state();
+        if waiting(&state) {
+            drop(state);
+            self.publish(RuntimeEvent::Paused);
+            state = self.state();
+        }
+        while waiting(&state) {
+            state = self.shared.changed.wait(state).unwrap();
+        }
+        if state.stopped {
+            return false;
+        }
+        if state.paused && state.steps_back > 0 {
+            state.steps_back -= 1;
+            return true;
+        }
+        if state.paused && state.steps > 0 {
+            state.steps -= 1;
+        }
+        false
+    }
+
+    /// Sleeps for `duration`, returns early when the program is stopped
+    pub fn sleep(&self, duration: Duration) {
+        self.wait_for(|_| duration);
+    }
+
+    /// Sleeps for `duration` of running time: time spent paused doesn't
+    /// count. Returns early when the program is stopped
+    pub fn sleep_running(&self, duration: Duration) {
+        let mut left = duration;
+        let mut state = self.state();
+        while !state.stopped && !left.is_zero() {
+            if state.paused && state.steps == 0 {
+                state = self.shared.changed.wait(state).unwrap();
+                continue;
+            }
+            let start = Instant::now();
+            state = self.shared.changed.wait_timeout(state, left).unwrap().0;
+            left = left.saturating_sub(start.elapsed());
+        }
+    }
+
+    /// Sleeps for the current actor delay
+    pub fn actor_delay(&self) {
+        self.wait_for(|state| state.delay);
+    }
+
+    fn wait_for(&self, duration: impl Fn(&State) -> Duration) {
+        let start = Instant::now();
+        let mut state = self.state();
+        while !state.stopped {
+            let elapsed = start.elapsed();
+            let duration = duration(&state);
+            if elapsed >= duration {
+                break;
+            }
+            state = self
+                .shared
+                .changed
+                .wait_timeout(state, duration - elapsed)
+                .unwrap()
+                .0;
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap()
+    }
+
+    fn update(&self, update: impl FnOnce(&mut State)) {
+        update(&mut self.state());
+        self.shared.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::AtomicUsize,
+        thread::{self, JoinHandle},
+    };
+
+    use super::*;
+
+    /// Waits for the next [`RuntimeEvent::Paused`]
+    fn paused(events: &Receiver<RuntimeEvent>) {
+        loop {
+            match events.recv_timeout(Duration::from_secs(5)) {
+                Ok(RuntimeEvent::Paused) => return,
+                Ok(_) => {}
+                Err(err) => panic!("not paused: {err}"),
+            }
+        }
+    }
+
+    /// Thread passing `count` checkpoints, counting them
+    fn checkpoints(control: &RuntimeControl, count: usize) -> (Arc<AtomicUsize>, JoinHandle<()>) {
+        let passed = Arc::new(AtomicUsize::new(0));
+        let counter = passed.clone();
+        let control = control.clone();
+        let thread = thread::spawn(move || {
+            for _ in 0..count {
+                control.checkpoint();
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        (passed, thread)
+    }
+
+    #[test]
+    fn paused_checkpoint_lets_steps_through() {
+        let control = RuntimeControl::new();
+        let events = control.subscribe();
+        control.pause();
+        let (passed, thread) = checkpoints(&control, 3);
+        paused(&events);
+        assert_eq!(passed.load(Ordering::SeqCst), 0);
+        control.step();
+        paused(&events);
+        assert_eq!(passed.load(Ordering::SeqCst), 1);
+        control.step();
+        paused(&events);
+        assert_eq!(passed.load(Ordering::SeqCst), 2);
+        control.resume();
+        thread.join().unwrap();
+        assert_eq!(passed.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn step_pauses_a_running_program() {
+        let control = RuntimeControl::new();
+        control.step();
+        assert!(control.is_paused());
+        control.resume();
+        assert!(!control.is_paused());
+    }
+
+    #[test]
+    fn step_back_wakes_only_a_paused_checkpoint() {
+        let control = RuntimeControl::new();
+        control.step_back();
+        assert!(!control.checkpoint_or_back());
+
+        control.pause();
+        control.step_back();
+        control.step();
+        assert!(control.checkpoint_or_back());
+        assert!(!control.checkpoint_or_back());
+    }
+
+    #[test]
+    fn stop_releases_a_paused_checkpoint_and_input() {
+        let control = RuntimeControl::new();
+        let events = control.subscribe();
+        control.pause();
+        let (passed, thread) = checkpoints(&control, 2);
+        paused(&events);
+        control.stop();
+        thread.join().unwrap();
+        assert_eq!(passed.load(Ordering::SeqCst), 2);
+        assert!(control.is_stopped());
+        assert!(control.kill_flag().load(Ordering::Relaxed));
+        assert_eq!(control.read_line(), Err(Message::UserInterrupt.into()));
+    }
+
+    #[test]
+    fn input_is_read_in_order() {
+        let control = RuntimeControl::new();
+        let events = control.subscribe();
+        control.send_input("1");
+        let reader = control.clone();
+        let thread = thread::spawn(move || [reader.read_line(), reader.read_line()]);
+        assert!(matches!(
+            events.recv_timeout(Duration::from_secs(5)),
+            Ok(RuntimeEvent::InputRequested)
+        ));
+        control.send_input("2");
+        assert_eq!(
+            thread.join().unwrap(),
+            [Ok("1".to_string()), Ok("2".to_string())]
+        );
+    }
+
+    #[test]
+    fn stop_interrupts_the_actor_delay() {
+        let control = RuntimeControl::new();
+        control.set_delay(Duration::from_secs(60));
+        let waiting = control.clone();
+        let start = Instant::now();
+        let thread = thread::spawn(move || waiting.actor_delay());
+        thread::sleep(Duration::from_millis(50));
+        control.stop();
+        thread.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+
+    #[test]
+    fn shorter_delay_ends_a_running_one() {
+        let control = RuntimeControl::new();
+        control.set_delay(Duration::from_secs(60));
+        let waiting = control.clone();
+        let start = Instant::now();
+        let thread = thread::spawn(move || waiting.actor_delay());
+        thread::sleep(Duration::from_millis(50));
+        control.set_delay(Duration::ZERO);
+        thread.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+
+    #[test]
+    fn paused_time_does_not_count_as_sleep() {
+        let control = RuntimeControl::new();
+        control.pause();
+        let sleeping = control.clone();
+        let start = Instant::now();
+        let thread = thread::spawn(move || sleeping.sleep_running(Duration::from_millis(20)));
+        thread::sleep(Duration::from_millis(200));
+        assert!(!thread.is_finished());
+        control.resume();
+        thread.join().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn stop_interrupts_a_sleep() {
+        let control = RuntimeControl::new();
+        let sleeping = control.clone();
+        let start = Instant::now();
+        let thread = thread::spawn(move || sleeping.sleep_running(Duration::from_secs(60)));
+        thread::sleep(Duration::from_millis(50));
+        control.pause();
+        control.stop();
+        thread.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+}
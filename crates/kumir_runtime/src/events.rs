@@ -0,0 +1,73 @@
+use std::fmt;
+
+use kumir_lang::{
+    debugger::CallFrame,
+    error,
+    lexer::Span,
+    limits::Limit,
+    messages::{Error, Locale},
+};
+
+/// What a running program does, published to every subscriber of
+/// [`crate::control::RuntimeControl::subscribe`]
+#[derive(Debug, PartialEq, Clone)]
+pub enum RuntimeEvent {
+    Started {
+        /// Seed of the random functions, to replay the run with
+        /// [`crate::RuntimeRequirementsTrait::seed`]. `None` where the
+        /// language has no seeded randomness
+        seed: Option<u64>,
+    },
+    /// Text printed by the program, line breaks included
+    Output(String),
+    /// The program waits for a line passed to
+    /// [`crate::control::RuntimeControl::send_input`]
+    InputRequested,
+    /// Kumir statement that is about to be executed
+    StatementEntered(Span),
+    /// Recorded statement shown after
+    /// [`crate::control::RuntimeControl::step_back`], the program stays at
+    /// the last [`RuntimeEvent::StatementEntered`] and is shown there again
+    /// with that event
+    Rewound(Span),
+    ActorCommand {
+        name: String,
+        args: Vec<String>,
+        /// Returned value, `None` for actions
+        result: Result<Option<String>, Error>,
+    },
+    Paused,
+    Finished(Result<(), Diagnostic>),
+}
+
+/// Error that ended the program
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub message: Error,
+    /// Line of the statement that failed, if known
+    pub line: Option<usize>,
+    /// Set when the program was stopped by its
+    /// [`crate::ExecutionLimits`] rather than failed on its own
+    pub limit_exceeded: Option<Limit>,
+    /// Kumir algorithm calls that led to the error, the innermost one is
+    /// the last
+    pub call_stack: Vec<CallFrame>,
+}
+
+impl Diagnostic {
+    /// Stable code of the error, see [`kumir_lang::messages`]
+    pub fn code(&self) -> Option<&'static str> {
+        self.message.code()
+    }
+
+    /// The error with its line and call stack, in the given language
+    pub fn text(&self, locale: Locale) -> String {
+        error::render(self.line, &self.message, &self.call_stack, locale)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text(Locale::Ru))
+    }
+}
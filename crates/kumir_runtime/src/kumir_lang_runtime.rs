@@ -1,58 +1,265 @@
-use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
-use std::{cell::RefCell, rc::Rc};
-
-use indexmap::IndexMap;
-use kumir_lang::ast::Namespace;
-use kumir_lang::{ast::Literal, lexer::TypeDefinition};
-use kumir_lang::{
-    ast::{Environment, NativeFunction},
-    interpreter::Interpreter,
-};
+use std::{cell::RefCell, rc::Rc, sync::atomic::AtomicBool, time::Duration};
+
+use kumir_lang::ast::{AstNode, Environment, Namespace};
+use kumir_lang::clock::{Clock, SystemClock};
+use kumir_lang::debugger::{StepMode, Stop, StopHandler};
+use kumir_lang::interpreter::Interpreter;
+use kumir_lang::io::ProgramIo;
+use kumir_lang::kumir_alg;
+use kumir_lang::messages::Error;
+use kumir_lang::trace::Trace;
 use log::info;
 
-use crate::{Lang, Runtime, RuntimeRequirements};
-
-macro_rules! register {
-    ($interpreter:expr, $requirements:expr, $method_name:ident, $func_name:expr) => {
-        let req = $requirements.clone();
-        $interpreter.register_native_function(
-            $func_name,
-            NativeFunction {
-                params: IndexMap::new(),
-                return_type: None,
-                native_function: Rc::new(RefCell::new(move |_: &Rc<RefCell<Environment>>| {
-                    let res = req.$method_name();
-                    match res {
-                        Ok(_) => Ok(None),
-                        Err(arg) => Err(arg),
-                    }
-                })),
-            },
-        );
-    };
-    ($interpreter:expr, $requirements:expr, $method_name:ident, $func_name:expr, $return_type:ident) => {
-        let req = $requirements.clone();
-        $interpreter.register_native_function(
-            $func_name,
-            NativeFunction {
-                params: IndexMap::new(),
-                return_type: Some(TypeDefinition::$return_type),
-                native_function: Rc::new(RefCell::new(move |_: &Rc<RefCell<Environment>>| {
-                    let res = req.$method_name();
-                    match res {
-                        Ok(arg) => Ok(Some(Literal::$return_type(arg.unwrap()))),
-                        Err(arg) => Err(arg),
-                    }
-                })),
-            },
-        )
-    };
+use crate::{
+    ExecutionLimits, FuncResult, Lang, Runtime, RuntimeRequirements,
+    control::RuntimeControl,
+    events::{Diagnostic, RuntimeEvent},
+};
+
+pub const ROBOT: &str = "Робот";
+
+/// Commands of `Робот`, each one is reported to the host as a
+/// [`RuntimeEvent::ActorCommand`]
+#[derive(Clone)]
+struct Robot {
+    requirements: RuntimeRequirements,
+    control: RuntimeControl,
+}
+
+impl Robot {
+    /// Available after `использовать Робот`
+    fn namespace(&self) -> Namespace {
+        let mut namespace = Namespace::default();
+        for (name, function) in [
+            self.move_up_alg(),
+            self.move_down_alg(),
+            self.move_left_alg(),
+            self.move_right_alg(),
+            self.paint_alg(),
+            self.free_left_alg(),
+            self.free_right_alg(),
+            self.free_above_alg(),
+            self.free_below_alg(),
+            self.wall_left_alg(),
+            self.wall_right_alg(),
+            self.wall_above_alg(),
+            self.wall_below_alg(),
+            self.colored_alg(),
+            self.not_colored_alg(),
+        ] {
+            namespace.register_native_function(name, function);
+        }
+        namespace
+    }
+
+    fn action(&self, name: &str, result: FuncResult<()>) -> Result<(), Error> {
+        self.control.publish(RuntimeEvent::ActorCommand {
+            name: name.to_string(),
+            args: vec![],
+            result: result.clone().map(|_| None),
+        });
+        self.control.actor_delay();
+        result.map(|_| ())
+    }
+
+    fn condition(&self, name: &str, result: FuncResult<bool>) -> Result<bool, Error> {
+        self.control.publish(RuntimeEvent::ActorCommand {
+            name: name.to_string(),
+            args: vec![],
+            result: result
+                .clone()
+                .map(|value| value.map(|value| value.to_string())),
+        });
+        result.map(Option::unwrap_or_default)
+    }
+
+    #[kumir_alg("вверх")]
+    fn move_up(&self) -> Result<(), Error> {
+        self.action("вверх", self.requirements.move_up())
+    }
+
+    #[kumir_alg("вниз")]
+    fn move_down(&self) -> Result<(), Error> {
+        self.action("вниз", self.requirements.move_down())
+    }
+
+    #[kumir_alg("влево")]
+    fn move_left(&self) -> Result<(), Error> {
+        self.action("влево", self.requirements.move_left())
+    }
+
+    #[kumir_alg("вправо")]
+    fn move_right(&self) -> Result<(), Error> {
+        self.action("вправо", self.requirements.move_right())
+    }
+
+    #[kumir_alg("закрасить")]
+    fn paint(&self) -> Result<(), Error> {
+        self.action("закрасить", self.requirements.paint())
+    }
+
+    #[kumir_alg("слева свободно")]
+    fn free_left(&self) -> Result<bool, Error> {
+        self.condition("слева свободно", self.requirements.free_left())
+    }
+
+    #[kumir_alg("справа свободно")]
+    fn free_right(&self) -> Result<bool, Error> {
+        self.condition("справа свободно", self.requirements.free_right())
+    }
+
+    #[kumir_alg("сверху свободно")]
+    fn free_above(&self) -> Result<bool, Error> {
+        self.condition("сверху свободно", self.requirements.free_above())
+    }
+
+    #[kumir_alg("снизу свободно")]
+    fn free_below(&self) -> Result<bool, Error> {
+        self.condition("снизу свободно", self.requirements.free_below())
+    }
+
+    #[kumir_alg("слева стена")]
+    fn wall_left(&self) -> Result<bool, Error> {
+        self.condition("слева стена", self.requirements.wall_left())
+    }
+
+    #[kumir_alg("справа стена")]
+    fn wall_right(&self) -> Result<bool, Error> {
+        self.condition("справа стена", self.requirements.wall_right())
+    }
+
+    #[kumir_alg("стена сверху")]
+    fn wall_above(&self) -> Result<bool, Error> {
+        self.condition("стена сверху", self.requirements.wall_above())
+    }
+
+    #[kumir_alg("стена снизу")]
+    fn wall_below(&self) -> Result<bool, Error> {
+        self.condition("стена снизу", self.requirements.wall_below())
+    }
+
+    #[kumir_alg("клетка закрашена")]
+    fn colored(&self) -> Result<bool, Error> {
+        self.condition("клетка закрашена", self.requirements.colored())
+    }
+
+    #[kumir_alg("клетка чистая")]
+    fn not_colored(&self) -> Result<bool, Error> {
+        self.condition("клетка чистая", self.requirements.not_colored())
+    }
+}
+
+/// `Робот` on `requirements` for a program run outside of a runtime, e.g. on
+/// a copy of the field. Its commands don't wait for the actor delay
+pub fn robot_namespace(requirements: RuntimeRequirements) -> Namespace {
+    Robot {
+        requirements,
+        control: RuntimeControl::new(),
+    }
+    .namespace()
+}
+
+/// Waits on the [`RuntimeControl`] before every statement. With a trace, a
+/// paused program steps back through it and forward again up to where it is
+struct ControlStopHandler {
+    control: RuntimeControl,
+    requirements: RuntimeRequirements,
+}
+
+impl ControlStopHandler {
+    /// Shows the recorded statement at `index`, the robot goes back or
+    /// forward from `actions` done to the ones done before it. Returns the
+    /// actions done there
+    fn show(&self, trace: &Trace, index: usize, actions: usize) -> usize {
+        let shown = trace.actions_before(index, ROBOT);
+        if shown < actions {
+            self.requirements.undo(actions - shown);
+        } else if shown > actions {
+            self.requirements.redo(shown - actions);
+        }
+        if let Some(entry) = trace.entries().get(index) {
+            let event = match index + 1 < trace.len() {
+                true => RuntimeEvent::Rewound(entry.span),
+                false => RuntimeEvent::StatementEntered(entry.span),
+            };
+            self.control.publish(event);
+        }
+        shown
+    }
+}
+
+impl StopHandler for ControlStopHandler {
+    fn started(&mut self, _: &Rc<RefCell<Environment>>) -> StepMode {
+        StepMode::Statement
+    }
+
+    fn stopped(&mut self, stop: &Stop, environment: &Rc<RefCell<Environment>>) -> StepMode {
+        self.control
+            .publish(RuntimeEvent::StatementEntered(stop.span));
+        let trace = environment.borrow().trace.clone();
+        //Statement shown and the robot actions done up to it
+        let mut rewound = None;
+        loop {
+            let back = self.control.checkpoint_or_back();
+            let Some(trace) = &trace else {
+                if back {
+                    continue;
+                }
+                break;
+            };
+            let trace = trace.borrow();
+            let present = trace.len().saturating_sub(1);
+            let (index, actions) = match (back, rewound) {
+                (true, None) => (present, trace.actions_before(present, ROBOT)),
+                (_, Some(rewound)) => rewound,
+                (false, None) => break,
+            };
+            let index = match back {
+                true => index.saturating_sub(1),
+                //Running on or stepping forward to the present
+                false if !self.control.is_paused() => present,
+                false => index + 1,
+            };
+            let actions = self.show(&trace, index, actions);
+            rewound = (index < present).then_some((index, actions));
+            if rewound.is_none() && !self.control.is_paused() {
+                break;
+            }
+        }
+        StepMode::Statement
+    }
+}
+
+/// `вывод` and `ввод` through the [`RuntimeControl`]
+struct ControlIo(RuntimeControl);
+
+impl ProgramIo for ControlIo {
+    fn write(&mut self, text: &str) {
+        self.0.publish(RuntimeEvent::Output(text.to_string()));
+    }
+
+    fn read_line(&mut self) -> Result<String, Error> {
+        self.0.read_line()
+    }
+}
+
+/// Real time, `ждать` doesn't count the time the program is paused
+struct ControlClock(RuntimeControl);
+
+impl Clock for ControlClock {
+    fn time_of_day(&self) -> Duration {
+        SystemClock.time_of_day()
+    }
+
+    fn sleep(&self, duration: Duration, _: &AtomicBool) {
+        self.0.sleep_running(duration);
+    }
 }
 
 pub struct KumirLangRuntime {
     requirements: RuntimeRequirements,
     interpreter: Interpreter,
+    control: RuntimeControl,
 }
 
 impl Runtime for KumirLangRuntime {
@@ -60,109 +267,323 @@ impl Runtime for KumirLangRuntime {
         requirements: RuntimeRequirements,
         _: Lang,
         code: String,
-        kill_flag: Arc<AtomicBool>,
-    ) -> Result<Self, String> {
+        limits: ExecutionLimits,
+    ) -> Result<(Self, RuntimeControl), Error> {
         info!("Initializing KuMir lang runtime");
-        let mut interpreter = Interpreter::new_from_string(&code, kill_flag)?;
-        interpreter.register_namespace("Робот", {
-            let mut namespace: Namespace = Default::default();
-            namespace.register_native_function(
-                "test",
-                NativeFunction {
-                    params: IndexMap::new(),
-                    return_type: Some(TypeDefinition::Bool),
-                    native_function: Rc::new(RefCell::new(move |_: &Rc<RefCell<Environment>>| {
-                        Ok(Some(Literal::Bool(true)))
-                    })),
-                },
-            );
-            namespace
+        let control = RuntimeControl::new();
+        let mut interpreter = Interpreter::new(AstNode::Program(vec![]), control.kill_flag());
+        interpreter.attach_debugger(ControlStopHandler {
+            control: control.clone(),
+            requirements: requirements.clone(),
         });
-        register!(&mut interpreter, requirements, move_up, "вверх");
-        register!(&mut interpreter, requirements, move_down, "вниз");
-        register!(&mut interpreter, requirements, move_left, "влево");
-        register!(&mut interpreter, requirements, move_right, "вправо");
-        register!(&mut interpreter, requirements, paint, "закрасить");
-        register!(
-            &mut interpreter,
-            requirements,
-            free_left,
-            "слева свободно",
-            Bool
-        );
-        register!(
-            &mut interpreter,
-            requirements,
-            free_right,
-            "справа свободно",
-            Bool
-        );
-        register!(
-            &mut interpreter,
-            requirements,
-            free_above,
-            "сверху свободно",
-            Bool
+        if requirements.record_trace() {
+            interpreter.enable_trace();
+        }
+        interpreter.set_io(ControlIo(control.clone()));
+        interpreter.set_limits(limits);
+        interpreter.set_fs(requirements.file_system());
+        interpreter.set_clock(
+            requirements
+                .clock()
+                .unwrap_or_else(|| Rc::new(ControlClock(control.clone()))),
         );
-        register!(
-            &mut interpreter,
-            requirements,
-            free_below,
-            "снизу свободно",
-            Bool
-        );
-        register!(
-            &mut interpreter,
-            requirements,
-            wall_left,
-            "слева стена",
-            Bool
-        );
-        register!(
-            &mut interpreter,
-            requirements,
-            wall_right,
-            "справа стена",
-            Bool
-        );
-        register!(
-            &mut interpreter,
-            requirements,
-            wall_above,
-            "стена сверху",
-            Bool
-        );
-        register!(
-            &mut interpreter,
-            requirements,
-            wall_below,
-            "стена снизу",
-            Bool
-        );
-        register!(
-            &mut interpreter,
-            requirements,
-            colored,
-            "клетка закрашена",
-            Bool
-        );
-        register!(
-            &mut interpreter,
-            requirements,
-            not_colored,
-            "клетка чистая",
-            Bool
-        );
-        Ok(Self {
-            requirements,
-            interpreter,
-        })
+        if let Some(seed) = requirements.seed() {
+            interpreter.set_seed(seed);
+        }
+        let robot = Robot {
+            requirements: requirements.clone(),
+            control: control.clone(),
+        };
+        interpreter.register_namespace(ROBOT, robot.namespace());
+        interpreter.load_string(&code)?;
+        Ok((
+            Self {
+                requirements,
+                interpreter,
+                control: control.clone(),
+            },
+            control,
+        ))
     }
 
-    fn run(&mut self) -> Result<(), String> {
+    fn run(&mut self) -> Result<(), Diagnostic> {
         self.requirements
             .println("Hello from runtime in requirements");
-        self.interpreter.run()?;
-        Ok(())
+        self.control.publish(RuntimeEvent::Started {
+            seed: Some(self.interpreter.seed()),
+        });
+        let result = self.interpreter.run().map_err(|error| Diagnostic {
+            line: Some(error.line()),
+            message: error.message,
+            limit_exceeded: self
+                .interpreter
+                .environment
+                .borrow()
+                .budget
+                .borrow()
+                .exceeded(),
+            call_stack: error.call_stack,
+        });
+        self.control.publish(RuntimeEvent::Finished(result.clone()));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver},
+    };
+    use std::thread;
+
+    use super::*;
+    use crate::{RobotRequirements, RuntimeRequirementsTrait};
+
+    /// Robot in an empty field that counts what is done and taken back
+    #[derive(Default)]
+    struct CountingRobot {
+        done: AtomicUsize,
+        undone: AtomicUsize,
+        redone: AtomicUsize,
+    }
+
+    impl CountingRobot {
+        fn act(&self) -> FuncResult<()> {
+            self.done.fetch_add(1, Ordering::SeqCst);
+            Ok(None)
+        }
+
+        fn counts(&self) -> (usize, usize, usize) {
+            (
+                self.done.load(Ordering::SeqCst),
+                self.undone.load(Ordering::SeqCst),
+                self.redone.load(Ordering::SeqCst),
+            )
+        }
+    }
+
+    impl RuntimeRequirementsTrait for CountingRobot {
+        fn println(&self, _: &str) {}
+
+        fn record_trace(&self) -> bool {
+            true
+        }
+    }
+
+    impl RobotRequirements for CountingRobot {
+        fn move_up(&self) -> FuncResult<()> {
+            self.act()
+        }
+        fn move_down(&self) -> FuncResult<()> {
+            self.act()
+        }
+        fn move_left(&self) -> FuncResult<()> {
+            self.act()
+        }
+        fn move_right(&self) -> FuncResult<()> {
+            self.act()
+        }
+        fn paint(&self) -> FuncResult<()> {
+            self.act()
+        }
+        fn free_right(&self) -> FuncResult<bool> {
+            Ok(Some(true))
+        }
+        fn free_left(&self) -> FuncResult<bool> {
+            Ok(Some(true))
+        }
+        fn free_above(&self) -> FuncResult<bool> {
+            Ok(Some(true))
+        }
+        fn free_below(&self) -> FuncResult<bool> {
+            Ok(Some(true))
+        }
+        fn wall_left(&self) -> FuncResult<bool> {
+            Ok(Some(false))
+        }
+        fn wall_right(&self) -> FuncResult<bool> {
+            Ok(Some(false))
+        }
+        fn wall_above(&self) -> FuncResult<bool> {
+            Ok(Some(false))
+        }
+        fn wall_below(&self) -> FuncResult<bool> {
+            Ok(Some(false))
+        }
+        fn colored(&self) -> FuncResult<bool> {
+            Ok(Some(false))
+        }
+        fn not_colored(&self) -> FuncResult<bool> {
+            Ok(Some(true))
+        }
+        fn undo(&self, actions: usize) {
+            self.done.fetch_sub(actions, Ordering::SeqCst);
+            self.undone.fetch_add(actions, Ordering::SeqCst);
+        }
+        fn redo(&self, actions: usize) {
+            self.done.fetch_add(actions, Ordering::SeqCst);
+            self.redone.fetch_add(actions, Ordering::SeqCst);
+        }
+    }
+
+    /// Starts `code` paused on another thread
+    fn start(
+        robot: &Arc<CountingRobot>,
+        code: &str,
+    ) -> (
+        RuntimeControl,
+        Receiver<RuntimeEvent>,
+        thread::JoinHandle<Result<(), Diagnostic>>,
+    ) {
+        let (sender, receiver) = mpsc::channel();
+        let requirements = robot.clone();
+        let code = code.to_string();
+        //The interpreter stays on the thread it runs on
+        let run = thread::spawn(move || {
+            let (mut runtime, control) =
+                KumirLangRuntime::init(requirements, Lang::Kumir, code, Default::default())
+                    .unwrap();
+            control.pause();
+            let (ready, go) = mpsc::channel();
+            sender.send((control, ready)).unwrap();
+            go.recv().unwrap();
+            runtime.run()
+        });
+        let (control, ready) = receiver.recv().unwrap();
+        let events = control.subscribe();
+        ready.send(()).unwrap();
+        (control, events, run)
+    }
+
+    /// Events up to the next pause
+    fn until_paused(events: &Receiver<RuntimeEvent>) -> Vec<RuntimeEvent> {
+        let mut seen = vec![];
+        loop {
+            match events.recv_timeout(Duration::from_secs(5)) {
+                Ok(RuntimeEvent::Paused) => return seen,
+                Ok(event) => seen.push(event),
+                Err(_) => panic!("not paused after {seen:?}"),
+            }
+        }
+    }
+
+    fn lines(events: &[RuntimeEvent]) -> Vec<String> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                RuntimeEvent::StatementEntered(span) => Some(format!("at {}", span.start.line)),
+                RuntimeEvent::Rewound(span) => Some(format!("back {}", span.start.line)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    const THREE_STEPS: &str = "использовать Робот\nалг\nнач\n  вправо\n  вниз\n  закрасить\nкон\n";
+
+    /// Steps a paused program from its start to the first command
+    fn step_to_commands(control: &RuntimeControl, events: &Receiver<RuntimeEvent>) {
+        assert_eq!(lines(&until_paused(events)), ["at 1"]);
+        control.step();
+        assert_eq!(lines(&until_paused(events)), ["at 2"]);
+        control.step();
+        assert_eq!(lines(&until_paused(events)), ["at 2"]);
+        control.step();
+        assert_eq!(lines(&until_paused(events)), ["at 4"]);
+    }
+
+    #[test]
+    fn steps_back_and_forward_take_the_robot_along() {
+        let robot = Arc::new(CountingRobot::default());
+        let (control, events, run) = start(&robot, THREE_STEPS);
+        step_to_commands(&control, &events);
+        control.step();
+        until_paused(&events);
+        control.step();
+        assert_eq!(lines(&until_paused(&events)), ["at 6"]);
+        assert_eq!(robot.counts(), (2, 0, 0));
+
+        control.step_back();
+        assert_eq!(lines(&until_paused(&events)), ["back 5"]);
+        assert_eq!(robot.counts(), (1, 1, 0));
+        control.step_back();
+        assert_eq!(lines(&until_paused(&events)), ["back 4"]);
+        assert_eq!(robot.counts(), (0, 2, 0));
+        control.step_back();
+        assert_eq!(lines(&until_paused(&events)), ["back 2"]);
+        assert_eq!(robot.counts(), (0, 2, 0));
+
+        control.step();
+        assert_eq!(lines(&until_paused(&events)), ["back 4"]);
+        assert_eq!(robot.counts(), (0, 2, 0));
+        control.step();
+        assert_eq!(lines(&until_paused(&events)), ["back 5"]);
+        assert_eq!(robot.counts(), (1, 2, 1));
+        control.step();
+        assert_eq!(lines(&until_paused(&events)), ["at 6"]);
+        assert_eq!(robot.counts(), (2, 2, 2));
+
+        control.resume();
+        assert!(run.join().unwrap().is_ok());
+        assert_eq!(robot.counts(), (3, 2, 2));
+    }
+
+    #[test]
+    fn resuming_while_rewound_returns_to_the_present() {
+        let robot = Arc::new(CountingRobot::default());
+        let (control, events, run) = start(&robot, THREE_STEPS);
+        step_to_commands(&control, &events);
+        control.step();
+        until_paused(&events);
+        control.step();
+        until_paused(&events);
+        control.step_back();
+        until_paused(&events);
+        control.step_back();
+        until_paused(&events);
+        assert_eq!(robot.counts(), (0, 2, 0));
+
+        control.resume();
+        assert!(run.join().unwrap().is_ok());
+        assert_eq!(robot.counts(), (3, 2, 2));
+        let rest: Vec<_> = events.try_iter().collect();
+        assert_eq!(lines(&rest), ["at 6"]);
+    }
+
+    #[test]
+    fn events_come_in_the_order_of_the_run() {
+        let robot = Arc::new(CountingRobot::default());
+        let code = "вывод \"а\"\nцел б\nб := 1 / 0\n".to_string();
+        let (mut runtime, control) =
+            KumirLangRuntime::init(robot, Lang::Kumir, code, Default::default()).unwrap();
+        let events = control.subscribe();
+        let result = runtime.run();
+        let events: Vec<_> = events
+            .try_iter()
+            .map(|event| match event {
+                RuntimeEvent::Started { .. } => "started".to_string(),
+                RuntimeEvent::Output(text) => format!("output {text}"),
+                RuntimeEvent::StatementEntered(span) => format!("at {}", span.start.line),
+                RuntimeEvent::Finished(Err(diagnostic)) => format!(
+                    "failed at {:?} with {:?}",
+                    diagnostic.line,
+                    diagnostic.code()
+                ),
+                event => format!("{event:?}"),
+            })
+            .collect();
+        assert_eq!(
+            events,
+            [
+                "started",
+                "at 1",
+                "output а",
+                "at 2",
+                "at 3",
+                "failed at Some(3) with Some(\"R009\")",
+            ]
+        );
+        assert_eq!(result.unwrap_err().line, Some(3));
     }
 }
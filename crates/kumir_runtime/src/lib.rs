@@ -1,10 +1,20 @@
 pub mod console_runtime_requirements;
+pub mod control;
+pub mod events;
 pub mod kumir_lang_runtime;
 pub mod python_runtime;
+
+pub use kumir_lang::clock::{Clock, SystemClock, VirtualClock};
+pub use kumir_lang::fs::{MemoryFs, NativeFs, VirtualFs};
+pub use kumir_lang::limits::{ExecutionLimits, Limit};
 use std::fmt;
-use std::sync::atomic::AtomicBool;
+use std::rc::Rc;
 use std::sync::Arc;
 
+use kumir_lang::messages::Error;
+
+use crate::control::RuntimeControl;
+use crate::events::Diagnostic;
 use crate::kumir_lang_runtime::KumirLangRuntime;
 use crate::python_runtime::PythonRuntime;
 
@@ -35,25 +45,21 @@ impl Runtime for Target {
         requirements: RuntimeRequirements,
         lang: Lang,
         code: String,
-        kill_flag: Arc<AtomicBool>,
-    ) -> Result<Self, String> {
+        limits: ExecutionLimits,
+    ) -> Result<(Self, RuntimeControl), Error> {
         match lang {
-            Lang::Kumir => Ok(Target::KumirLang(KumirLangRuntime::init(
-                requirements,
-                lang,
-                code,
-                kill_flag,
-            )?)),
-            Lang::Python => Ok(Target::Python(PythonRuntime::init(
-                requirements,
-                lang,
-                code,
-                kill_flag,
-            )?)),
+            Lang::Kumir => {
+                let (runtime, control) = KumirLangRuntime::init(requirements, lang, code, limits)?;
+                Ok((Target::KumirLang(runtime), control))
+            }
+            Lang::Python => {
+                let (runtime, control) = PythonRuntime::init(requirements, lang, code, limits)?;
+                Ok((Target::Python(runtime), control))
+            }
         }
     }
 
-    fn run(&mut self) -> Result<(), String> {
+    fn run(&mut self) -> Result<(), Diagnostic> {
         match self {
             Target::KumirLang(kumir_lang_runtime) => kumir_lang_runtime.run(),
             Target::Python(python_runtime) => python_runtime.run(),
@@ -64,10 +70,35 @@ impl Runtime for Target {
 }
 
 pub type RuntimeRequirements = Arc<dyn RuntimeRequirementsTrait + 'static>;
-pub type FuncResult<T> = Result<Option<T>, String>;
+pub type FuncResult<T> = Result<Option<T>, Error>;
 
 pub trait RuntimeRequirementsTrait: RobotRequirements + Send + Sync {
     fn println(&self, message: &str);
+
+    /// Files the program reaches with the `Файлы` actor, an empty
+    /// [`MemoryFs`] unless the host shares a directory
+    fn file_system(&self) -> Box<dyn VirtualFs> {
+        Box::new(MemoryFs::default())
+    }
+
+    /// Clock of `ждать` and `время`, e.g. a [`VirtualClock`] for grading.
+    /// `None` is the real time, where `ждать` also waits while the program
+    /// is paused
+    fn clock(&self) -> Option<Rc<dyn Clock>> {
+        None
+    }
+
+    /// Seed of the random functions, `None` picks a new one every run. The
+    /// seed used is reported by [`events::RuntimeEvent::Started`]
+    fn seed(&self) -> Option<u64> {
+        None
+    }
+
+    /// Whether a Kumir program records every statement, so it can step back
+    /// while paused. Off by default, the record grows with every step
+    fn record_trace(&self) -> bool {
+        false
+    }
 }
 
 pub trait RobotRequirements {
@@ -86,17 +117,28 @@ pub trait RobotRequirements {
     fn wall_below(&self) -> FuncResult<bool>;
     fn colored(&self) -> FuncResult<bool>;
     fn not_colored(&self) -> FuncResult<bool>;
+    /// Takes back the last `actions` moves and paintings, when the program
+    /// steps back
+    fn undo(&self, actions: usize);
+    /// Repeats `actions` moves and paintings taken back by
+    /// [`RobotRequirements::undo`]
+    fn redo(&self, actions: usize);
 }
 
 pub trait Runtime {
+    /// Prepares `code` to run, the returned [`RuntimeControl`] pauses,
+    /// steps and stops it from any thread. The program is stopped once it
+    /// exceeds `limits`
     fn init(
         requirements: RuntimeRequirements,
         lang: Lang,
         code: String,
-        kill_flag: Arc<AtomicBool>,
-    ) -> Result<Self, String>
+        limits: ExecutionLimits,
+    ) -> Result<(Self, RuntimeControl), Error>
     where
         Self: Sized;
 
-    fn run(&mut self) -> Result<(), String>;
+    /// Error of a program stopped by its limits has
+    /// [`Diagnostic::limit_exceeded`] set
+    fn run(&mut self) -> Result<(), Diagnostic>;
 }
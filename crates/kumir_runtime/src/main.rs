@@ -1,14 +1,23 @@
-use std::sync::Arc;
+use std::{io::Write, sync::Arc, thread};
 
+use kumir_lang::messages::Locale;
 use kumir_runtime::{
-    Runtime, Target, console_runtime_requirements::ConsoleRuntimeRequirements,
+    Runtime, Target, console_runtime_requirements::ConsoleRuntimeRequirements, events::RuntimeEvent,
 };
 use log::{error, info};
 
 pub fn main() {
     env_logger::init();
+    let locale = match std::env::var("KUMIR_LOCALE").map(|locale| locale.parse::<Locale>()) {
+        Ok(Ok(locale)) => locale,
+        Ok(Err(err)) => {
+            error!("{err}");
+            Locale::default()
+        }
+        Err(_) => Locale::default(),
+    };
     info!("Starting runtime");
-    let mut target = Target::init(
+    let (mut target, control) = Target::init(
         Arc::new(ConsoleRuntimeRequirements {}),
         kumir_runtime::Lang::Kumir,
         include_str!("test.kum").to_string(),
@@ -16,7 +25,30 @@ pub fn main() {
     )
     .unwrap();
 
-    if let Err(err) = target.run() {
-        error!("{err}")
-    };
+    let events = control.subscribe();
+    let printer = thread::spawn(move || {
+        for event in events {
+            match event {
+                RuntimeEvent::Output(text) => {
+                    print!("{text}");
+                    let _ = std::io::stdout().flush();
+                }
+                RuntimeEvent::InputRequested => {
+                    let mut line = String::new();
+                    let _ = std::io::stdin().read_line(&mut line);
+                    control.send_input(line.trim_end_matches(['\r', '\n']));
+                }
+                RuntimeEvent::Finished(result) => {
+                    if let Err(diagnostic) = result {
+                        error!("{}", diagnostic.text(locale));
+                    }
+                    break;
+                }
+                event => info!("{event:?}"),
+            }
+        }
+    });
+
+    let _ = target.run();
+    let _ = printer.join();
 }
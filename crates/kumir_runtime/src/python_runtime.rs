@@ -1,27 +1,65 @@
 use std::{
+    convert::Infallible,
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicBool, Ordering},
     },
     time::Duration,
 };
 
-use crate::{Lang, Runtime, RuntimeRequirements};
+use crate::{
+    ExecutionLimits, Lang, Limit, Runtime, RuntimeRequirements,
+    control::RuntimeControl,
+    events::{Diagnostic, RuntimeEvent},
+};
+use kumir_lang::{
+    limits::Budget,
+    messages::{Error, Message},
+};
 use log::{error, info};
 use rustpython::vm::{PyResult, VirtualMachine};
 use rustpython_vm::{
     AsObject,
-    builtins::{PyBaseException, PyStr},
-    compiler,
-    object::{PyObjectRef, PyRef},
+    builtins::{PyBaseException, PyCode, PyStr, PyStrRef},
+    compiler::{
+        self, LinearLocator,
+        codegen::compile::compile_top,
+        parser::{
+            ast::{
+                Expr, ExprCall, ExprContext, ExprName, Identifier, Ranged, Stmt, StmtIf,
+                fold::{self, Fold},
+            },
+            text_size::TextRange,
+        },
+    },
+    function::OptionalArg,
+    object::{PyObject, PyObjectRef, PyRef},
+    signal::UserSignal,
 };
 
+/// Sends `print` output through the event stream
+const OUTPUT_PRELUDE: &str = "
+import sys
+
+class KumirOutput:
+    def write(self, text):
+        kumir_output(text)
+        return len(text)
+
+    def flush(self):
+        pass
+
+sys.stdout = KumirOutput()
+";
+
 pub struct PythonRuntime {
     requirements: RuntimeRequirements,
     interpreter: rustpython_vm::Interpreter,
     code: String,
-    #[allow(dead_code)]
-    kill_flag: Arc<AtomicBool>,
+    control: RuntimeControl,
+    budget: Arc<Mutex<Budget>>,
+    /// Whether the program runs, limits are only checked then
+    running: Arc<AtomicBool>,
 }
 
 fn parse_rustpython_error(err: PyRef<PyBaseException>) -> String {
@@ -44,16 +82,33 @@ fn parse_rustpython_error(err: PyRef<PyBaseException>) -> String {
     format!("{error_text}\n{traceback}")
 }
 
+/// Actor commands, actions without a result are followed by the actor delay
 macro_rules! new_function {
-    ($vm:expr, $requirements:expr, $name:ident) => {
+    ($vm:expr, $requirements:expr, $control:expr, $name:ident) => {
         $vm.new_function(
             stringify!($name),
             move |vm: &VirtualMachine| -> PyResult<PyObjectRef> {
-                match $requirements.$name() {
-                    Err(arg) => Err(vm.new_runtime_error(arg)),
+                if $control.is_stopped() {
+                    return Err(
+                        vm.new_exception_empty(vm.ctx.exceptions.keyboard_interrupt.to_owned())
+                    );
+                }
+                let result = $requirements.$name();
+                $control.publish(RuntimeEvent::ActorCommand {
+                    name: stringify!($name).to_string(),
+                    args: vec![],
+                    result: result
+                        .clone()
+                        .map(|result| result.map(|result| format!("{result:?}"))),
+                });
+                match result {
+                    Err(arg) => Err(vm.new_runtime_error(arg.to_string())),
                     Ok(arg) => match arg {
                         Some(result) => Ok(vm.new_pyobj(result)),
-                        None => Ok(vm.new_pyobj(vm.ctx.none())),
+                        None => {
+                            $control.actor_delay();
+                            Ok(vm.new_pyobj(vm.ctx.none()))
+                        }
                     },
                 }
             },
@@ -62,12 +117,13 @@ macro_rules! new_function {
 }
 
 macro_rules! register_module {
-    ($vm:expr, $scope:expr, $requirements:expr, $module_name:ident, $($name:ident),+) => {
+    ($vm:expr, $scope:expr, $requirements:expr, $control:expr, $module_name:ident, $($name:ident),+) => {
         let module_dict = $vm.ctx.new_dict();
 
         $(
             let req = $requirements.clone();
-            let func = new_function!($vm, req, $name);
+            let control = $control.clone();
+            let func = new_function!($vm, req, control, $name);
             module_dict.set_item(stringify!($name), func.into(), &$vm).unwrap();
         )+
 
@@ -94,8 +150,9 @@ macro_rules! register_module {
 
 #[allow(unused_macros)]
 macro_rules! register_function {
-    ($vm:expr, $scope:expr, $requirements:expr, $name:ident) => {
-        let func = new_function!($vm, $requirements.clone(), $name);
+    ($vm:expr, $scope:expr, $requirements:expr, $control:expr, $name:ident) => {
+        let control = $control.clone();
+        let func = new_function!($vm, $requirements.clone(), control, $name);
         match $scope
             .globals
             .set_item(stringify!($name), func.into(), &$vm)
@@ -117,17 +174,29 @@ impl Runtime for PythonRuntime {
         requirements: RuntimeRequirements,
         _: Lang,
         code: String,
-        kill_flag: Arc<AtomicBool>,
-    ) -> Result<Self, String> {
+        limits: ExecutionLimits,
+    ) -> Result<(Self, RuntimeControl), Error> {
         info!("Initializing Python runtime");
-        let kill_flag_clone = kill_flag.clone();
+        let control = RuntimeControl::new();
+        let budget = Arc::new(Mutex::new(Budget::new(limits)));
+        let running = Arc::new(AtomicBool::new(false));
+        let (signal_tx, signal_rx) = rustpython_vm::signal::user_signal_channel();
+        let kill_flag = control.kill_flag();
+        let watched_budget = budget.clone();
+        let watched_running = running.clone();
         let interpreter = rustpython::InterpreterConfig::new()
             .init_stdlib()
             .init_hook(Box::new(move |vm: &mut VirtualMachine| {
-                let (signal_tx, signal_rx) = rustpython_vm::signal::user_signal_channel();
-                let kill_flag = kill_flag_clone;
+                let budget = watched_budget;
+                let running = watched_running;
                 wasm_thread::spawn(move || {
                     loop {
+                        //Raised again until the program ends, so catching it
+                        //doesn't help
+                        if running.load(Ordering::Relaxed) && limit_exceeded(&budget) {
+                            let _ = signal_tx.send(raise_limit_exceeded(budget.clone()));
+                        }
+
                         if kill_flag.load(Ordering::Relaxed) {
                             kill_flag.store(false, Ordering::Relaxed);
                             let _ =
@@ -147,58 +216,386 @@ impl Runtime for PythonRuntime {
             }))
             .interpreter();
 
-        Ok(Self {
-            requirements,
-            interpreter,
-            code,
-            kill_flag,
-        })
-    }
-
-    fn run(&mut self) -> Result<(), String> {
-        self.interpreter.enter(|vm: &VirtualMachine| {
-            let scope = vm.new_scope_with_builtins();
-
-            register_module!(
-                vm,
-                &scope,
-                self.requirements.clone(),
-                robot,
-                move_up,
-                move_down,
-                move_left,
-                move_right,
-                paint,
-                free_right,
-                free_left,
-                free_above,
-                free_below,
-                wall_left,
-                wall_right,
-                wall_above,
-                wall_below,
-                colored,
-                not_colored
-            );
-
-            let source = self.code.as_str();
-            let code_obj = vm
-                .compile(source, compiler::Mode::Exec, "<embedded>".to_owned())
-                .map_err(|err| format!("{:?}", vm.new_syntax_error(&err, Some(source))))?;
-
-            vm.run_code_obj(code_obj, scope).map_err(|err| {
-                let mut output = String::new();
-
-                match vm.write_exception_inner(&mut output, &err) {
-                    Ok(_) => return output,
-                    Err(args) => {
-                        error!("Failed to use ready parser: {:?}", args);
-                        parse_rustpython_error(err)
-                    }
+        Ok((
+            Self {
+                requirements,
+                interpreter,
+                code,
+                control: control.clone(),
+                budget,
+                running,
+            },
+            control,
+        ))
+    }
+
+    fn run(&mut self) -> Result<(), Diagnostic> {
+        self.control.publish(RuntimeEvent::Started { seed: None });
+        let result = self
+            .interpreter
+            .enter(|vm: &VirtualMachine| self.run_code(vm));
+        self.running.store(false, Ordering::Relaxed);
+        self.control.publish(RuntimeEvent::Finished(result.clone()));
+        result
+    }
+}
+
+impl PythonRuntime {
+    fn run_code(&self, vm: &VirtualMachine) -> Result<(), Diagnostic> {
+        let scope = vm.new_scope_with_builtins();
+
+        register_module!(
+            vm,
+            &scope,
+            self.requirements.clone(),
+            self.control,
+            robot,
+            move_up,
+            move_down,
+            move_left,
+            move_right,
+            paint,
+            free_right,
+            free_left,
+            free_above,
+            free_below,
+            wall_left,
+            wall_right,
+            wall_above,
+            wall_below,
+            colored,
+            not_colored
+        );
+        self.redirect_io(vm);
+
+        let source = self.code.as_str();
+        let code_obj = self.compile(vm, source).map_err(|err| Diagnostic {
+            message: format!("{:?}", vm.new_syntax_error(&err, Some(source))).into(),
+            line: None,
+            limit_exceeded: None,
+            call_stack: vec![],
+        })?;
+
+        self.apply_limits(vm);
+        let result = vm.run_code_obj(code_obj, scope);
+        result.map_err(|err| {
+            let mut budget = self.budget.lock().unwrap();
+            if err.fast_isinstance(vm.ctx.exceptions.recursion_error)
+                && let Some(max_call_depth) = budget.limits.max_call_depth
+            {
+                let _ = budget.exceed(Limit::CallDepth(max_call_depth));
+            }
+            let line = innermost_line(&err);
+            let mut output = String::new();
+
+            let message = match vm.write_exception_inner(&mut output, &err) {
+                Ok(_) => output,
+                Err(args) => {
+                    error!("Failed to use ready parser: {:?}", args);
+                    parse_rustpython_error(err)
                 }
-            })?;
+            };
+            Diagnostic {
+                message: message.into(),
+                line,
+                limit_exceeded: budget.exceeded(),
+                call_stack: vec![],
+            }
+        })?;
+
+        Ok(())
+    }
+
+    /// Compiles `source`, every statement calls `kumir_step` first, see
+    /// [`StepCounter`]
+    fn compile(
+        &self,
+        vm: &VirtualMachine,
+        source: &str,
+    ) -> Result<PyRef<PyCode>, compiler::CompileError> {
+        let source_path = "<embedded>".to_owned();
+        let mut locator = LinearLocator::new(source);
+        let ast = compiler::parser::parse(source, compiler::parser::Mode::Module, &source_path)
+            .map_err(|err| locator.locate_error(err))?;
+        let ast = StepCounter.fold_mod(ast).unwrap_or_else(|err| match err {});
+        let ast = locator.fold_mod(ast).unwrap_or_else(|err| match err {});
+        let code = compile_top(&ast, source_path, compiler::Mode::Exec, vm.compile_opts())
+            .map_err(compiler::CompileError::from)?;
+        Ok(vm.ctx.new_code(code))
+    }
+
+    /// Starts counting the budget. Before every statement `kumir_step`
+    /// waits while the program is paused, counts the statement and measures
+    /// the variables of its frame. The time is checked by the watchdog thread
+    fn apply_limits(&self, vm: &VirtualMachine) {
+        let limits = {
+            let mut budget = self.budget.lock().unwrap();
+            budget.start();
+            budget.limits
+        };
+        if let Some(max_call_depth) = limits.max_call_depth {
+            //The module itself takes a frame
+            vm.recursion_limit.set(max_call_depth + 1);
+        }
+        let budget = self.budget.clone();
+        let control = self.control.clone();
+        let step = vm.new_function("kumir_step", move |vm: &VirtualMachine| -> PyResult<bool> {
+            control.checkpoint();
+            if control.is_stopped() {
+                return Err(vm.new_exception_empty(vm.ctx.exceptions.keyboard_interrupt.to_owned()));
+            }
+            let len = match (limits.max_value_len, vm.current_frame()) {
+                (Some(_), Some(frame)) => longest_local(frame.as_object(), vm)?,
+                _ => 0,
+            };
+            let mut locked = budget.lock().unwrap();
+            if locked.step().and_then(|_| locked.check_len(len)).is_err() {
+                drop(locked);
+                raise_limit_exceeded(budget.clone())(vm)?;
+            }
+            Ok(true)
+        });
+        if let Err(err) = vm.builtins.set_attr("kumir_step", step, vm) {
+            error!("Failed to install the statement checkpoint: {:?}", err);
+        }
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    /// `print` publishes [`RuntimeEvent::Output`], `input` waits for
+    /// [`RuntimeControl::send_input`]
+    fn redirect_io(&self, vm: &VirtualMachine) {
+        let scope = vm.new_scope_with_builtins();
+        let control = self.control.clone();
+        let output = vm.new_function("kumir_output", move |text: PyStrRef| {
+            control.publish(RuntimeEvent::Output(text.as_str().to_string()));
+        });
+        let control = self.control.clone();
+        let input = vm.new_function(
+            "input",
+            move |prompt: OptionalArg<PyStrRef>, vm: &VirtualMachine| -> PyResult<String> {
+                if let OptionalArg::Present(prompt) = prompt {
+                    control.publish(RuntimeEvent::Output(prompt.as_str().to_string()));
+                }
+                control.read_line().map_err(|_| {
+                    vm.new_exception_empty(vm.ctx.exceptions.keyboard_interrupt.to_owned())
+                })
+            },
+        );
+
+        let result = scope
+            .globals
+            .set_item("kumir_output", output.into(), vm)
+            .and_then(|_| vm.builtins.set_attr("input", input, vm))
+            .and_then(|_| vm.run_code_string(scope, OUTPUT_PRELUDE, "<kumir>".to_owned()));
+        if let Err(err) = result {
+            error!("Failed to redirect input and output: {:?}", err);
+        }
+    }
+}
+
+/// Line of the innermost frame of the traceback
+fn innermost_line(err: &PyRef<PyBaseException>) -> Option<usize> {
+    let mut traceback = err.traceback()?;
+    loop {
+        let next = traceback.next.lock().clone();
+        match next {
+            Some(next) => traceback = next,
+            None => return Some(traceback.lineno.to_usize()),
+        }
+    }
+}
+
+/// Checks the time, whether any limit is exceeded
+fn limit_exceeded(budget: &Mutex<Budget>) -> bool {
+    let mut budget = budget.lock().unwrap();
+    let _ = budget.check_time();
+    budget.exceeded().is_some()
+}
+
+fn raise_limit_exceeded(budget: Arc<Mutex<Budget>>) -> UserSignal {
+    Box::new(move |vm: &VirtualMachine| -> PyResult<()> {
+        let limit = budget.lock().unwrap().exceeded();
+        let message = match limit {
+            Some(limit) => Message::LimitExceeded(limit).to_string(),
+            None => Message::UserInterrupt.to_string(),
+        };
+        Err(vm.new_exception_msg(vm.ctx.exceptions.keyboard_interrupt.to_owned(), message))
+    })
+}
+
+/// Wraps every statement into `if kumir_step():`, so a paused program stops
+/// before the next statement and loops without calls count towards the step
+/// limit too
+struct StepCounter;
+
+impl Fold<TextRange> for StepCounter {
+    type TargetU = TextRange;
+    type Error = Infallible;
+    type UserContext = ();
+
+    fn will_map_user(&mut self, _: &TextRange) {}
+
+    fn map_user(&mut self, range: TextRange, _: ()) -> Result<TextRange, Infallible> {
+        Ok(range)
+    }
+
+    fn fold_stmt(&mut self, stmt: Stmt) -> Result<Stmt, Infallible> {
+        let stmt = fold::fold_stmt(self, stmt)?;
+        let range = stmt.range();
+        //Empty, the locator only goes forward through the source
+        let start = TextRange::empty(range.start());
+        let step = ExprCall {
+            range: start,
+            func: Box::new(Expr::Name(ExprName {
+                range: start,
+                id: Identifier::new("kumir_step"),
+                ctx: ExprContext::Load,
+            })),
+            args: vec![],
+            keywords: vec![],
+        };
+        Ok(Stmt::If(StmtIf {
+            range,
+            test: Box::new(Expr::Call(step)),
+            body: vec![stmt],
+            orelse: vec![],
+        }))
+    }
+}
+
+/// Length of the longest string or collection among the frame's variables
+fn longest_local(frame: &PyObject, vm: &VirtualMachine) -> PyResult<usize> {
+    let locals = frame.get_attr("f_locals", vm)?;
+    let values: Vec<PyObjectRef> = vm.call_method(&locals, "values", ())?.try_into_value(vm)?;
+    let types = [
+        vm.ctx.types.str_type,
+        vm.ctx.types.list_type,
+        vm.ctx.types.tuple_type,
+        vm.ctx.types.dict_type,
+        vm.ctx.types.set_type,
+    ];
+    let mut longest = 0;
+    for value in values {
+        if types.iter().any(|class| value.class().is(*class)) {
+            longest = longest.max(value.length(vm)?);
+        }
+    }
+    Ok(longest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console_runtime_requirements::ConsoleRuntimeRequirements;
+
+    fn run(code: &str, limits: ExecutionLimits) -> Result<(), Diagnostic> {
+        let requirements = Arc::new(ConsoleRuntimeRequirements);
+        let (mut runtime, _) =
+            PythonRuntime::init(requirements, Lang::Python, code.to_string(), limits).unwrap();
+        runtime.run()
+    }
+
+    fn exceeded(code: &str, limits: ExecutionLimits) -> Option<Limit> {
+        run(code, limits).err().and_then(|err| err.limit_exceeded)
+    }
+
+    #[test]
+    fn loops_without_calls_count_steps() {
+        let limits = ExecutionLimits {
+            max_steps: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(
+            exceeded("while True:\n    pass\n", limits),
+            Some(Limit::Steps(1000))
+        );
+        assert!(run("x = 0\nwhile x < 10:\n    x += 1\n", limits).is_ok());
+    }
+
+    #[test]
+    fn counted_programs_keep_error_lines() {
+        let limits = ExecutionLimits {
+            max_steps: Some(1000),
+            ..Default::default()
+        };
+        let err = run("x = 1\nif x:\n    y = x / 0\n", limits).unwrap_err();
+        assert_eq!(err.line, Some(3));
+        assert_eq!(err.limit_exceeded, None);
+    }
+
+    #[test]
+    fn long_values_are_stopped() {
+        let limits = ExecutionLimits {
+            max_value_len: Some(5),
+            ..Default::default()
+        };
+        let code = "def f():\n    s = 'a' * 10\n    return len(s)\nf()\n";
+        assert_eq!(exceeded(code, limits), Some(Limit::ValueLen(5)));
+        assert!(run("s = 'a' * 5\nprint(s)\n", limits).is_ok());
+    }
+
+    #[test]
+    fn deep_recursion_is_stopped() {
+        let limits = ExecutionLimits {
+            max_call_depth: Some(10),
+            ..Default::default()
+        };
+        let code = "def f(n):\n    return f(n + 1)\nf(0)\n";
+        assert_eq!(exceeded(code, limits), Some(Limit::CallDepth(10)));
+    }
+
+    /// Output printed until the program pauses or finishes
+    fn until_paused(events: &std::sync::mpsc::Receiver<RuntimeEvent>) -> String {
+        let mut output = String::new();
+        loop {
+            match events.recv_timeout(Duration::from_secs(10)) {
+                Ok(RuntimeEvent::Paused | RuntimeEvent::Finished(_)) => return output,
+                Ok(RuntimeEvent::Output(text)) => output.push_str(&text),
+                Ok(_) => {}
+                Err(err) => panic!("not paused after {output:?}: {err}"),
+            }
+        }
+    }
+
+    #[test]
+    fn step_runs_one_statement() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        //The interpreter stays on the thread it runs on
+        let run = std::thread::spawn(move || {
+            let code = "print(1)\nfor i in range(2):\n    print(i + 2)\n".to_string();
+            let requirements = Arc::new(ConsoleRuntimeRequirements);
+            let (mut runtime, control) =
+                PythonRuntime::init(requirements, Lang::Python, code, Default::default()).unwrap();
+            let events = control.subscribe();
+            control.pause();
+            sender.send((control, events)).unwrap();
+            runtime.run()
+        });
+        let (control, events) = receiver.recv().unwrap();
+        assert_eq!(until_paused(&events), "");
+        for output in ["1\n", "", "2\n", "3\n"] {
+            control.step();
+            assert_eq!(until_paused(&events), output);
+        }
+        assert!(run.join().unwrap().is_ok());
+    }
 
-            Ok(())
-        })
+    #[test]
+    fn stop_ends_a_paused_program() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let run = std::thread::spawn(move || {
+            let code = "while True:\n    pass\n".to_string();
+            let requirements = Arc::new(ConsoleRuntimeRequirements);
+            let (mut runtime, control) =
+                PythonRuntime::init(requirements, Lang::Python, code, Default::default()).unwrap();
+            let events = control.subscribe();
+            sender.send((control, events)).unwrap();
+            runtime.run()
+        });
+        let (control, events) = receiver.recv().unwrap();
+        control.pause();
+        until_paused(&events);
+        control.stop();
+        assert!(run.join().unwrap().is_err());
     }
 }